@@ -19,14 +19,24 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::types::ColorSettings;
+#[cfg(unix_without_macos)]
+use crate::types::{
+    Brightness, Gamma, Temperature, DEFAULT_GAMMA, DEFAULT_TEMPERATURE,
+    MAX_BRIGHTNESS, MAX_GAMMA, MAX_TEMPERATURE, MIN_BRIGHTNESS, MIN_GAMMA,
+    MIN_TEMPERATURE,
+};
+use crate::types::{ColorSettings, DEFAULT_CONTRAST};
 use std::ops::{Deref, DerefMut};
 
+/// Bits of the values of the ramps that are given to the display servers
+pub const FULL_DEPTH: u32 = 16;
+
 #[cfg(unix_without_macos)]
 #[derive(Debug, Clone)]
 pub struct GammaRamps(pub [Vec<u16>; 3]);
 
-#[allow(unused)]
+/// Ramps with values between 0 and 1. All of the calculations are done on
+/// them, and they are converted to integers once at the end
 #[derive(Debug, Clone)]
 pub struct GammaRampsFloat(pub [Vec<f64>; 3]);
 
@@ -34,42 +44,56 @@ pub struct GammaRampsFloat(pub [Vec<f64>; 3]);
 #[derive(Debug, Clone)]
 pub struct GammaRampsWin32<const SIZE: usize>(pub Box<[[u16; SIZE]; 3]>);
 
-// A macro is used to prevent repetition. The same effect can be achieved with
-// Iterator traits, but this is easier to read.
-#[cfg(any(unix_without_macos, windows))]
-macro_rules! colorramp_fill {
-    ($self:ident, $setting:ident) => {
-        let white_point = approximate_white_point($setting);
-        let a = (u16::MAX as u32 + 1) as f64;
-        let f = |y: u16, c: usize| -> u16 {
-            let r = y as f64 / a * *$setting.brght * white_point[c];
-            let r = r.powf(1.0 / $setting.gamma[c]) * a;
-            r as u16
-        };
-
-        for i in 0..$self[0].len() {
-            $self[0][i] = f($self[0][i], 0);
-            $self[1][i] = f($self[1][i], 1);
-            $self[2][i] = f($self[2][i], 2);
-        }
-    };
-}
-
 #[cfg(unix_without_macos)]
 impl GammaRamps {
     // used in vidmode and randr
     pub fn new(ramp_size: u32) -> Self {
-        // Initialize gamma ramps to pure state
-        // if ramp_size == 1024 => ramps == [[0, 64, 128, 192, ..], ..]
-        let a = (u16::MAX as u32 + 1) as f64;
-        let v = (0..ramp_size)
-            .map(|i| (i as f64 / ramp_size as f64 * a) as u16)
-            .collect::<Vec<_>>();
-        Self([v.clone(), v.clone(), v])
+        GammaRampsFloat::new(ramp_size).to_ramps(ramp_size, FULL_DEPTH)
     }
 
-    pub fn colorramp_fill(&mut self, setting: &ColorSettings) {
-        colorramp_fill!(self, setting);
+    /// Estimate the color settings that result in these ramps when they are
+    /// applied on top of linear ramps, by inverting colorramp_fill. Returns
+    /// the settings and the root mean square error of the ramps they produce
+    /// relative to the maximum value
+    pub fn estimate(&self) -> (ColorSettings, f64) {
+        let n = self[0].len();
+        let [(g0, k0), (g1, k1), (g2, k2)] =
+            [0, 1, 2].map(|c| fit_ramp(&self[c]));
+
+        // The white point of the blackbody colors is normalized to have a
+        // maximum of 1.0, so the brightness is the largest factor
+        let brght = k0.max(k1).max(k2).clamp(MIN_BRIGHTNESS, MAX_BRIGHTNESS);
+        let wp = [k0, k1, k2].map(|k| k / brght);
+        let temp = (MIN_TEMPERATURE..=MAX_TEMPERATURE)
+            .min_by(|&t1, &t2| {
+                let d = |t| {
+                    let w = blackbody_white_point(t);
+                    (0..3).map(|c| (w[c] - wp[c]).powi(2)).sum::<f64>()
+                };
+                d(t1).total_cmp(&d(t2))
+            })
+            .unwrap_or(DEFAULT_TEMPERATURE);
+
+        let cs = ColorSettings {
+            temp: Temperature::try_from(temp).unwrap_or_default(),
+            gamma: Gamma::try_from((g0, g1, g2)).unwrap_or_default(),
+            brght: Brightness::try_from(brght).unwrap_or_default(),
+            ..Default::default()
+        };
+        let mut ramps = GammaRampsFloat::new(n as u32);
+        ramps.colorramp_fill(&cs);
+        let ramps = ramps.to_ramps(n as u32, FULL_DEPTH);
+        let sum = (0..3)
+            .flat_map(|c| self[c].iter().zip(&ramps[c]))
+            .map(|(&y1, &y2)| (y1 as f64 - y2 as f64).powi(2))
+            .sum::<f64>();
+        let error = (sum / (3 * n) as f64).sqrt() / u16::MAX as f64;
+        (cs, error)
+    }
+
+    /// Linearly interpolate the ramps to a different size
+    pub fn resample(&self, ramp_size: u32) -> Self {
+        GammaRampsFloat::from(self).to_ramps(ramp_size, FULL_DEPTH)
     }
 }
 
@@ -77,28 +101,50 @@ impl GammaRamps {
 impl<const SIZE: usize> GammaRampsWin32<SIZE> {
     pub fn new() -> Self {
         // Initialize gamma ramps to pure state
-        // if ramp_size == 1024 => ramps == [[0, 64, 128, 192, ..], ..]
-        let a = (u16::MAX as u32 + 1) as f64;
+        let linear = GammaRampsFloat::new(SIZE as u32);
         let mut ramp = [0; SIZE];
-        #[allow(clippy::needless_range_loop)]
-        for i in 0..SIZE {
-            ramp[i] = (i as f64 / SIZE as f64 * a) as u16;
+        for (y, r) in ramp.iter_mut().zip(&linear[0]) {
+            *y = quantize(*r, FULL_DEPTH);
         }
         Self(Box::new([ramp; 3]))
     }
 
     pub fn colorramp_fill(&mut self, setting: &ColorSettings) {
-        colorramp_fill!(self, setting);
+        let mut ramps = GammaRampsFloat(self.0.each_ref().map(|ramp| {
+            ramp.iter().map(|&y| y as f64 / u16::MAX as f64).collect()
+        }));
+        ramps.colorramp_fill(setting);
+        for (ramp, r) in self.0.iter_mut().zip(ramps.0) {
+            for (y, r) in ramp.iter_mut().zip(r) {
+                *y = quantize(r, FULL_DEPTH);
+            }
+        }
     }
 }
 
 impl GammaRampsFloat {
-    #![allow(dead_code)]
+    /// Linear ramps from exactly 0 to exactly 1
+    pub fn new(ramp_size: u32) -> Self {
+        let n = ramp_size as usize;
+        let v = (0..n)
+            .map(|i| match n {
+                1 => 0.0,
+                _ => i as f64 / (n - 1) as f64,
+            })
+            .collect::<Vec<_>>();
+        Self([v.clone(), v.clone(), v])
+    }
+
+    /// Apply the curve, the white point and brightness, the gamma and then
+    /// the effects. The curve is sampled at each value of the ramps, which
+    /// resamples it to their size
     pub fn colorramp_fill(&mut self, setting: &ColorSettings) {
         let white_point = approximate_white_point(setting);
+        let linear = setting.curve.is_linear();
         let f = |y: f64, c: usize| -> f64 {
+            let y = if linear { y } else { setting.curve.apply(y, c) };
             let r = y * *setting.brght * white_point[c];
-            r.powf(1.0 / setting.gamma[c])
+            apply_effects(setting, r.powf(1.0 / setting.gamma[c]), c)
         };
 
         for i in 0..self[0].len() {
@@ -107,15 +153,185 @@ impl GammaRampsFloat {
             self[2][i] = f(self[2][i], 2);
         }
     }
+
+    /// Linearly interpolate the ramps to a different size. The first and
+    /// the last values are kept as they are
+    pub fn resample(&self, ramp_size: u32) -> Self {
+        let n = ramp_size as usize;
+        Self(self.0.clone().map(|ramp| {
+            let m = ramp.len();
+            if m == n || m == 0 {
+                return ramp;
+            }
+            (0..n)
+                .map(|i| {
+                    let x = match n {
+                        1 => 0.0,
+                        _ => i as f64 * (m - 1) as f64 / (n - 1) as f64,
+                    };
+                    let (j, t) = (x as usize, x.fract());
+                    let (a, b) = (ramp[j], ramp[(j + 1).min(m - 1)]);
+                    a + (b - a) * t
+                })
+                .collect()
+        }))
+    }
+
+    /// Resample the ramps to the ramp size of a display and round them to
+    /// the bits of its LUT. The values are scaled back to 16 bits, so a LUT
+    /// that keeps the high bits of each value stores exactly these values
+    #[cfg(unix_without_macos)]
+    pub fn to_ramps(&self, ramp_size: u32, depth: u32) -> GammaRamps {
+        let ramps = self.resample(ramp_size);
+        GammaRamps(
+            ramps
+                .0
+                .map(|r| r.into_iter().map(|r| quantize(r, depth)).collect()),
+        )
+    }
+}
+
+#[cfg(unix_without_macos)]
+impl From<&GammaRamps> for GammaRampsFloat {
+    fn from(ramps: &GammaRamps) -> Self {
+        Self(ramps.0.each_ref().map(|ramp| {
+            ramp.iter().map(|&y| y as f64 / u16::MAX as f64).collect()
+        }))
+    }
+}
+
+/// Round a value between 0 and 1 to the nearest value that can be stored
+/// with the bits of depth and scale it to 16 bits
+#[cfg(any(unix_without_macos, windows))]
+fn quantize(r: f64, depth: u32) -> u16 {
+    let max = ((1_u32 << depth.clamp(1, FULL_DEPTH)) - 1) as f64;
+    let y = (r.clamp(0.0, 1.0) * max).round();
+    (y / max * u16::MAX as f64).round() as u16
+}
+
+/// Gamma and the product of brightness and white point of a ramp that was
+/// filled by colorramp_fill on top of a linear ramp
+#[cfg(unix_without_macos)]
+fn fit_ramp(ramp: &[u16]) -> (f64, f64) {
+    // Each ramp is y = (x * brightness * white_point)^(1 / gamma), so
+    // ln(y) is a linear function of ln(x) with a slope of 1 / gamma
+    let n = ramp.len().saturating_sub(1).max(1) as f64;
+    let a = u16::MAX as f64;
+    let points = ramp
+        .iter()
+        .enumerate()
+        .filter(|&(i, &y)| i > 0 && y > 0)
+        .map(|(i, &y)| ((i as f64 / n).ln(), (y as f64 / a).ln()))
+        .collect::<Vec<_>>();
+    if points.len() < 2 {
+        return (DEFAULT_GAMMA, 0.0);
+    }
+
+    let m = points.len() as f64;
+    let (sx, sy) = points
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (mx, my) = (sx / m, sy / m);
+    let (sxy, sxx) = points.iter().fold((0.0, 0.0), |(sxy, sxx), (x, y)| {
+        (sxy + (x - mx) * (y - my), sxx + (x - mx).powi(2))
+    });
+    let slope = sxy / sxx;
+    if !slope.is_normal() {
+        return (DEFAULT_GAMMA, 0.0);
+    }
+    let gamma = (1.0 / slope).clamp(MIN_GAMMA, MAX_GAMMA);
+    // the intercept is ln(brightness * white_point) / gamma
+    (gamma, ((my - slope * mx) * gamma).exp())
+}
+
+/// Apply the contrast, inversion, black level and channel gain in order to a
+/// value of a ramp between 0 and 1. Effects with their default values are
+/// skipped so that the ramps stay exactly the same without them
+fn apply_effects(setting: &ColorSettings, r: f64, c: usize) -> f64 {
+    let mut r = r;
+    if *setting.contrast != DEFAULT_CONTRAST {
+        r = ((r - 0.5) * *setting.contrast + 0.5).clamp(0.0, 1.0);
+    }
+    if *setting.invert != 0.0 {
+        r += *setting.invert * (1.0 - 2.0 * r);
+    }
+    if *setting.black != 0.0 {
+        r = *setting.black + (1.0 - *setting.black) * r;
+    }
+    if setting.channels[c] != 1.0 {
+        r *= setting.channels[c];
+    }
+    r
 }
 
 fn approximate_white_point(setting: &ColorSettings) -> [f64; 3] {
-    let alpha = (*setting.temp % 100) as f64 / 100.0;
-    let temp_index = (*setting.temp - 1000) as usize / 100;
+    let wp = blackbody_white_point(*setting.temp);
+    if *setting.tint == 0.0 {
+        return wp;
+    }
+
+    // Move the white point along the normal of the Planckian locus in the
+    // CIE 1960 UCS, towards green for positive tints
+    let t = *setting.temp as f64;
+    let (u0, v0) = planckian_uv(t);
+    let (u1, v1) = planckian_uv(t + 1.0);
+    let len = (u1 - u0).hypot(v1 - v0);
+    let normal = ((v1 - v0) / len, (u0 - u1) / len);
+
+    let [x, y, z] = srgb_to_xyz(wp);
+    let (u, v) = xyz_to_uv([x, y, z]);
+    let u = u + *setting.tint * normal.0;
+    let v = v + *setting.tint * normal.1;
+    let wp = xyz_to_srgb(uv_to_xyz(u, v, y)).map(|c| c.max(0.0));
+    let max = wp.into_iter().fold(f64::EPSILON, f64::max);
+    wp.map(|c| c / max)
+}
+
+/// Chromaticity of the Planckian locus using the approximation of Krystek
+/// (1985)
+fn planckian_uv(t: f64) -> (f64, f64) {
+    let u = (0.860117757 + 1.54118254e-4 * t + 1.28641212e-7 * t * t)
+        / (1.0 + 8.42420235e-4 * t + 7.08145163e-7 * t * t);
+    let v = (0.317398726 + 4.22806245e-5 * t + 4.20481691e-8 * t * t)
+        / (1.0 - 2.89741816e-5 * t + 1.61456053e-7 * t * t);
+    (u, v)
+}
+
+fn srgb_to_xyz([r, g, b]: [f64; 3]) -> [f64; 3] {
+    [
+        0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+        0.2126729 * r + 0.7151522 * g + 0.0721750 * b,
+        0.0193339 * r + 0.1191920 * g + 0.9503041 * b,
+    ]
+}
+
+fn xyz_to_srgb([x, y, z]: [f64; 3]) -> [f64; 3] {
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+}
+
+fn xyz_to_uv([x, y, z]: [f64; 3]) -> (f64, f64) {
+    let d = x + 15.0 * y + 3.0 * z;
+    (4.0 * x / d, 6.0 * y / d)
+}
+
+/// Tristimulus values of a chromaticity with the luminance y
+fn uv_to_xyz(u: f64, v: f64, y: f64) -> [f64; 3] {
+    let d = 2.0 * u - 8.0 * v + 4.0;
+    let (cx, cy) = (3.0 * u / d, 2.0 * v / d);
+    [cx * y / cy, y, (1.0 - cx - cy) * y / cy]
+}
+
+fn blackbody_white_point(temp: u16) -> [f64; 3] {
+    let alpha = (temp % 100) as f64 / 100.0;
+    let temp_index = (temp - 1000) as usize / 100;
     interpolate_color(
         alpha,
         &BLACKBODY_COLOR[temp_index],
-        &BLACKBODY_COLOR[temp_index + 3],
+        &BLACKBODY_COLOR[temp_index + 1],
     )
 }
 
@@ -419,3 +635,172 @@ const BLACKBODY_COLOR: [[f64; 3]; 242] = [
     [0.62774186, 0.75306977, 1.00000000], // 25000K
     [0.62740336, 0.75282962, 1.00000000], // 25100K
 ];
+
+#[cfg(all(test, unix_without_macos))]
+mod test {
+    use super::*;
+    use crate::types::Tint;
+    use anyhow::Result;
+
+    fn fill(ramp_size: u32, cs: &ColorSettings, depth: u32) -> GammaRamps {
+        let mut ramps = GammaRampsFloat::new(ramp_size);
+        ramps.colorramp_fill(cs);
+        ramps.to_ramps(ramp_size, depth)
+    }
+
+    #[test]
+    fn white_point_interpolation() -> Result<()> {
+        let wp = |temp: u16| -> Result<[f64; 3]> {
+            let cs = ColorSettings {
+                temp: temp.try_into()?,
+                ..Default::default()
+            };
+            Ok(approximate_white_point(&cs))
+        };
+
+        assert_eq!(wp(6500)?, BLACKBODY_COLOR[55]);
+        // halfway between the neighboring entries of the table
+        let (w, c1, c2) =
+            (wp(6550)?, BLACKBODY_COLOR[55], BLACKBODY_COLOR[56]);
+        assert!((0..3).all(|c| (w[c] - (c1[c] + c2[c]) / 2.0).abs() < 1e-9));
+        assert_eq!(wp(25000)?, BLACKBODY_COLOR[240]);
+        Ok(())
+    }
+
+    #[test]
+    fn colorramp_estimate() -> Result<()> {
+        let cs = ColorSettings {
+            temp: Temperature::try_from(3400)?,
+            gamma: Gamma::try_from((0.9, 1.0, 1.1))?,
+            brght: Brightness::try_from(0.8)?,
+            ..Default::default()
+        };
+        let (estimate, error) = fill(1024, &cs, FULL_DEPTH).estimate();
+        assert!((*estimate.temp as i32 - 3400).abs() <= 10);
+        assert!((*estimate.brght - 0.8).abs() < 0.01);
+        assert!((0..3).all(|c| (estimate.gamma[c] - cs.gamma[c]).abs() < 0.01));
+        assert!(error < 0.001);
+
+        let (estimate, error) = GammaRamps::new(256).estimate();
+        assert!(!estimate.is_very_diff_from(&ColorSettings::default()));
+        assert!(error < 0.001);
+        Ok(())
+    }
+
+    #[test]
+    fn colorramp_effects() -> Result<()> {
+        let fill = |cs: &ColorSettings| fill(256, cs, FULL_DEPTH);
+
+        let red_only = ColorSettings {
+            channels: "r".parse()?,
+            ..Default::default()
+        };
+        let ramps = fill(&red_only);
+        assert_eq!(*ramps[0].last().unwrap_or(&0), u16::MAX);
+        assert!(ramps[1].iter().chain(&ramps[2]).all(|y| *y == 0));
+
+        let inverted = ColorSettings {
+            invert: "true".parse()?,
+            black: "0.1".parse()?,
+            ..Default::default()
+        };
+        let ramps = fill(&inverted);
+        assert!(ramps[0].windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(ramps[0][0], u16::MAX);
+        // the black level is lifted after inverting
+        assert!((ramps[0][255] as f64 / u16::MAX as f64 - 0.1).abs() < 0.01);
+
+        let contrast = ColorSettings {
+            contrast: "2".parse()?,
+            ..Default::default()
+        };
+        let ramps = fill(&contrast);
+        assert_eq!(ramps[1][32], 0);
+        // symmetric around the middle of the ramps
+        let sum = ramps[1][127] as u32 + ramps[1][128] as u32;
+        assert!(sum.abs_diff(u16::MAX as u32) <= 1);
+        Ok(())
+    }
+
+    #[test]
+    fn colorramp_golden() -> Result<()> {
+        let settings = [
+            ColorSettings::default(),
+            ColorSettings {
+                temp: Temperature::try_from(1000)?,
+                gamma: Gamma::try_from((0.5, 1.0, 2.0))?,
+                brght: Brightness::try_from(0.5)?,
+                ..Default::default()
+            },
+            ColorSettings {
+                temp: Temperature::try_from(25000)?,
+                brght: Brightness::try_from(0.1)?,
+                ..Default::default()
+            },
+        ];
+        for (cs, ramp_size, depth) in itertools::iproduct!(
+            &settings,
+            [2, 256, 1024, 4096],
+            [8, 10, FULL_DEPTH]
+        ) {
+            let ramps = fill(ramp_size, cs, depth);
+            let wp = approximate_white_point(cs);
+            for c in 0..3 {
+                let ramp = &ramps[c];
+                assert_eq!(ramp.len(), ramp_size as usize);
+                assert!(ramp.windows(2).all(|w| w[0] <= w[1]));
+                assert_eq!(ramp[0], 0);
+                let last = (*cs.brght * wp[c]).powf(1.0 / cs.gamma[c]);
+                let last = quantize(last, depth);
+                assert_eq!(ramp[ramp.len() - 1], last);
+                // every value can be stored in a LUT of the depth
+                let max = ((1 << depth) - 1) as f64;
+                assert!(ramp.iter().all(|&y| {
+                    let v = (y as f64 * max / u16::MAX as f64).round();
+                    quantize(v / max, depth) == y
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn colorramp_linear() {
+        let linear = GammaRamps::new(256);
+        assert_eq!(linear[0][0], 0);
+        assert_eq!(linear[0][255], u16::MAX);
+        assert_eq!(linear[0][1], 257);
+        let ramps = fill(1024, &ColorSettings::default(), FULL_DEPTH);
+        assert_eq!(ramps[0], GammaRamps::new(1024)[0]);
+        let ramps = fill(1024, &ColorSettings::default(), 10);
+        // a 10-bit LUT of 1024 entries can hold a distinct value per entry
+        assert!(ramps[0].windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn colorramp_tint() -> Result<()> {
+        let wp = |temp: u16, tint: f64| -> Result<[f64; 3]> {
+            Ok(approximate_white_point(&ColorSettings {
+                temp: Temperature::try_from(temp)?,
+                tint: Tint::try_from(tint)?,
+                ..Default::default()
+            }))
+        };
+
+        for temp in [1000, 3000, 6500, 25000] {
+            let neutral = wp(temp, 0.0)?;
+            let green = wp(temp, 0.01)?;
+            let magenta = wp(temp, -0.01)?;
+            assert!(green[1] / green[0] > neutral[1] / neutral[0]);
+            assert!(magenta[1] / magenta[0] < neutral[1] / neutral[0]);
+            assert!([green, magenta]
+                .iter()
+                .all(|w| w.iter().all(|c| (0.0..=1.0).contains(c))));
+        }
+
+        // a tiny tint barely moves the white point
+        let [a, b] = [wp(6500, 0.0)?, wp(6500, 1e-6)?];
+        assert!((0..3).all(|c| (a[c] - b[c]).abs() < 1e-4));
+        Ok(())
+    }
+}
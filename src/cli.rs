@@ -17,17 +17,24 @@
 */
 
 use crate::{
-    config::{DEFAULT_SLEEP_DURATION, DEFAULT_SLEEP_DURATION_SHORT},
+    config::{
+        DEFAULT_EXPORT_SIZE, DEFAULT_FADE_DURATION, DEFAULT_SLEEP_DURATION,
+        DEFAULT_SLEEP_DURATION_SHORT,
+    },
+    curve::Curve,
+    error::parse::KeyValueError,
     types::{
-        AdjustmentMethodType, Brightness, BrightnessRange, Gamma, GammaRange,
-        LocationProviderType, Temperature, TemperatureRange, TransitionScheme,
-        MAX_TEMPERATURE, MIN_TEMPERATURE,
+        AdjustmentMethodType, BlackLevel, BlackLevelRange, Brightness,
+        BrightnessRange, Channels, ChannelsRange, ColorMatrix, Contrast,
+        ContrastRange, ExportFormat, Gamma, GammaRange, Invert, InvertRange,
+        LocationProviderType, SetValue, Temperature, TemperatureRange, Tint,
+        TintRange, TransitionScheme, MAX_TEMPERATURE, MIN_TEMPERATURE,
     },
 };
 use anstream::ColorChoice;
 use clap::{
-    ArgAction, Args, ColorChoice as ClapColorChoice, Command, CommandFactory,
-    Parser, Subcommand,
+    Args, ColorChoice as ClapColorChoice, Command, CommandFactory, Parser,
+    Subcommand,
 };
 use const_format::formatcp;
 use std::{cmp::Ordering, marker::PhantomData, path::PathBuf, str::FromStr};
@@ -91,7 +98,7 @@ pub enum ModeArgs {
         /// It will cause an immediate change between screen temperatures. by default,
         /// the new screen temperature are gradually applied over a couple of seconds
         #[arg(verbatim_doc_comment)]
-        #[arg(long, action = ArgAction::SetTrue)]
+        #[arg(long, num_args = 0, default_missing_value = "true")]
         disable_fade: Option<bool>,
 
         #[arg(help = formatcp!("Duration of sleep between screen updates [default: {DEFAULT_SLEEP_DURATION}]"))]
@@ -101,11 +108,31 @@ pub enum ModeArgs {
         #[arg(help = formatcp!("Duration of sleep between screen updates for fade [default: {DEFAULT_SLEEP_DURATION_SHORT}]"))]
         #[arg(long, value_name = "MILLISECONDS")]
         sleep_duration_short: Option<u16>,
+
+        /// Detect gamma ramps overwritten by other programs
+        ///
+        /// Read back the gamma ramps on every screen update and compare them with
+        /// the ramps that were last applied. Not supported by all methods
+        #[arg(verbatim_doc_comment)]
+        #[arg(long, num_args = 0, default_missing_value = "true")]
+        monitor_ramps: Option<bool>,
+
+        /// Adopt gamma ramps of other programs that persist as the new base
+        ///
+        /// Color settings will be applied on top of them instead of the ramps that
+        /// existed at startup, even with --reset-ramps. It requires --monitor-ramps
+        #[arg(verbatim_doc_comment)]
+        #[arg(long, num_args = 0, default_missing_value = "true")]
+        adopt_foreign_ramps: Option<bool>,
     },
 
     /// Like daemon mode, but do not run continuously
     #[command(next_line_help(true))]
     Oneshot {
+        #[arg(help = formatcp!("Fade from the current color settings over a duration [default: {DEFAULT_FADE_DURATION}]"))]
+        #[arg(long, value_name = "MILLISECONDS", num_args = 0..=1)]
+        #[arg(default_missing_value = formatcp!("{DEFAULT_FADE_DURATION}"))]
+        fade: Option<u16>,
         #[command(flatten)]
         c: CmdArgs,
     },
@@ -113,6 +140,10 @@ pub enum ModeArgs {
     /// Apply a specific screen color settings
     #[command(next_line_help(true))]
     Set {
+        #[arg(help = formatcp!("Fade from the current color settings over a duration [default: {DEFAULT_FADE_DURATION}]"))]
+        #[arg(long, value_name = "MILLISECONDS", num_args = 0..=1)]
+        #[arg(default_missing_value = formatcp!("{DEFAULT_FADE_DURATION}"))]
+        fade: Option<u16>,
         #[command(flatten)]
         cs: ColorSettingsArgs,
         #[command(flatten)]
@@ -122,6 +153,18 @@ pub enum ModeArgs {
     /// Remove adjustment from screen
     #[command(next_line_help(true))]
     Reset {
+        /// Restore the gamma ramps from before the first adjustment
+        ///
+        /// The original gamma ramps are saved to the runtime directory when they
+        /// are adjusted for the first time. Unlike the linear ramps that are set
+        /// by default, they keep the calibration of the display
+        #[arg(verbatim_doc_comment)]
+        #[arg(long)]
+        original: bool,
+        #[arg(help = formatcp!("Fade from the current color settings over a duration [default: {DEFAULT_FADE_DURATION}]"))]
+        #[arg(long, value_name = "MILLISECONDS", num_args = 0..=1)]
+        #[arg(default_missing_value = formatcp!("{DEFAULT_FADE_DURATION}"))]
+        fade: Option<u16>,
         #[command(flatten)]
         i: CmdInnerArgs,
     },
@@ -129,51 +172,276 @@ pub enum ModeArgs {
     /// Print all solar elevation angles for the next 24 hours
     #[command(next_line_help(true))]
     Print {
-        /// Location [default: 0:0]
+        /// Location [default: timezone]
         ///
         /// Either set latitude and longitude manually or select a location provider.
         /// Negative values represent west and south, respectively. e.g.:
         ///     51.48:0.0 (Greenwich)
+        ///     timezone  (Principal city of the timezone of the system)
         ///     geoclue2  (Currently not available)
         #[arg(verbatim_doc_comment)]
         #[arg(long, short, value_parser = LocationProviderType::from_str)]
         #[arg(value_name = "LATITUDE:LONGITUDE | PROVIDER")]
         #[arg(allow_hyphen_values = true)]
-        location: LocationProviderType,
+        location: Option<LocationProviderType>,
+    },
+
+    /// List adjustment methods and the screens, crtcs and outputs they can
+    /// adjust
+    #[command(visible_alias = "probe")]
+    List {
+        /// Print the list in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show, check or import the configuration without affecting the display
+    #[command(subcommand)]
+    Config(ConfigArgs),
+
+    /// Save, restore and compare snapshots of the gamma ramps
+    #[command(subcommand)]
+    Snapshot(SnapshotArgs),
+
+    /// Translate the arguments of Redshift and run the equivalent command
+    ///
+    /// Invoking the program as redshift (e.g. through a symlink) has the same
+    /// effect. The equivalent command is printed with -v, e.g.:
+    ///     compat -O 4000              (set --temperature 4000)
+    ///     compat -l 51.5:0 -t 6500:3600
+    ///                                 (daemon --temperature 6500-3600 --location 51.5:0)
+    ///     compat -x -m randr:crtc=62  (reset --method randr::62)
+    #[command(verbatim_doc_comment, disable_help_flag = true)]
+    Compat {
+        /// Arguments of Redshift
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        #[arg(value_name = "ARGS")]
+        args: Vec<String>,
+    },
+
+    /// Estimate the color settings that are applied to each crtc
+    ///
+    /// The current gamma ramps are compared with the ramps reddish-shift would
+    /// set. A large fit error means that the ramps were set by another program
+    /// or on top of ramps that were not linear. Only the temperature, gamma
+    /// and brightness are estimated, not the tint, effects or curve
+    #[command(next_line_help(true))]
+    Status {
+        #[command(flatten)]
+        i: CmdInnerArgs,
+    },
+
+    /// Write the gamma ramps of the color settings to a file
+    ///
+    /// The ramps are calculated the same way as the set command calculates
+    /// them, starting from linear ramps, e.g.:
+    ///     export -t 4500 -o night.csv
+    ///     export -t 3600 -b 0.8 --size 1024 -o night.cube
+    ///     export --preset night -o night.icc
+    #[command(verbatim_doc_comment, next_line_help(true))]
+    Export {
+        /// Format of the file
+        ///
+        /// It is guessed from the extension of the output file if not set and
+        /// is csv otherwise. The icc format is a display profile with a vcgt
+        /// tag that is loaded by color management tools. Values:
+        ///     csv   a row of red, green and blue values for each entry
+        ///     cube  a 1D LUT
+        ///     icc   an ICC v2 display profile
+        #[arg(verbatim_doc_comment)]
+        #[arg(long, short, value_parser = ExportFormat::from_str)]
+        format: Option<ExportFormat>,
+        #[arg(help = formatcp!("Number of entries of each channel [default: {DEFAULT_EXPORT_SIZE}]"))]
+        #[arg(long, short, value_name = "2-65535")]
+        #[arg(value_parser = clap::value_parser!(u16).range(2..))]
+        size: Option<u16>,
+        /// File to write to [default: stdout]
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        cs: ColorSettingsArgs,
+        #[command(flatten)]
+        i: CmdInnerArgs,
+    },
+
+    /// Apply the adjustments recorded by the file method
+    ///
+    /// Recorded color settings are applied with the selected method, which
+    /// calculates the ramps for its own crtcs. e.g.:
+    ///     replay rec.jsonl -m randr
+    ///     replay rec.jsonl -m drm --delay 500
+    #[command(verbatim_doc_comment, next_line_help(true))]
+    Replay {
+        /// Recording of the file method
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+        /// Wait a fixed duration between the records instead of the
+        /// recorded time
+        #[arg(long, value_name = "MILLISECONDS")]
+        delay: Option<u64>,
+        #[command(flatten)]
+        i: CmdInnerArgs,
     },
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Subcommand)]
+pub enum ConfigArgs {
+    /// Print the merged configuration and where each value comes from
+    #[command(next_line_help(true))]
+    Show {
+        #[command(flatten)]
+        c: Box<CmdArgs>,
+    },
+
+    /// Check the config files for errors
+    #[command(next_line_help(true))]
+    Check {
+        /// Path of the config file
+        #[arg(long, short, value_name = "FILE")]
+        config: Option<PathBuf>,
+    },
+
+    /// Convert a Redshift or Gammastep config file to a reddish-shift config
+    /// file and print it
+    ///
+    /// Options that have no equivalent are reported as warnings
+    #[command(next_line_help(true))]
+    Import {
+        /// Path of the Redshift or Gammastep config file [default:
+        /// redshift/redshift.conf, redshift.conf or gammastep/config.ini in
+        /// the config directory]
+        #[arg(value_name = "FILE")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotArgs {
+    /// Save the current gamma ramps of the crtcs
+    #[command(next_line_help(true))]
+    Save {
+        /// Name of the snapshot
+        name: String,
+        #[command(flatten)]
+        i: CmdInnerArgs,
+    },
+
+    /// Restore the gamma ramps of a snapshot
+    ///
+    /// The ramps are resampled if the ramp size of a crtc has changed. Crtcs that
+    /// are not in the snapshot are left unchanged
+    #[command(next_line_help(true))]
+    Restore {
+        /// Name of the snapshot
+        name: String,
+        #[command(flatten)]
+        i: CmdInnerArgs,
+    },
+
+    /// List the saved snapshots
+    List,
+
+    /// Compare a snapshot with another snapshot or the current gamma ramps
+    #[command(next_line_help(true))]
+    Diff {
+        /// Name of the snapshot
+        name: String,
+        /// Name of the snapshot to compare with [default: current gamma ramps]
+        other: Option<String>,
+        #[command(flatten)]
+        i: CmdInnerArgs,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
 pub struct ColorSettingsArgs {
+    /// Color settings of a preset to apply
+    ///
+    /// Either a [preset.NAME] table of the config file or one of the built-in
+    /// presets: candle, incandescent, halogen and daylight. Other arguments
+    /// override the values of the preset
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, short, value_name = "NAME")]
+    pub preset: Option<String>,
+
     /// Color temperature to apply [default: 6500]
     ///
     /// The neutral temperature is 6500K. Using this value will not change the color
     /// temperature of the display. Setting the color temperature to a value higher
     /// than this results in more blue light, and setting a lower value will result
-    /// in more red light.
+    /// in more red light. The names candle (1900), incandescent (2700), halogen
+    /// (3400) and daylight (6500) can be used too. Values starting with + or -
+    /// are relative to the last applied settings, e.g.:
+    ///     +300 (300K higher than the current temperature)
+    ///     -10% (10 percent lower than the current temperature)
     #[arg(verbatim_doc_comment)]
-    #[arg(long, short, value_parser = Temperature::from_str)]
+    #[arg(long, short, value_parser = SetValue::<Temperature>::from_str)]
     #[arg(value_name = formatcp!("FROM {MIN_TEMPERATURE} TO {MAX_TEMPERATURE}"))]
-    #[arg(default_value_t)]
-    pub temperature: Temperature,
+    #[arg(allow_hyphen_values = true)]
+    pub temperature: Option<SetValue<Temperature>>,
+
+    /// Distance of the white point from the Planckian locus (Duv) [default: 0]
+    ///
+    /// Positive values tint the colors green and negative values magenta, e.g.
+    /// -0.005 makes a blackbody white point look less green
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = Tint::from_str)]
+    #[arg(value_name = "FROM -0.05 TO 0.05")]
+    #[arg(allow_hyphen_values = true)]
+    pub tint: Option<Tint>,
 
     /// Additional gamma correction to apply [default: 1.0]
     ///
     /// Either set it for all colors, or each color channel individually. e.g.:
     ///     0.9         (R=G=B=0.9)
     ///     0.8:0.9:0.9 (R=0.8, G=0.9, B=0.9)
+    ///     +0.1        (0.1 higher than the current gamma)
     #[arg(verbatim_doc_comment)]
-    #[arg(long, short, value_parser = Gamma::from_str)]
+    #[arg(long, short, value_parser = SetValue::<Gamma>::from_str)]
     #[arg(value_name = "FROM 0.1 TO 10")]
-    #[arg(default_value_t)]
-    pub gamma: Gamma,
+    #[arg(allow_hyphen_values = true)]
+    pub gamma: Option<SetValue<Gamma>>,
 
     /// Screen brightness to apply [default: 1.0]
+    ///
+    /// Relative values like -0.1 or +5% are applied to the current brightness
     #[arg(verbatim_doc_comment)]
-    #[arg(long, short, value_parser = Brightness::from_str)]
+    #[arg(long, short, value_parser = SetValue::<Brightness>::from_str)]
     #[arg(value_name = "FROM 0.1 TO 1.0")]
-    #[arg(default_value_t)]
-    pub brightness: Brightness,
+    #[arg(allow_hyphen_values = true)]
+    pub brightness: Option<SetValue<Brightness>>,
+
+    /// Color channels to keep, the others are muted [default: rgb]
+    ///
+    /// Either the letters of the channels to keep, or the gain of each channel. e.g.:
+    ///     r           (red only night vision)
+    ///     rg          (mute blue)
+    ///     1:0.5:0.2   (R=1.0, G=0.5, B=0.2)
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = Channels::from_str)]
+    #[arg(value_name = "LETTERS | R:G:B")]
+    pub channels: Option<Channels>,
+
+    /// Invert the colors [default: false]
+    #[arg(long, value_parser = Invert::from_str)]
+    #[arg(value_name = "BOOL | FROM 0 TO 1")]
+    pub invert: Option<Invert>,
+
+    /// Lift the black level to reduce the contrast of dark colors [default: 0]
+    #[arg(long, value_parser = BlackLevel::from_str)]
+    #[arg(value_name = "FROM 0 TO 0.5")]
+    pub black_level: Option<BlackLevel>,
+
+    /// Contrast around the middle of the ramps [default: 1.0]
+    #[arg(long, value_parser = Contrast::from_str)]
+    #[arg(value_name = "FROM 0.1 TO 5.0")]
+    pub contrast: Option<Contrast>,
+
+    /// Curve file to apply to each channel before the color temperature and
+    /// brightness [default: linear]
+    #[arg(long, value_parser = Curve::from_str)]
+    #[arg(value_name = "PATH")]
+    pub curve: Option<Curve>,
 }
 
 #[derive(Debug, Args)]
@@ -183,14 +451,28 @@ pub struct CmdArgs {
     /// The neutral temperature is 6500K. Using this value will not change the color
     /// temperature of the display. Setting the color temperature to a value higher
     /// than this results in more blue light, and setting a lower value will result
-    /// in more red light. e.g.:
+    /// in more red light. The names candle (1900), incandescent (2700), halogen
+    /// (3400) and daylight (6500) can be used too. e.g.:
     ///     5000      (day=night=5000)
     ///     6500-4500 (day=6500, night=4500)
+    ///     daylight-halogen
     #[arg(verbatim_doc_comment)]
     #[arg(long, short, value_parser = TemperatureRange::from_str)]
     #[arg(value_name = formatcp!("FROM {MIN_TEMPERATURE} TO {MAX_TEMPERATURE}"))]
     pub temperature: Option<TemperatureRange>,
 
+    /// Distance of the white point from the Planckian locus (Duv) for day and night [default: 0]
+    ///
+    /// Positive values tint the colors green and negative values magenta. A dash
+    /// at the start of a value is its sign. e.g.:
+    ///     -0.005      (day=night=-0.005)
+    ///     0 - -0.005  (day=0, night=-0.005)
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = TintRange::from_str)]
+    #[arg(value_name = "FROM -0.05 TO 0.05")]
+    #[arg(allow_hyphen_values = true)]
+    pub tint: Option<TintRange>,
+
     /// Additional gamma correction to apply for day and night [default: 1.0]
     ///
     /// Either set it for all colors, or each color channel individually. e.g.:
@@ -212,6 +494,56 @@ pub struct CmdArgs {
     #[arg(value_name = "FROM 0.1 TO 1.0")]
     pub brightness: Option<BrightnessRange>,
 
+    /// Color channels to keep for day and night, the others are muted [default: rgb]
+    ///
+    /// Either the letters of the channels to keep, or the gain of each channel. e.g.:
+    ///     rgb-r       (day=all channels, night=red only night vision)
+    ///     rg          (day=night=mute blue)
+    ///     1:0.5:0.2   (day=night=(R=1.0, G=0.5, B=0.2))
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = ChannelsRange::from_str)]
+    #[arg(value_name = "LETTERS | R:G:B")]
+    pub channels: Option<ChannelsRange>,
+
+    /// Invert the colors for day and night [default: false]
+    ///
+    /// e.g.:
+    ///     true       (day=night=inverted)
+    ///     false-true (day=normal, night=inverted)
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = InvertRange::from_str)]
+    #[arg(value_name = "BOOL | FROM 0 TO 1")]
+    pub invert: Option<InvertRange>,
+
+    /// Lift the black level for day and night [default: 0]
+    ///
+    /// The lowest value of the ramps, e.g. 0-0.1 (day=0, night=0.1)
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = BlackLevelRange::from_str)]
+    #[arg(value_name = "FROM 0 TO 0.5")]
+    pub black_level: Option<BlackLevelRange>,
+
+    /// Contrast around the middle of the ramps for day and night [default: 1.0]
+    ///
+    /// e.g. 1.0-0.8 (day=1.0, night=0.8)
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = ContrastRange::from_str)]
+    #[arg(value_name = "FROM 0.1 TO 5.0")]
+    pub contrast: Option<ContrastRange>,
+
+    /// Curve files to apply to each channel for day and night [default: linear]
+    ///
+    /// The curves are applied before the color temperature and brightness and
+    /// are blended during transitions. Either a 1D LUT (.cube), evenly spaced
+    /// samples (.csv) or lines of control points (x followed by one value or a
+    /// value for each channel). Use linear for no curve. e.g.:
+    ///     lift.cube             (day=night=lift.cube)
+    ///     linear night.csv      (day=no curve, night=night.csv)
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = Curve::from_str, num_args = 1..=2)]
+    #[arg(value_name = "DAY [NIGHT]")]
+    pub curve: Option<Vec<Curve>>,
+
     /// Transition scheme [default: 3:-6]
     ///
     /// Either time ranges or elevation angles. By default, Reddish Shift will use
@@ -227,12 +559,15 @@ pub struct CmdArgs {
     #[arg(allow_hyphen_values = true)]
     pub scheme: Option<TransitionScheme>,
 
-    /// Location, used for computation of current solar elevation [default: 0:0]
+    /// Location, used for computation of current solar elevation [default: timezone]
     ///
     /// It is not needed when using manual time ranges for transition scheme Either
     /// set latitude and longitude manually or select a location provider. Negative
-    /// values represent west and south, respectively. e.g.:
+    /// values represent west and south, respectively. The timezone provider uses
+    /// the principal city of the timezone of the system (TZ or /etc/localtime)
+    /// and falls back to 0:0. e.g.:
     ///     51.48:0.0 (Greenwich)
+    ///     timezone (e.g. 52.5:13.37 for Europe/Berlin)
     ///     geoclue2 (Currently not available)
     #[arg(verbatim_doc_comment)]
     #[arg(long, short, value_parser = LocationProviderType::from_str)]
@@ -257,23 +592,67 @@ pub struct CmdInnerArgs {
     ///     randr               (apply to $DISPLAY)
     ///     randr:0             (apply to screen 0)
     ///     randr$DISPLAY:62,63 (apply to $DISPLAY with crtcs 62 and 63)
+    ///     randr:0:HDMI-1,eDP-1
+    ///                         (apply to screen 0 with outputs HDMI-1 and eDP-1)
     ///   Direct Rendering Manager:
     ///     drm                 (apply to /dev/dri/card0)
     ///     drm:1               (apply to /dev/dri/card1)
     ///     drm:0:80            (apply to /dev/dri/card0 with crtc 80)
+    ///     drm:0:DP-2          (apply to /dev/dri/card0 with connector DP-2)
+    ///     drm:0:edid=DEL/ABC123
+    ///                         (apply to the monitor with the given EDID
+    ///                          manufacturer id and optional serial number)
+    /// Outputs are selected again when monitors are plugged or unplugged
+    ///   GNOME Mutter through D-Bus:
+    ///     mutter              (apply to all monitors)
+    ///     mutter::HDMI-1      (apply to the monitor on output HDMI-1)
+    ///   KDE KWin night light through D-Bus (color temperature only):
+    ///     kwin
+    ///   Linux console palette:
+    ///     vt                  (apply to the 16 colors of all consoles)
+    ///     vt:2                (change the palette through /dev/tty2)
+    ///   Emulated crtcs that record every adjustment to a file:
+    ///     file:rec.jsonl      (a crtc with 256 entries, or the recorded ones)
+    ///     file:rec.jsonl:1024:0,1
+    ///                         (crtcs 0 and 1 with 1024 entries)
+    /// New crtcs start with linear ramps. Restore a snapshot with the file
+    /// method to start a recording from the ramps of a display instead
     ///   Windows graphics device interface:
     ///     win32gdi            (apply to current display)
     #[arg(verbatim_doc_comment)]
     #[arg(long, short, value_parser = AdjustmentMethodType::from_str)]
     #[arg(
-        value_name = "METHOD [:(DISPLAY_NUM | CARD_NUM) [:CRTC1,CRTC2,...]]"
+        value_name = "METHOD [:(DISPLAY_NUM | CARD_NUM) [:CRTC1,OUTPUT2,...]]"
     )]
     pub method: Option<AdjustmentMethodType>,
 
     /// Reset existing gamma ramps before applying new color settings
-    #[arg(long, action = ArgAction::SetTrue)]
+    #[arg(long, num_args = 0, default_missing_value = "true")]
     pub reset_ramps: Option<bool>,
 
+    /// Color transformation matrix to apply before the gamma ramps
+    ///
+    /// Either a saturation (0 results in grayscale) or nine comma separated
+    /// numbers of a 3x3 matrix in row-major order. e.g.:
+    ///     0.5                            (half saturation)
+    ///     0,1,0,1,0,0,0,0,1              (swap red and green)
+    /// Only supported by the drm method on hardware with a CTM property
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = ColorMatrix::from_str, allow_hyphen_values = true)]
+    #[arg(value_name = "SATURATION | MATRIX")]
+    pub color_matrix: Option<ColorMatrix>,
+
+    /// Set a key of the config file, e.g. --set temperature=6500-3800
+    ///
+    /// Values are parsed as in the config file. It can be used multiple times
+    /// and overrides the REDDISH_SHIFT_<KEY> environment variables (e.g.
+    /// REDDISH_SHIFT_SLEEP_DURATION for sleep-duration) which override the
+    /// config files
+    #[arg(verbatim_doc_comment)]
+    #[arg(long, value_parser = key_value, value_name = "KEY=VALUE")]
+    #[arg(display_order(98))]
+    pub set: Vec<KeyValue>,
+
     /// Path of the config file
     ///
     /// A template for the config file should have been installed alongside
@@ -379,6 +758,13 @@ impl ClapColorChoiceExt for ClapColorChoice {
     }
 }
 
+pub type KeyValue = (String, String);
+
+fn key_value(s: &str) -> Result<KeyValue, KeyValueError> {
+    let (k, v) = s.split_once('=').ok_or(KeyValueError)?;
+    Ok((k.trim().to_string(), v.trim().to_string()))
+}
+
 // used for generation of shell completion scripts and man pages
 
 pub fn cli_args_command() -> Command {
@@ -0,0 +1,379 @@
+/*  compat.rs -- Redshift compatible command line interface
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    cli::{CliArgs, ModeArgs},
+    config::PKG_NAME,
+    config_import::method_arg,
+};
+use clap::{
+    error::ErrorKind, ColorChoice as ClapColorChoice, CommandFactory, Parser,
+};
+use itertools::Itertools;
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    iter,
+    path::Path,
+};
+
+// Options of redshift that take a value and the ones that do not
+const OPTIONS: &str = "bcglmOt";
+const FLAGS: &str = "hopPrvVx";
+
+/// Arguments of reddish-shift translated from the arguments of redshift
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compat {
+    args: Vec<String>,
+    verbose: bool,
+}
+
+/// Options of the redshift command line interface
+#[derive(Debug, Default)]
+struct Redshift {
+    brightness: Option<String>,
+    config: Option<String>,
+    gamma: Option<String>,
+    location: Option<String>,
+    method: Option<String>,
+    temperature: Option<String>,
+    // One of the o, O, p and x options. The last one takes effect
+    mode: Option<char>,
+    set_temperature: Option<String>,
+    reset_ramps: bool,
+    disable_fade: bool,
+    verbose: bool,
+    help: bool,
+    version: bool,
+}
+
+/// Parse the command line arguments. They are translated first if the
+/// program is invoked as redshift or with the compat command
+pub fn parse() -> (CliArgs, Option<Compat>) {
+    let mut args = env::args_os();
+    let is_redshift = args
+        .next()
+        .as_ref()
+        .and_then(|a| Path::new(a).file_stem())
+        .is_some_and(|s| s == "redshift");
+
+    if is_redshift {
+        let args = args.map(|a| a.to_string_lossy().into_owned());
+        let c = Compat::new(&args.collect_vec()).unwrap_or_else(|e| e.exit());
+        return (c.cli_args(None), Some(c));
+    }
+
+    let cli_args = CliArgs::parse();
+    match &cli_args.mode {
+        ModeArgs::Compat { args } => {
+            let c = Compat::new(args).unwrap_or_else(|e| e.exit());
+            (c.cli_args(cli_args.color), Some(c))
+        }
+        _ => (cli_args, None),
+    }
+}
+
+impl Compat {
+    pub fn new(args: &[String]) -> Result<Self, clap::Error> {
+        let r = Redshift::new(args)?;
+        Ok(Self {
+            args: r.native_args()?,
+            verbose: r.verbose,
+        })
+    }
+
+    /// Whether the equivalent reddish-shift command should be printed
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn cli_args(&self, color: Option<ClapColorChoice>) -> CliArgs {
+        let args = iter::once(PKG_NAME).chain(self.args.iter().map(|s| &**s));
+        let mut cli_args = CliArgs::parse_from(args);
+        cli_args.color = cli_args.color.or(color);
+        cli_args
+    }
+}
+
+impl Display for Compat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let quote = |s: &String| {
+            if !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_alphanumeric() || ":.,-+=_/".contains(c))
+            {
+                s.clone()
+            } else {
+                format!("'{}'", s.replace('\'', r"'\''"))
+            }
+        };
+        write!(f, "{PKG_NAME} {}", self.args.iter().map(quote).join(" "))
+    }
+}
+
+fn error(kind: ErrorKind, msg: String) -> clap::Error {
+    CliArgs::command().error(kind, msg)
+}
+
+impl Redshift {
+    /// Parse the arguments like getopt, e.g. `-vO 4000` or `-O4000`
+    fn new(args: &[String]) -> Result<Self, clap::Error> {
+        let mut r = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            // redshift has no long options
+            let Some(opts) = arg
+                .strip_prefix('-')
+                .filter(|s| !s.is_empty() && !s.starts_with('-'))
+            else {
+                let msg = format!("unexpected redshift argument `{arg}`");
+                return Err(error(ErrorKind::UnknownArgument, msg));
+            };
+
+            for (i, opt) in opts.char_indices() {
+                if FLAGS.contains(opt) {
+                    r.flag(opt);
+                } else if OPTIONS.contains(opt) {
+                    let rest = &opts[i + opt.len_utf8()..];
+                    let value = match rest {
+                        "" => args.next().cloned().ok_or_else(|| {
+                            let msg = format!(
+                                "redshift option `-{opt}` requires a value"
+                            );
+                            error(ErrorKind::InvalidValue, msg)
+                        })?,
+                        rest => rest.to_string(),
+                    };
+                    r.option(opt, value);
+                    break;
+                } else {
+                    let msg = format!("unsupported redshift option `-{opt}`");
+                    return Err(error(ErrorKind::UnknownArgument, msg));
+                }
+            }
+        }
+        Ok(r)
+    }
+
+    fn flag(&mut self, opt: char) {
+        match opt {
+            'h' => self.help = true,
+            'V' => self.version = true,
+            'v' => self.verbose = true,
+            'P' => self.reset_ramps = true,
+            'r' => self.disable_fade = true,
+            _ => self.mode = Some(opt),
+        }
+    }
+
+    fn option(&mut self, opt: char, value: String) {
+        let field = match opt {
+            'b' => &mut self.brightness,
+            'c' => &mut self.config,
+            'g' => &mut self.gamma,
+            'l' => &mut self.location,
+            'm' => &mut self.method,
+            't' => &mut self.temperature,
+            _ => {
+                self.mode = Some(opt);
+                &mut self.set_temperature
+            }
+        };
+        *field = Some(value);
+    }
+
+    fn native_args(&self) -> Result<Vec<String>, clap::Error> {
+        if self.help {
+            return Ok(vec!["--help".into()]);
+        } else if self.version {
+            return Ok(vec!["--version".into()]);
+        } else if self.location.as_deref() == Some("list")
+            || self.method.as_deref() == Some("list")
+        {
+            return Ok(vec!["list".into()]);
+        }
+
+        let mode = match self.mode {
+            Some('x') => "reset",
+            Some('O') => "set",
+            Some('o' | 'p') => "oneshot",
+            _ => "daemon",
+        };
+        let mut args = vec![mode.to_string()];
+        for (k, v) in self.options()? {
+            if let Some(v) = v {
+                args.extend([k.to_string(), v]);
+            }
+        }
+
+        if self.reset_ramps && self.mode != Some('x') {
+            args.push("--reset-ramps".into());
+        }
+        if self.disable_fade && self.mode.is_none() {
+            args.push("--disable-fade".into());
+        }
+        Ok(args)
+    }
+
+    /// Options of the command and their values if they are set
+    fn options(
+        &self,
+    ) -> Result<Vec<(&'static str, Option<String>)>, clap::Error> {
+        // redshift separates the values of day and night with a colon
+        let day_night =
+            |v: &Option<String>| v.as_ref().map(|v| v.replace(':', "-"));
+        let day = |v: &Option<String>| {
+            v.as_ref()
+                .and_then(|v| v.split(':').next())
+                .map(str::to_string)
+        };
+        let method = self.method.as_deref().map(method).transpose()?;
+        let location = self.location.as_deref().map(location).transpose()?;
+
+        let mut options = match self.mode {
+            Some('x') => vec![("--method", method)],
+            Some('O') => vec![
+                ("--temperature", self.set_temperature.clone()),
+                ("--gamma", self.gamma.clone()),
+                ("--brightness", day(&self.brightness)),
+                ("--method", method),
+            ],
+            _ => vec![
+                ("--temperature", day_night(&self.temperature)),
+                ("--gamma", self.gamma.clone()),
+                ("--brightness", day_night(&self.brightness)),
+                ("--location", location),
+                // print mode only prints the color settings
+                match self.mode {
+                    Some('p') => ("--method", Some("dummy".into())),
+                    _ => ("--method", method),
+                },
+            ],
+        };
+        options.push(("--config", self.config.clone()));
+        Ok(options)
+    }
+}
+
+/// Translate the `METHOD:key=value:...` syntax of redshift methods
+fn method(s: &str) -> Result<String, clap::Error> {
+    let mut opts = s.split(':');
+    let kind = opts.next().unwrap_or_default();
+    let kind = match kind {
+        "w32gdi" | "wingdi" => "win32gdi",
+        "randr" | "vidmode" | "drm" | "dummy" => kind,
+        _ => {
+            let msg =
+                format!("unsupported redshift adjustment method `{kind}`");
+            return Err(error(ErrorKind::InvalidValue, msg));
+        }
+    };
+
+    let (mut num, mut crtcs) = (None, None);
+    for opt in opts {
+        match (kind, opt.split_once('=')) {
+            ("randr" | "vidmode", Some(("screen", v)))
+            | ("drm", Some(("card", v))) => num = Some(v.to_string()),
+            ("randr" | "drm", Some(("crtc", v))) => {
+                crtcs = Some(v.to_string())
+            }
+            _ => {
+                let msg =
+                    format!("unsupported option `{opt}` of the {kind} method");
+                return Err(error(ErrorKind::InvalidValue, msg));
+            }
+        }
+    }
+    Ok(method_arg(kind, num, crtcs))
+}
+
+/// Translate the `PROVIDER:key=value:...` syntax of redshift location
+/// providers. Latitude and longitude are accepted as is
+fn location(s: &str) -> Result<String, clap::Error> {
+    let mut opts = s.split(':');
+    match opts.next().unwrap_or_default() {
+        "geoclue2" => Ok("geoclue2".into()),
+        "manual" => {
+            let (mut lat, mut lon) = (None, None);
+            for opt in opts {
+                match opt.split_once('=') {
+                    Some(("lat", v)) => lat = Some(v),
+                    Some(("lon", v)) => lon = Some(v),
+                    _ => {
+                        let msg = format!(
+                            "unsupported option `{opt}` of the manual location provider"
+                        );
+                        return Err(error(ErrorKind::InvalidValue, msg));
+                    }
+                }
+            }
+            match (lat, lon) {
+                (Some(lat), Some(lon)) => Ok(format!("{lat}:{lon}")),
+                _ => {
+                    let msg = "manual location requires both lat and lon";
+                    Err(error(ErrorKind::InvalidValue, msg.into()))
+                }
+            }
+        }
+        p if p.parse::<f64>().is_ok() => Ok(s.to_string()),
+        p => {
+            let msg = format!("unsupported redshift location provider `{p}`");
+            Err(error(ErrorKind::InvalidValue, msg))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    #[cfg(unix_without_macos)]
+    fn compat_args() -> Result<()> {
+        let native = |s: &str| -> Result<String> {
+            let args = s.split_whitespace().map(str::to_string).collect_vec();
+            let c = Compat::new(&args)?;
+            // the translated arguments must be valid
+            CliArgs::try_parse_from(
+                iter::once(PKG_NAME).chain(c.args.iter().map(|s| &**s)),
+            )?;
+            Ok(c.to_string())
+        };
+
+        assert_eq!(native("-O 4000")?, "reddish-shift set --temperature 4000");
+        assert_eq!(
+            native("-x -m vidmode")?,
+            "reddish-shift reset --method vidmode"
+        );
+        assert_eq!(
+            native("-l 51.5:0 -t 6500:3600 -b 1:0.8 -P -r")?,
+            "reddish-shift daemon --temperature 6500-3600 --brightness 1-0.8 \
+             --location 51.5:0 --reset-ramps --disable-fade"
+        );
+        assert_eq!(
+            native("-o -m randr:screen=0:crtc=62 -l manual:lat=10:lon=-20")?,
+            "reddish-shift oneshot --location 10:-20 --method randr:0:62"
+        );
+        assert_eq!(native("-vp")?, "reddish-shift oneshot --method dummy");
+        assert_eq!(native("-m list")?, "reddish-shift list");
+        assert!(Compat::new(&["-m".into(), "quartz".into()]).is_err());
+        assert!(Compat::new(&["-z".into()]).is_err());
+        Ok(())
+    }
+}
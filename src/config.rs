@@ -19,38 +19,68 @@
 #[cfg(windows)]
 use crate::gamma_win32gdi::Win32Gdi;
 #[cfg(unix_without_macos)]
-use crate::{gamma_drm::Drm, gamma_randr::Randr, gamma_vidmode::Vidmode};
+use crate::{
+    gamma_drm::Drm,
+    gamma_file::Recorder,
+    gamma_kwin::{self, Kwin},
+    gamma_mutter::Mutter,
+    gamma_randr::Randr,
+    gamma_vidmode::Vidmode,
+    gamma_vt::Vt,
+};
 
 use crate::{
     cli::{
-        CliArgs, CmdArgs, CmdInnerArgs, ColorSettingsArgs, ModeArgs, Verbosity,
+        CliArgs, CmdArgs, CmdInnerArgs, ColorSettingsArgs, ConfigArgs,
+        KeyValue, ModeArgs, SnapshotArgs, Verbosity,
     },
+    compat::{self, Compat},
+    curve::Curve,
     error::{
-        config::{ConfigError, ConfigFileError},
+        config::{
+            ConfigDiagnostic, ConfigDiagnosticKind, ConfigError,
+            ConfigFileError, OverrideError,
+        },
         parse::DayNightErrorType,
         VecError,
     },
+    state,
     types::{
-        AdjustmentMethodType, BrightnessRange, ColorSettings, DayNight,
-        GammaRange, LocationProviderType, Mode, TemperatureRange,
-        TransitionScheme,
+        AdjustmentMethodType, BlackLevelRange, BrightnessRange, ChannelsRange,
+        ColorMatrix, ColorSettings, ContrastRange, CurveRange, DayNight,
+        ElevationRange, GammaRange, InvertRange, Location,
+        LocationProviderType, Mode, SnapshotMode, Temperature,
+        TemperatureRange, TimeRanges, TintRange, TransitionScheme,
+        TEMPERATURE_NAMES,
     },
     types_display::WARN,
     utils::IsDefault,
-    AdjustmentMethod, LocationProvider, Manual,
+    Adjuster, AdjustmentMethod, LocationProvider, Manual, Timezone,
 };
 use chrono::{DateTime, Local};
 use clap::ColorChoice;
-use clap::Parser;
 #[cfg(unix)]
 use const_format::formatcp;
-use serde::{de, Deserialize, Deserializer};
+use itertools::Itertools;
+use serde::{
+    de::{self, IgnoredAny, MapAccess, Visitor},
+    ser, Deserialize, Deserializer, Serialize, Serializer,
+};
 use std::{
-    fmt::Display, fs::File, io::Read, marker::PhantomData, path::Path,
-    str::FromStr, time::Duration,
+    collections::{BTreeMap, BTreeSet},
+    env,
+    ffi::OsString,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::Read,
+    marker::PhantomData,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
 };
-use toml::Value;
-use tracing::warn;
+use toml::{Spanned, Table, Value};
+use tracing::{info, warn};
 
 pub const PKG_NAME: &str = env!("CARGO_PKG_NAME");
 // Length of fade in numbers of fade's sleep durations
@@ -58,6 +88,11 @@ pub const FADE_STEPS: u8 = 40;
 // Duration of sleep between screen updates (milliseconds)
 pub const DEFAULT_SLEEP_DURATION: u64 = 5000;
 pub const DEFAULT_SLEEP_DURATION_SHORT: u64 = 100;
+// Duration of the fade of the oneshot, set and reset commands (milliseconds)
+pub const DEFAULT_FADE_DURATION: u64 =
+    FADE_STEPS as u64 * DEFAULT_SLEEP_DURATION_SHORT;
+// Number of entries of each channel written by the export command
+pub const DEFAULT_EXPORT_SIZE: u16 = 256;
 
 #[cfg(unix_without_macos)]
 pub const RANDR_MINOR_VERSION_MIN: u32 = 3;
@@ -66,9 +101,11 @@ pub const RANDR_MAJOR_VERSION: u32 = 1;
 
 /// Merge of cli arguments and config files from highest priority to lowest:
 /// 1. CLI arguments
-/// 2. User config file
-/// 3. System config file (Unix-like OS's only)
-/// 4. Default values
+/// 2. --set arguments
+/// 3. REDDISH_SHIFT_<KEY> environment variables
+/// 4. User config file
+/// 5. System config file (Unix-like OS's only)
+/// 6. Default values
 #[derive(Debug)]
 pub struct Config {
     pub mode: Mode,
@@ -80,10 +117,18 @@ pub struct Config {
     pub disable_fade: bool,
     pub sleep_duration: Duration,
     pub sleep_duration_short: Duration,
+    pub monitor_ramps: bool,
+    pub adopt_foreign_ramps: bool,
+    // Duration of the fade of the oneshot, set and reset commands
+    pub fade: Option<Duration>,
 
     pub location: LocationProvider,
     pub method: AdjustmentMethod,
     pub time: fn() -> DateTime<Local>,
+    // Key of the persisted color settings of the adjustment method
+    pub state_key: String,
+    // Key of the original gamma ramps of the adjustment method
+    pub original_key: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -97,17 +142,51 @@ pub struct ConfigBuilder {
     scheme: TransitionScheme,
     sleep_duration: Duration,
     sleep_duration_short: Duration,
+    monitor_ramps: bool,
+    adopt_foreign_ramps: bool,
+    fade: Option<Duration>,
 
     location: LocationProviderType,
     method: Option<AdjustmentMethodType>,
+    color_matrix: Option<ColorMatrix>,
+    presets: BTreeMap<String, ColorSettings>,
+    day_preset: Option<String>,
+    night_preset: Option<String>,
+    // Values of the set command, applied on top of the day field when the
+    // persisted color settings are known
+    set_args: Option<ColorSettingsArgs>,
+
+    // Where each field was set. Fields that are missing have default values
+    sources: BTreeMap<&'static str, Source>,
+    files: Vec<PathBuf>,
+}
+
+/// Origin of the value of a configuration field
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    File(PathBuf),
+    Env(String),
+    Set,
+    Cli,
 }
 
+// Prefix of the environment variables of config keys
+// e.g. REDDISH_SHIFT_SLEEP_DURATION for sleep-duration
+const ENV_PREFIX: &str = "REDDISH_SHIFT_";
+
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct ConfigFile {
     temperature: Option<Either<u16, TemperatureRange>>,
+    tint: Option<Either<f64, TintRange>>,
     gamma: Option<Either<f64, GammaRange>>,
     brightness: Option<Either<f64, BrightnessRange>>,
+    channels: Option<ChannelsRange>,
+    invert: Option<Either<bool, InvertRange>>,
+    black_level: Option<Either<f64, BlackLevelRange>>,
+    contrast: Option<Either<f64, ContrastRange>>,
+    #[serde(default, deserialize_with = "curve_range")]
+    curve: Option<CurveRange>,
     scheme: Option<TransitionScheme>,
     location: Option<LocationProviderType>,
     method: Option<AdjustmentMethodType>,
@@ -115,8 +194,41 @@ struct ConfigFile {
     disable_fade: Option<bool>,
     sleep_duration_short: Option<u16>,
     sleep_duration: Option<u16>,
+    monitor_ramps: Option<bool>,
+    adopt_foreign_ramps: Option<bool>,
+    color_matrix: Option<ColorMatrix>,
+    day: Option<String>,
+    night: Option<String>,
+    preset: Option<BTreeMap<String, ColorSettings>>,
+
+    // Color fields set by the layers after the day and night presets. They
+    // take precedence over the presets
+    #[serde(skip)]
+    day_overrides: BTreeSet<&'static str>,
+    #[serde(skip)]
+    night_overrides: BTreeSet<&'static str>,
+    #[serde(skip)]
+    sources: BTreeMap<&'static str, Source>,
+    #[serde(skip)]
+    files: Vec<PathBuf>,
 }
 
+// Keys of the fields of ConfigFile that are replaced by a preset
+const COLOR_KEYS: [&str; 9] = [
+    "temperature",
+    "tint",
+    "gamma",
+    "brightness",
+    "channels",
+    "invert",
+    "black-level",
+    "contrast",
+    "curve",
+];
+
+// Path of an included config file and the span of the include key's value
+type Include = (PathBuf, Range<usize>);
+
 #[derive(Debug, Clone, Default)]
 struct Either<U: TryInto<T>, T> {
     t: T,
@@ -127,20 +239,43 @@ impl ConfigBuilder {
     pub fn new(
         logging_init: impl FnOnce(Verbosity, ColorChoice),
     ) -> Result<Self, ConfigError> {
-        let cli_args = CliArgs::parse();
+        let (cli_args, compat) = compat::parse();
         logging_init(cli_args.verbosity, cli_args.color.unwrap_or_default());
+        if let Some(c) = compat.filter(Compat::verbose) {
+            info!("{c}");
+        }
 
         let mut cfg = Self::default();
-        if let Some(path) = Self::config_path_from_mode(&cli_args.mode) {
-            let config_file = ConfigFile::new(path)?;
-            cfg.merge_with_config_file(config_file);
+        if let Some((path, set)) = Self::config_args_from_mode(&cli_args.mode)
+        {
+            let mut config_file = ConfigFile::new(path)?;
+            config_file.merge_overrides(env::vars_os(), set)?;
+            cfg.merge_with_config_file(config_file)?;
         }
-        cfg.merge_with_cli_args(cli_args);
+        cfg.merge_with_cli_args(cli_args)?;
 
         Ok(cfg)
     }
 
-    pub fn build(self) -> Result<Config, ConfigError> {
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    /// Config files that were read, from lowest priority to highest
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn build(mut self) -> Result<Config, ConfigError> {
+        let (method, keys) = self.build_method()?;
+        Ok(self.into_config(method, keys))
+    }
+
+    fn into_config(
+        self,
+        method: AdjustmentMethod,
+        [state_key, original_key]: [String; 2],
+    ) -> Config {
         let Self {
             mode,
             day,
@@ -150,14 +285,26 @@ impl ConfigBuilder {
             scheme,
             sleep_duration,
             sleep_duration_short,
+            monitor_ramps,
+            adopt_foreign_ramps,
+            fade,
             location,
-            method,
+            method: _,
+            color_matrix: _,
+            presets: _,
+            day_preset: _,
+            night_preset: _,
+            set_args: _,
+            sources: _,
+            files: _,
         } = self;
 
-        Ok(Config {
-            location: Self::get_location_provider(location, mode, &scheme),
-            method: Self::get_adjustment_method(method, mode)?,
+        Config {
+            location: Self::get_location_provider(location, &mode, &scheme),
+            method,
             time: Local::now,
+            state_key,
+            original_key,
             mode,
             day,
             night,
@@ -166,22 +313,131 @@ impl ConfigBuilder {
             disable_fade,
             sleep_duration_short,
             sleep_duration,
-        })
+            monitor_ramps,
+            adopt_foreign_ramps,
+            fade,
+        }
+    }
+
+    /// Build the adjustment method and apply the settings that depend on
+    /// it: the sleep duration of kwin and the color settings of set
+    fn build_method(
+        &mut self,
+    ) -> Result<(AdjustmentMethod, [String; 2]), ConfigError> {
+        let kind = self.method.clone();
+        let method = Self::get_adjustment_method(
+            kind.clone(),
+            &self.mode,
+            self.color_matrix.take(),
+        )?;
+        #[cfg(unix_without_macos)]
+        if let AdjustmentMethod::Kwin(_) = method {
+            self.sleep_duration =
+                Self::kwin_sleep_duration(&self.mode, self.sleep_duration);
+        }
+        let keys = Self::state_keys(kind, &method);
+        if let Some(args) = self.set_args.take() {
+            let day = std::mem::take(&mut self.day);
+            let last = || state::load(&keys[0]);
+            self.day = Self::apply_set_args(args, day, last);
+        }
+        Ok((method, keys))
+    }
+
+    /// Keys of the persisted color settings and of the original ramps. The
+    /// color settings belong to the method with the crtcs it adjusts. Crtcs
+    /// share their state however they were selected, e.g. by their output or
+    /// the edid of their monitor
+    fn state_keys(
+        kind: Option<AdjustmentMethodType>,
+        method: &AdjustmentMethod,
+    ) -> [String; 2] {
+        // an auto-detected method has the default parameters
+        let Some(kind) = kind.or_else(|| method.to_string().parse().ok())
+        else {
+            return [method.to_string(), method.to_string()];
+        };
+        #[cfg(unix_without_macos)]
+        let kind = {
+            use crate::types::CrtcSelector;
+            use AdjustmentMethodType::*;
+            let ids = method.saved_ramps().into_iter();
+            let crtcs = ids.map(|(id, _)| CrtcSelector::Id(id)).collect();
+            match kind {
+                Drm { card_num, .. } => Drm {
+                    card_num: Some(card_num.unwrap_or_default()),
+                    crtcs,
+                },
+                Randr { screen_num, .. } => Randr { screen_num, crtcs },
+                Mutter { .. } => Mutter { crtcs },
+                kind => kind,
+            }
+        };
+        let original = Self::original_key(&kind);
+        [kind.to_string(), original]
+    }
+
+    /// The original ramps belong to the method with its card or screen only.
+    /// They are looked up by crtc, so that any selection of crtcs finds them
+    fn original_key(kind: &AdjustmentMethodType) -> String {
+        #[cfg(unix_without_macos)]
+        {
+            use AdjustmentMethodType::*;
+            let crtcs = Vec::new();
+            let kind = match kind {
+                Drm { card_num, .. } => Drm {
+                    card_num: *card_num,
+                    crtcs,
+                },
+                Randr { screen_num, .. } => Randr {
+                    screen_num: *screen_num,
+                    crtcs,
+                },
+                Mutter { .. } => Mutter { crtcs },
+                File { path, .. } => File {
+                    path: path.clone(),
+                    ramp_size: None,
+                    crtcs: Vec::new(),
+                },
+                kind => kind.clone(),
+            };
+            kind.to_string()
+        }
+        #[cfg(not(unix_without_macos))]
+        kind.to_string()
+    }
+
+    /// The color temperature of kwin is a preview that times out. Only the
+    /// daemon keeps it, as long as it updates more often than the timeout
+    #[cfg(unix_without_macos)]
+    fn kwin_sleep_duration(mode: &Mode, sleep_duration: Duration) -> Duration {
+        match mode {
+            Mode::Daemon => sleep_duration.min(gamma_kwin::MAX_SLEEP_DURATION),
+            Mode::Oneshot | Mode::Set | Mode::Replay { .. } => {
+                let s = format!(
+                    "kwin reverts the color temperature after {}s, use the \
+                     daemon command to keep it",
+                    gamma_kwin::PREVIEW_TIMEOUT.as_secs()
+                );
+                warn!("{WARN}warning:{WARN:#} {s}");
+                sleep_duration
+            }
+            _ => sleep_duration,
+        }
     }
 
     fn get_location_provider(
         kind: LocationProviderType,
-        mode: Mode,
+        mode: &Mode,
         scheme: &TransitionScheme,
     ) -> LocationProvider {
+        let is_used = matches!(
+            (mode, scheme),
+            (Mode::Daemon | Mode::Oneshot, TransitionScheme::Elev(_))
+        );
         match kind {
             LocationProviderType::Manual(l) => {
-                if let (
-                    Mode::Daemon | Mode::Oneshot,
-                    TransitionScheme::Elev(_),
-                    true,
-                ) = (mode, scheme, l.is_default())
-                {
+                if is_used && l.is_default() {
                     warn!(
                         "{WARN}warning:{WARN:#} using default location ({l})"
                     );
@@ -191,18 +447,49 @@ impl ConfigBuilder {
             LocationProviderType::Geoclue2 => {
                 LocationProvider::Geoclue2(Default::default())
             }
+            // without a timezone, Null Island is used like before
+            LocationProviderType::Timezone => match Timezone::new() {
+                Ok(t) => LocationProvider::Timezone(t),
+                Err(e) => {
+                    let l = Location::default();
+                    if is_used {
+                        let s = format!("using default location ({l}): {e}");
+                        warn!("{WARN}warning:{WARN:#} {s}");
+                    }
+                    LocationProvider::Manual(Manual::new(l))
+                }
+            },
         }
     }
 
     #[allow(clippy::too_many_lines)]
     fn get_adjustment_method(
         kind: Option<AdjustmentMethodType>,
-        mode: Mode,
+        mode: &Mode,
+        color_matrix: Option<ColorMatrix>,
     ) -> Result<AdjustmentMethod, ConfigError> {
+        #[cfg(unix_without_macos)]
+        let is_drm = matches!(kind, Some(AdjustmentMethodType::Drm { .. }));
+        #[cfg(not(unix_without_macos))]
+        let is_drm = false;
+        if color_matrix.is_some() && kind.is_some() && !is_drm {
+            let s = "color matrix is only supported by the drm method";
+            warn!("{WARN}warning:{WARN:#} {s}");
+        }
+
         match (mode, kind) {
-            (Mode::Print, _) => {
-                Ok(AdjustmentMethod::Dummy(Default::default()))
-            }
+            (
+                Mode::Print
+                | Mode::List { .. }
+                | Mode::ConfigShow
+                | Mode::ConfigCheck
+                | Mode::ConfigImport(_)
+                | Mode::Export { .. }
+                | Mode::Snapshot(
+                    SnapshotMode::List | SnapshotMode::Diff(_, Some(_)),
+                ),
+                _,
+            ) => Ok(AdjustmentMethod::Dummy(Default::default())),
 
             (_, Some(m)) => match m {
                 AdjustmentMethodType::Dummy => {
@@ -212,15 +499,39 @@ impl ConfigBuilder {
                 }
                 #[cfg(unix_without_macos)]
                 AdjustmentMethodType::Drm { card_num, crtcs } => {
-                    Ok(AdjustmentMethod::Drm(Drm::new(card_num, crtcs)?))
+                    let m = Drm::new(card_num, crtcs, color_matrix)?;
+                    Ok(AdjustmentMethod::Drm(m))
                 }
                 #[cfg(unix_without_macos)]
                 AdjustmentMethodType::Randr { screen_num, crtcs } => {
-                    Ok(AdjustmentMethod::Randr(Randr::new(screen_num, crtcs)?))
+                    let m = Randr::new(screen_num, crtcs)?;
+                    Ok(AdjustmentMethod::Randr(Box::new(m)))
                 }
                 #[cfg(unix_without_macos)]
                 AdjustmentMethodType::Vidmode { screen_num } => {
-                    Ok(AdjustmentMethod::Vidmode(Vidmode::new(screen_num)?))
+                    let m = Vidmode::new(screen_num)?;
+                    Ok(AdjustmentMethod::Vidmode(Box::new(m)))
+                }
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::Mutter { crtcs } => {
+                    Ok(AdjustmentMethod::Mutter(Mutter::new(crtcs)?))
+                }
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::Kwin => {
+                    Ok(AdjustmentMethod::Kwin(Kwin::new()?))
+                }
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::Vt { tty_num } => {
+                    Ok(AdjustmentMethod::Vt(Vt::new(tty_num)?))
+                }
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::File {
+                    path,
+                    ramp_size,
+                    crtcs,
+                } => {
+                    let m = Recorder::new(path, ramp_size, crtcs)?;
+                    Ok(AdjustmentMethod::File(m))
                 }
 
                 #[cfg(windows)]
@@ -234,24 +545,58 @@ impl ConfigBuilder {
                 warn!("{WARN}warning:{WARN:#} {s}");
                 let r = Err::<AdjustmentMethod, _>(VecError::default());
 
+                // X methods seem to work through Xwayland but do not affect
+                // the display, so the compositor is asked first
+                #[cfg(unix_without_macos)]
+                let r = match env::var_os("WAYLAND_DISPLAY") {
+                    None => r,
+                    Some(_) => r
+                        .or_else(|errs| -> Result<_, VecError<_>> {
+                            let m = Mutter::new(Vec::new())
+                                .map_err(|e| errs.push(e.into()))?;
+                            Ok(AdjustmentMethod::Mutter(m))
+                        })
+                        .or_else(|errs| -> Result<_, VecError<_>> {
+                            let m = Kwin::new()
+                                .map_err(|e| errs.push(e.into()))?;
+                            Ok(AdjustmentMethod::Kwin(m))
+                        }),
+                };
+
                 #[cfg(unix_without_macos)]
                 let r = r
                     .or_else(|errs| -> Result<_, VecError<_>> {
                         let m = Randr::new(None, Vec::new())
                             .map_err(|e| errs.push(e.into()))?;
-                        Ok(AdjustmentMethod::Randr(m))
+                        Ok(AdjustmentMethod::Randr(Box::new(m)))
                     })
                     .or_else(|errs| -> Result<_, VecError<_>> {
                         let m = Vidmode::new(None)
                             .map_err(|e| errs.push(e.into()))?;
-                        Ok(AdjustmentMethod::Vidmode(m))
+                        Ok(AdjustmentMethod::Vidmode(Box::new(m)))
                     })
                     .or_else(|errs| -> Result<_, VecError<_>> {
-                        let m = Drm::new(None, Vec::new())
+                        let m = Drm::new(None, Vec::new(), color_matrix)
                             .map_err(|e| errs.push(e.into()))?;
                         Ok(AdjustmentMethod::Drm(m))
                     });
 
+                // The console palette is only tried outside of a graphical
+                // session, where tinting it would go unnoticed
+                #[cfg(unix_without_macos)]
+                let is_graphical = ["DISPLAY", "WAYLAND_DISPLAY"]
+                    .iter()
+                    .any(|v| env::var_os(v).is_some());
+                #[cfg(unix_without_macos)]
+                let r = match is_graphical {
+                    true => r,
+                    false => r.or_else(|errs| -> Result<_, VecError<_>> {
+                        let m =
+                            Vt::new(None).map_err(|e| errs.push(e.into()))?;
+                        Ok(AdjustmentMethod::Vt(m))
+                    }),
+                };
+
                 #[cfg(windows)]
                 let r = r.or_else(|errs| -> Result<_, VecError<_>> {
                     let m =
@@ -264,36 +609,49 @@ impl ConfigBuilder {
         }
     }
 
-    fn config_path_from_mode(mode: &ModeArgs) -> Option<Option<&Path>> {
+    /// Path of the config file and the --set arguments
+    fn config_args_from_mode(
+        mode: &ModeArgs,
+    ) -> Option<(Option<&Path>, &[KeyValue])> {
         match mode {
-            ModeArgs::Print { .. } => None,
+            ModeArgs::Print { .. }
+            | ModeArgs::List { .. }
+            | ModeArgs::Snapshot(SnapshotArgs::List)
+            | ModeArgs::Config(ConfigArgs::Import { .. }) => None,
+            // replaced with the translated arguments while parsing
+            ModeArgs::Compat { .. } => unreachable!(),
+            ModeArgs::Config(ConfigArgs::Check { config }) => {
+                Some((config.as_deref(), &[]))
+            }
+            ModeArgs::Config(ConfigArgs::Show { c }) => {
+                Some((c.i.config.as_deref(), &c.i.set))
+            }
             ModeArgs::Daemon {
-                c:
-                    CmdArgs {
-                        i: CmdInnerArgs { config, .. },
-                        ..
-                    },
+                c: CmdArgs { i, .. },
                 ..
             }
             | ModeArgs::Oneshot {
-                c:
-                    CmdArgs {
-                        i: CmdInnerArgs { config, .. },
-                        ..
-                    },
-            }
-            | ModeArgs::Set {
-                i: CmdInnerArgs { config, .. },
+                c: CmdArgs { i, .. },
                 ..
             }
-            | ModeArgs::Reset {
-                i: CmdInnerArgs { config, .. },
-            } => Some(config.as_deref()),
+            | ModeArgs::Set { i, .. }
+            | ModeArgs::Reset { i, .. }
+            | ModeArgs::Status { i }
+            | ModeArgs::Export { i, .. }
+            | ModeArgs::Replay { i, .. }
+            | ModeArgs::Snapshot(
+                SnapshotArgs::Save { i, .. }
+                | SnapshotArgs::Restore { i, .. }
+                | SnapshotArgs::Diff { i, .. },
+            ) => Some((i.config.as_deref(), &i.set)),
         }
     }
 
     #[allow(clippy::too_many_lines)]
-    fn merge_with_cli_args(&mut self, cli_args: CliArgs) {
+    fn merge_with_cli_args(
+        &mut self,
+        cli_args: CliArgs,
+    ) -> Result<(), ConfigFileError> {
         let CliArgs {
             mode,
             verbosity: _,
@@ -306,68 +664,240 @@ impl ConfigBuilder {
                 disable_fade,
                 sleep_duration,
                 sleep_duration_short,
+                monitor_ramps,
+                adopt_foreign_ramps,
             } => {
                 if let Some(t) = sleep_duration {
                     self.sleep_duration = Duration::from_millis(t as u64);
+                    self.sources.insert("sleep-duration", Source::Cli);
                 }
                 if let Some(t) = sleep_duration_short {
                     self.sleep_duration_short =
                         Duration::from_millis(t as u64);
+                    self.sources.insert("sleep-duration-short", Source::Cli);
                 }
                 if let Some(t) = disable_fade {
                     self.disable_fade = t;
+                    self.sources.insert("disable-fade", Source::Cli);
+                }
+                if let Some(t) = monitor_ramps {
+                    self.monitor_ramps = t;
+                    self.sources.insert("monitor-ramps", Source::Cli);
+                }
+                if let Some(t) = adopt_foreign_ramps {
+                    self.adopt_foreign_ramps = t;
+                    self.sources.insert("adopt-foreign-ramps", Source::Cli);
                 }
                 self.merge_with_cmd_args(c);
                 self.mode = Mode::Daemon;
             }
-            ModeArgs::Oneshot { c } => {
+            ModeArgs::Oneshot { fade, c } => {
                 self.merge_with_cmd_args(c);
+                self.fade = fade.map(|t| Duration::from_millis(t as u64));
                 self.mode = Mode::Oneshot;
             }
-            ModeArgs::Set { cs, i } => {
+            ModeArgs::Set { fade, cs, i } => {
                 self.merge_with_inner_cmd_args(i);
-                self.day = cs.into();
+                self.merge_with_color_settings_args(cs)?;
+                self.fade = fade.map(|t| Duration::from_millis(t as u64));
                 self.mode = Mode::Set;
             }
-            ModeArgs::Reset { i } => {
+            ModeArgs::Reset { original, fade, i } => {
                 self.merge_with_inner_cmd_args(i);
-                self.mode = Mode::Reset;
+                self.fade = fade.map(|t| Duration::from_millis(t as u64));
+                self.mode = Mode::Reset { original };
             }
             ModeArgs::Print { location } => {
-                self.location = location;
+                if let Some(t) = location {
+                    self.location = t;
+                }
                 self.mode = Mode::Print;
             }
+            ModeArgs::List { json } => {
+                self.mode = Mode::List { json };
+            }
+            ModeArgs::Config(ConfigArgs::Show { c }) => {
+                self.merge_with_cmd_args(*c);
+                self.mode = Mode::ConfigShow;
+            }
+            ModeArgs::Config(ConfigArgs::Check { config: _ }) => {
+                self.mode = Mode::ConfigCheck;
+            }
+            ModeArgs::Config(ConfigArgs::Import { path }) => {
+                self.mode = Mode::ConfigImport(path);
+            }
+            ModeArgs::Compat { .. } => unreachable!(),
+            ModeArgs::Snapshot(SnapshotArgs::Save { name, i }) => {
+                self.merge_with_inner_cmd_args(i);
+                self.mode = Mode::Snapshot(SnapshotMode::Save(name));
+            }
+            ModeArgs::Snapshot(SnapshotArgs::Restore { name, i }) => {
+                self.merge_with_inner_cmd_args(i);
+                self.mode = Mode::Snapshot(SnapshotMode::Restore(name));
+            }
+            ModeArgs::Snapshot(SnapshotArgs::List) => {
+                self.mode = Mode::Snapshot(SnapshotMode::List);
+            }
+            ModeArgs::Status { i } => {
+                self.merge_with_inner_cmd_args(i);
+                self.mode = Mode::Status;
+            }
+            ModeArgs::Snapshot(SnapshotArgs::Diff { name, other, i }) => {
+                self.merge_with_inner_cmd_args(i);
+                self.mode = Mode::Snapshot(SnapshotMode::Diff(name, other));
+            }
+            ModeArgs::Export {
+                format,
+                size,
+                output,
+                cs,
+                i,
+            } => {
+                self.merge_with_inner_cmd_args(i);
+                self.merge_with_color_settings_args(cs)?;
+                let size = size.unwrap_or(DEFAULT_EXPORT_SIZE);
+                self.mode = Mode::Export {
+                    format,
+                    size,
+                    output,
+                };
+            }
+            ModeArgs::Replay { path, delay, i } => {
+                self.merge_with_inner_cmd_args(i);
+                self.mode = Mode::Replay { path, delay };
+            }
+        }
+        Ok(())
+    }
+
+    // for the set and export commands, color settings are stored in the day
+    // field
+    fn merge_with_color_settings_args(
+        &mut self,
+        args: ColorSettingsArgs,
+    ) -> Result<(), ConfigFileError> {
+        self.day = match &args.preset {
+            Some(name) => self.preset(name)?,
+            None => ColorSettings::default(),
+        };
+        self.set_args = Some(args);
+        Ok(())
+    }
+
+    /// Without a preset, relative values are applied to the last color
+    /// settings that were applied using the same adjustment method
+    fn apply_set_args(
+        args: ColorSettingsArgs,
+        day: ColorSettings,
+        last: impl FnOnce() -> Option<ColorSettings>,
+    ) -> ColorSettings {
+        let ColorSettingsArgs {
+            preset,
+            temperature,
+            tint,
+            gamma,
+            brightness,
+            channels,
+            invert,
+            black_level,
+            contrast,
+            curve,
+        } = args;
+
+        let is_relative = [
+            temperature.is_some_and(|t| t.is_relative()),
+            gamma.is_some_and(|t| t.is_relative()),
+            brightness.is_some_and(|t| t.is_relative()),
+        ];
+        let mut cs = match preset {
+            None if is_relative.contains(&true) => last().unwrap_or_default(),
+            _ => day,
+        };
+        cs.temp = temperature.map_or(cs.temp, |t| t.apply(cs.temp));
+        cs.gamma = gamma.map_or(cs.gamma, |t| t.apply(cs.gamma));
+        cs.brght = brightness.map_or(cs.brght, |t| t.apply(cs.brght));
+        cs.tint = tint.unwrap_or(cs.tint);
+        cs.channels = channels.unwrap_or(cs.channels);
+        cs.invert = invert.unwrap_or(cs.invert);
+        cs.black = black_level.unwrap_or(cs.black);
+        cs.contrast = contrast.unwrap_or(cs.contrast);
+        if let Some(t) = curve {
+            cs.curve = t;
+        }
+        cs
+    }
+
+    /// Color settings of a preset of the config files or a built-in one
+    fn preset(&self, name: &str) -> Result<ColorSettings, ConfigFileError> {
+        match (self.presets.get(name), Temperature::from_name(name)) {
+            (Some(cs), _) => Ok(cs.clone()),
+            (None, Some(temp)) => Ok(ColorSettings {
+                temp,
+                ..Default::default()
+            }),
+            (None, None) => {
+                let names = self.presets.keys().map(String::as_str);
+                let mut names = names.chain(TEMPERATURE_NAMES.map(|(n, _)| n));
+                Err(ConfigFileError::UnknownPreset(
+                    name.into(),
+                    names.join(", "),
+                ))
+            }
+        }
+    }
+
+    /// Set the day and night values of a color setting from the command line
+    fn set_range<T>(
+        &mut self,
+        key: &'static str,
+        range: Option<DayNight<T>>,
+        field: impl Fn(&mut ColorSettings) -> &mut T,
+    ) {
+        if let Some(t) = range {
+            *field(&mut self.day) = t.day;
+            *field(&mut self.night) = t.night;
+            self.sources.insert(key, Source::Cli);
         }
     }
 
     fn merge_with_cmd_args(&mut self, args: CmdArgs) {
         let CmdArgs {
             temperature,
+            tint,
             brightness,
             gamma,
+            channels,
+            invert,
+            black_level,
+            contrast,
+            curve,
             scheme,
             location,
             i,
         } = args;
 
-        if let Some(t) = temperature {
-            self.day.temp = t.day;
-            self.night.temp = t.night;
-        }
-        if let Some(t) = brightness {
-            self.day.brght = t.day;
-            self.night.brght = t.night;
-        }
-        if let Some(t) = gamma {
-            self.day.gamma = t.day;
-            self.night.gamma = t.night;
+        self.set_range("temperature", temperature, |c| &mut c.temp);
+        self.set_range("tint", tint, |c| &mut c.tint);
+        self.set_range("brightness", brightness, |c| &mut c.brght);
+        self.set_range("gamma", gamma, |c| &mut c.gamma);
+        self.set_range("channels", channels, |c| &mut c.channels);
+        self.set_range("invert", invert, |c| &mut c.invert);
+        self.set_range("black-level", black_level, |c| &mut c.black);
+        self.set_range("contrast", contrast, |c| &mut c.contrast);
+        if let Some(mut t) = curve {
+            self.night.curve = t.pop().unwrap_or_default();
+            self.day.curve =
+                t.pop().unwrap_or_else(|| self.night.curve.clone());
+            self.sources.insert("curve", Source::Cli);
         }
 
         if let Some(t) = scheme {
             self.scheme = t;
+            self.sources.insert("scheme", Source::Cli);
         }
         if let Some(t) = location {
             self.location = t;
+            self.sources.insert("location", Source::Cli);
         }
         self.merge_with_inner_cmd_args(i);
     }
@@ -375,37 +905,68 @@ impl ConfigBuilder {
     fn merge_with_inner_cmd_args(&mut self, args: CmdInnerArgs) {
         let CmdInnerArgs {
             config: _,
+            set: _,
             reset_ramps,
             method,
+            color_matrix,
         } = args;
 
         if let Some(t) = reset_ramps {
             self.reset_ramps = t;
+            self.sources.insert("reset-ramps", Source::Cli);
+        }
+        if let Some(t) = color_matrix {
+            self.color_matrix = Some(t);
+            self.sources.insert("color-matrix", Source::Cli);
         }
         if let Some(t) = method {
             self.method = Some(t);
+            self.sources.insert("method", Source::Cli);
         }
     }
 
     #[allow(clippy::too_many_lines)]
-    fn merge_with_config_file(&mut self, config: ConfigFile) {
+    fn merge_with_config_file(
+        &mut self,
+        config: ConfigFile,
+    ) -> Result<(), ConfigFileError> {
         let ConfigFile {
             temperature,
+            tint,
             brightness,
             gamma,
+            channels,
+            invert,
+            black_level,
+            contrast,
+            curve,
             reset_ramps,
             scheme,
             disable_fade,
             sleep_duration_short,
             sleep_duration,
+            monitor_ramps,
+            adopt_foreign_ramps,
+            color_matrix,
             method,
             location,
+            day,
+            night,
+            preset,
+            day_overrides,
+            night_overrides,
+            sources,
+            files,
         } = config;
 
         if let Some(t) = temperature {
             self.day.temp = t.t.day;
             self.night.temp = t.t.night;
         }
+        if let Some(t) = tint {
+            self.day.tint = t.t.day;
+            self.night.tint = t.t.night;
+        }
         if let Some(t) = brightness {
             self.day.brght = t.t.day;
             self.night.brght = t.t.night;
@@ -414,6 +975,26 @@ impl ConfigBuilder {
             self.day.gamma = t.t.day;
             self.night.gamma = t.t.night;
         }
+        if let Some(t) = channels {
+            self.day.channels = t.day;
+            self.night.channels = t.night;
+        }
+        if let Some(t) = invert {
+            self.day.invert = t.t.day;
+            self.night.invert = t.t.night;
+        }
+        if let Some(t) = black_level {
+            self.day.black = t.t.day;
+            self.night.black = t.t.night;
+        }
+        if let Some(t) = contrast {
+            self.day.contrast = t.t.day;
+            self.night.contrast = t.t.night;
+        }
+        if let Some(t) = curve {
+            self.day.curve = t.day;
+            self.night.curve = t.night;
+        }
 
         if let Some(t) = reset_ramps {
             self.reset_ramps = t;
@@ -431,6 +1012,15 @@ impl ConfigBuilder {
         if let Some(t) = sleep_duration {
             self.sleep_duration = Duration::from_millis(t as u64);
         }
+        if let Some(t) = monitor_ramps {
+            self.monitor_ramps = t;
+        }
+        if let Some(t) = adopt_foreign_ramps {
+            self.adopt_foreign_ramps = t;
+        }
+        if let Some(t) = color_matrix {
+            self.color_matrix = Some(t);
+        }
 
         if let Some(t) = location {
             self.location = t;
@@ -438,6 +1028,27 @@ impl ConfigBuilder {
         if let Some(t) = method {
             self.method = Some(t);
         }
+
+        // presets take precedence over the color fields of their layer and
+        // of the layers before it
+        if let Some(t) = preset {
+            self.presets.extend(t);
+        }
+        if let Some(t) = day {
+            let mut cs = self.preset(&t)?;
+            copy_color_fields(&day_overrides, &self.day, &mut cs);
+            self.day = cs;
+            self.day_preset = Some(t);
+        }
+        if let Some(t) = night {
+            let mut cs = self.preset(&t)?;
+            copy_color_fields(&night_overrides, &self.night, &mut cs);
+            self.night = cs;
+            self.night_preset = Some(t);
+        }
+        self.sources.extend(sources);
+        self.files = files;
+        Ok(())
     }
 }
 
@@ -458,69 +1069,581 @@ impl ConfigFile {
             .ok_or(ConfigFileError::ConfigDirNotFound)?;
 
         let mut config = Self::default();
+        let mut errs = Vec::new();
+        #[cfg(unix)]
+        config.read_with_drop_ins(system_config, &mut errs)?;
+        config.read_with_drop_ins(user_config, &mut errs)?;
+        match errs.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigFileError::Invalid(VecError(errs))),
+        }
+    }
+
+    /// Read a config file followed by the files in the config.d directory
+    /// next to it in lexical order
+    fn read_with_drop_ins(
+        &mut self,
+        path: &Path,
+        errs: &mut Vec<ConfigDiagnostic>,
+    ) -> Result<(), ConfigFileError> {
+        if path.is_file() {
+            self.read(path, &mut Vec::new(), errs)?;
+        }
+
+        let dir = path.parent().unwrap_or(Path::new(".")).join("config.d");
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Ok(());
+        };
+        let mut drop_ins = entries
+            .filter_map(|e| Some(e.ok()?.path()))
+            .filter(|p| p.extension() == Some("toml".as_ref()) && p.is_file())
+            .collect::<Vec<_>>();
+        drop_ins.sort();
+        for p in drop_ins {
+            self.read(&p, &mut Vec::new(), errs)?;
+        }
+        Ok(())
+    }
+
+    /// Read a config file. Included files are merged before the file itself
+    /// so that its keys take precedence. `stack` holds the chain of files
+    /// that included this one
+    fn read(
+        &mut self,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        errs: &mut Vec<ConfigDiagnostic>,
+    ) -> Result<(), ConfigFileError> {
         let mut buf = String::new();
-        let mut read = |path: &Path| -> Result<(), ConfigFileError> {
-            if path.is_file() {
-                (|| File::open(path)?.read_to_string(&mut buf))().map_err(
-                    |e| ConfigFileError::OpenFailed(e, path.into()),
-                )?;
-                let cfg = toml::from_str(&buf).map_err(|e| {
-                    ConfigFileError::DeserializeFailed(e, path.into())
-                })?;
-                config.merge(cfg);
-                Ok(())
+        (|| File::open(path)?.read_to_string(&mut buf))()
+            .map_err(|e| ConfigFileError::OpenFailed(e, path.into()))?;
+        let (cfg, includes) = match Self::parse(&buf, path) {
+            Ok(t) => t,
+            Err(e) => {
+                errs.extend(e);
+                return Ok(());
+            }
+        };
+
+        stack.push(fs::canonicalize(path).unwrap_or(path.into()));
+        for (include, span) in includes {
+            let include =
+                path.parent().unwrap_or(Path::new(".")).join(include);
+            let canonical =
+                fs::canonicalize(&include).unwrap_or(include.clone());
+            let kind = if !include.is_file() {
+                ConfigDiagnosticKind::IncludeNotFound(include)
+            } else if let Some(i) = stack.iter().position(|p| *p == canonical)
+            {
+                let chain = stack[i..].iter().chain([&canonical]);
+                let chain = chain.map(|p| p.display()).join(" -> ");
+                ConfigDiagnosticKind::IncludeCycle(chain)
             } else {
-                Ok(())
+                self.read(&include, stack, errs)?;
+                continue;
+            };
+            errs.push(ConfigDiagnostic::new(path, &buf, span, kind));
+        }
+        stack.pop();
+
+        self.merge(cfg);
+        self.files.push(path.into());
+        Ok(())
+    }
+
+    /// Every field is deserialized on its own so that all the errors of a
+    /// file can be reported at once. Returns the included files with the
+    /// span of the include key
+    fn parse(
+        s: &str,
+        path: &Path,
+    ) -> Result<(Self, Vec<Include>), Vec<ConfigDiagnostic>> {
+        let diagnostic =
+            |(span, kind)| ConfigDiagnostic::new(path, s, span, kind);
+        let table = toml::from_str::<Table>(s).map_err(|e| {
+            let kind = ConfigDiagnosticKind::Syntax(e.message().into());
+            vec![diagnostic((e.span().unwrap_or_default(), kind))]
+        })?;
+        let spans = Spans::new(s);
+
+        let source = Source::File(path.into());
+        let mut cfg = Self::default();
+        let mut includes = Vec::new();
+        let mut errs = Vec::new();
+        for (key, value) in table {
+            let (key_span, span) = (spans.key(&key), spans.value(&key));
+            let res = match value {
+                _ if key == "include" => {
+                    Self::includes(value, &span).map(|v| includes = v).map_err(
+                        |msg| ConfigDiagnosticKind::InvalidValue { key, msg },
+                    )
+                }
+                Value::Table(presets) if key == "preset" => {
+                    errs.extend(cfg.set_presets(presets, &source, &spans));
+                    Ok(())
+                }
+                _ => cfg.set(key, value, &source),
+            };
+            match res {
+                Ok(()) => {}
+                Err(kind @ ConfigDiagnosticKind::UnknownKey { .. }) => {
+                    errs.push((key_span, kind));
+                }
+                Err(kind) => errs.push((span, kind)),
             }
+        }
+
+        let mut errs = errs.into_iter().map(diagnostic).collect::<Vec<_>>();
+        errs.sort_by_key(|e| (e.line, e.column));
+        match errs.is_empty() {
+            true => Ok((cfg, includes)),
+            false => Err(errs),
+        }
+    }
+
+    fn includes(
+        v: Value,
+        span: &Range<usize>,
+    ) -> Result<Vec<Include>, String> {
+        let v = Vec::<PathBuf>::deserialize(v)
+            .map_err(|e| e.message().to_string())?;
+        Ok(v.into_iter().map(|p| (p, span.clone())).collect())
+    }
+
+    /// Presets are set one by one. The fields of an invalid preset are set
+    /// on their own to find the span of the invalid value
+    fn set_presets(
+        &mut self,
+        presets: Table,
+        source: &Source,
+        spans: &Spans,
+    ) -> Vec<(Range<usize>, ConfigDiagnosticKind)> {
+        let table =
+            |k: &str, v| Value::Table(Table::from_iter([(k.into(), v)]));
+        let mut errs = Vec::new();
+        for (name, preset) in presets {
+            let p = table(&name, preset.clone());
+            let Err(kind) = self.set("preset".into(), p, source) else {
+                continue;
+            };
+            let err = match preset {
+                Value::Table(fields) => {
+                    fields.into_iter().find_map(|(k, v)| {
+                        let p = table(&name, table(&k, v));
+                        let kind =
+                            Self::default().set("preset".into(), p, source);
+                        Self::preset_field_error(&name, k, kind.err()?, spans)
+                    })
+                }
+                _ => None,
+            };
+            errs.push(err.unwrap_or((spans.value("preset"), kind)));
+        }
+        errs
+    }
+
+    /// Error of a single field of a preset, located at the field
+    fn preset_field_error(
+        name: &str,
+        field: String,
+        kind: ConfigDiagnosticKind,
+        spans: &Spans,
+    ) -> Option<(Range<usize>, ConfigDiagnosticKind)> {
+        let (key_span, value_span) = spans.preset(name, &field)?;
+        if !field_names::<ColorSettings>().contains(&field.as_str()) {
+            let kind = ConfigDiagnosticKind::UnknownKey {
+                key: format!("preset.{name}.{field}"),
+                suggestion: None,
+            };
+            return Some((key_span, kind));
+        }
+        let kind = match kind {
+            ConfigDiagnosticKind::InvalidValue { msg, .. } => {
+                let key = format!("preset.{name}.{field}");
+                ConfigDiagnosticKind::InvalidValue { key, msg }
+            }
+            kind => kind,
         };
+        Some((value_span, kind))
+    }
 
-        #[cfg(unix)]
-        read(system_config)?;
-        read(user_config)?;
-        Ok(config)
+    /// Apply the environment variables and then the --set arguments. Unknown
+    /// environment variables are only warned about, since other variables
+    /// share the prefix, e.g. REDDISH_SHIFT_DRM_CARD of the drm tests
+    fn merge_overrides(
+        &mut self,
+        env: impl IntoIterator<Item = (OsString, OsString)>,
+        set: &[KeyValue],
+    ) -> Result<(), ConfigError> {
+        let env = env.into_iter().filter_map(|(var, value)| {
+            let var = var.into_string().ok()?;
+            let key = var.strip_prefix(ENV_PREFIX)?;
+            let key = key.to_lowercase().replace('_', "-");
+            Some((key, value.into_string().ok()?, Source::Env(var)))
+        });
+        let set = set.iter().map(|(k, v)| (k.clone(), v.clone(), Source::Set));
+
+        let errs = env
+            .collect::<Vec<_>>()
+            .into_iter()
+            .chain(set)
+            .filter_map(|(key, value, source)| {
+                let kind =
+                    self.set(key, value_from_str(&value), &source).err()?;
+                let e = OverrideError {
+                    origin: source.to_string(),
+                    kind,
+                };
+                match (&source, &e.kind) {
+                    (
+                        Source::Env(_),
+                        ConfigDiagnosticKind::UnknownKey { .. },
+                    ) => {
+                        warn!("{WARN}warning:{WARN:#} {e}");
+                        None
+                    }
+                    _ => Some(e),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        match errs.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError::Override(VecError(errs))),
+        }
     }
 
+    /// Deserialize and set the field of a key
+    fn set(
+        &mut self,
+        key: String,
+        mut v: Value,
+        source: &Source,
+    ) -> Result<(), ConfigDiagnosticKind> {
+        let Some(k) = field_names::<Self>().iter().find(|k| **k == key) else {
+            let suggestion = suggest_key(&key);
+            return Err(ConfigDiagnosticKind::UnknownKey { key, suggestion });
+        };
+
+        match (k, &mut v) {
+            (&"curve", Value::String(s)) => *s = resolve_curve_path(s, source),
+            (&"curve", Value::Array(a)) => {
+                for v in a {
+                    if let Value::String(s) = v {
+                        *s = resolve_curve_path(s, source);
+                    }
+                }
+            }
+            (&"preset", Value::Table(presets)) => {
+                for (_, p) in presets.iter_mut() {
+                    if let Some(Value::String(s)) = p.get_mut("curve") {
+                        *s = resolve_curve_path(s, source);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let table = Value::Table([(key.clone(), v)].into_iter().collect());
+        let other = Self::deserialize(table).map_err(|e| {
+            let msg = e.message().to_string();
+            ConfigDiagnosticKind::InvalidValue { key, msg }
+        })?;
+        self.merge(other);
+        self.sources.insert(k, source.clone());
+        Ok(())
+    }
+
+    /// Fields that are set in other take precedence. Presets are merged
     fn merge(&mut self, other: Self) {
-        let Self {
-            temperature,
-            brightness,
-            gamma,
-            reset_ramps,
-            scheme,
-            disable_fade,
-            sleep_duration_short,
-            sleep_duration,
-            method,
-            location,
-        } = other;
+        fn or<T>(t: &mut Option<T>, other: Option<T>) {
+            if other.is_some() {
+                *t = other;
+            }
+        }
 
-        if let Some(t) = temperature {
-            self.temperature = Some(t);
+        self.merge_presets(&other);
+        or(&mut self.temperature, other.temperature);
+        or(&mut self.tint, other.tint);
+        or(&mut self.gamma, other.gamma);
+        or(&mut self.brightness, other.brightness);
+        or(&mut self.channels, other.channels);
+        or(&mut self.invert, other.invert);
+        or(&mut self.black_level, other.black_level);
+        or(&mut self.contrast, other.contrast);
+        or(&mut self.curve, other.curve);
+        or(&mut self.scheme, other.scheme);
+        or(&mut self.location, other.location);
+        or(&mut self.method, other.method);
+        or(&mut self.reset_ramps, other.reset_ramps);
+        or(&mut self.disable_fade, other.disable_fade);
+        or(&mut self.sleep_duration_short, other.sleep_duration_short);
+        or(&mut self.sleep_duration, other.sleep_duration);
+        or(&mut self.monitor_ramps, other.monitor_ramps);
+        or(&mut self.adopt_foreign_ramps, other.adopt_foreign_ramps);
+        or(&mut self.color_matrix, other.color_matrix);
+        if let Some(t) = other.preset {
+            let presets = self.preset.get_or_insert_with(Default::default);
+            presets.extend(t);
         }
-        if let Some(t) = brightness {
-            self.brightness = Some(t);
+        self.sources.extend(other.sources);
+    }
+
+    /// A day or night preset replaces the color fields of the earlier
+    /// layers and of its own layer. The color fields of the later layers
+    /// replace the preset
+    fn merge_presets(&mut self, other: &Self) {
+        let keys = other.color_keys();
+        let presets = [
+            (&mut self.day, &mut self.day_overrides, &other.day),
+            (&mut self.night, &mut self.night_overrides, &other.night),
+        ];
+        for (preset, overrides, other) in presets {
+            match other {
+                Some(t) => {
+                    *preset = Some(t.clone());
+                    overrides.clear();
+                }
+                None => overrides.extend(&keys),
+            }
         }
-        if let Some(t) = gamma {
-            self.gamma = Some(t);
+
+        let source = ["day", "night"]
+            .into_iter()
+            .find_map(|k| other.sources.get(k).cloned());
+        if let Some(source) = source {
+            for k in COLOR_KEYS.iter().filter(|k| !keys.contains(k)) {
+                self.sources.insert(k, source.clone());
+            }
         }
-        self.reset_ramps = reset_ramps;
-        self.disable_fade = disable_fade;
-        if let Some(t) = scheme {
-            self.scheme = Some(t);
+    }
+}
+
+impl ConfigFile {
+    /// Keys of the color fields that are set
+    fn color_keys(&self) -> Vec<&'static str> {
+        let set = [
+            self.temperature.is_some(),
+            self.tint.is_some(),
+            self.gamma.is_some(),
+            self.brightness.is_some(),
+            self.channels.is_some(),
+            self.invert.is_some(),
+            self.black_level.is_some(),
+            self.contrast.is_some(),
+            self.curve.is_some(),
+        ];
+        let keys = COLOR_KEYS.into_iter().zip(set);
+        keys.filter_map(|(k, set)| set.then_some(k)).collect()
+    }
+}
+
+/// Copy the color fields of the keys, e.g. the fields that were set after
+/// a preset
+fn copy_color_fields(
+    keys: &BTreeSet<&str>,
+    from: &ColorSettings,
+    to: &mut ColorSettings,
+) {
+    for key in keys {
+        match *key {
+            "temperature" => to.temp = from.temp,
+            "tint" => to.tint = from.tint,
+            "gamma" => to.gamma = from.gamma,
+            "brightness" => to.brght = from.brght,
+            "channels" => to.channels = from.channels,
+            "invert" => to.invert = from.invert,
+            "black-level" => to.black = from.black,
+            "contrast" => to.contrast = from.contrast,
+            "curve" => to.curve = from.curve.clone(),
+            _ => {}
         }
+    }
+}
 
-        if let Some(t) = sleep_duration {
-            self.sleep_duration = Some(t);
+/// Spans of the keys and values of a config file
+#[derive(Debug, Default)]
+struct Spans {
+    keys: BTreeMap<String, Range<usize>>,
+    values: BTreeMap<String, Range<usize>>,
+    // Spans of the keys and values of the fields of each preset
+    presets: BTreeMap<(String, String), (Range<usize>, Range<usize>)>,
+}
+
+impl Spans {
+    fn new(s: &str) -> Self {
+        // Spanned values of dotted keys (e.g. a.b = 1) are not supported, so
+        // the keys are deserialized on their own in case the values fail
+        let keys = toml::from_str::<BTreeMap<Spanned<String>, IgnoredAny>>(s)
+            .unwrap_or_default()
+            .into_keys()
+            .map(|k| (k.get_ref().clone(), k.span()))
+            .collect();
+        let Self {
+            values, presets, ..
+        } = toml::from_str(s).unwrap_or_default();
+        Self {
+            keys,
+            values,
+            presets,
         }
-        if let Some(t) = sleep_duration_short {
-            self.sleep_duration_short = Some(t);
+    }
+
+    fn key(&self, key: &str) -> Range<usize> {
+        self.keys.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Falls back to the span of the key
+    fn value(&self, key: &str) -> Range<usize> {
+        self.values
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| self.key(key))
+    }
+
+    fn preset(
+        &self,
+        name: &str,
+        key: &str,
+    ) -> Option<(Range<usize>, Range<usize>)> {
+        self.presets.get(&(name.into(), key.into())).cloned()
+    }
+}
+
+/// Tables of tables (e.g. [preset.NAME]) can't be spanned as a whole, so
+/// the fields of the presets are spanned instead
+impl<'de> Deserialize<'de> for Spans {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        type Presets =
+            BTreeMap<String, BTreeMap<Spanned<String>, Spanned<IgnoredAny>>>;
+        struct SpansVisitor;
+        impl<'de> Visitor<'de> for SpansVisitor {
+            type Value = Spans;
+
+            fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+                f.write_str("a table")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Spans, A::Error> {
+                let mut spans = Spans::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    if key != "preset" {
+                        let v = map.next_value::<Spanned<IgnoredAny>>()?;
+                        spans.values.insert(key, v.span());
+                        continue;
+                    }
+                    for (name, p) in map.next_value::<Presets>()? {
+                        for (k, v) in p {
+                            let k_span = k.span();
+                            let k = (name.clone(), k.into_inner());
+                            spans.presets.insert(k, (k_span, v.span()));
+                        }
+                    }
+                }
+                Ok(spans)
+            }
         }
+        d.deserialize_map(SpansVisitor)
+    }
+}
 
-        if let Some(t) = location {
-            self.location = Some(t);
+/// Values of environment variables and --set arguments are parsed as TOML
+/// values, or as strings if they are not valid TOML (e.g. 6500-3800)
+fn value_from_str(s: &str) -> Value {
+    toml::from_str::<BTreeMap<String, Value>>(&format!("v = {s}"))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| Value::String(s.into()))
+}
+
+/// Paths of curves in config files are relative to the file
+fn resolve_curve_path(s: &str, source: &Source) -> String {
+    match source {
+        Source::File(file) if s != "linear" && Path::new(s).is_relative() => {
+            let dir = file.parent().unwrap_or(Path::new("."));
+            dir.join(s).display().to_string()
         }
-        if let Some(t) = method {
-            self.method = Some(t);
+        _ => s.into(),
+    }
+}
+
+/// A curve for day and night, or an array of the day and night curves
+fn curve_range<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<CurveRange>, D::Error> {
+    let paths = match Value::deserialize(d)? {
+        Value::String(s) => vec![s.clone(), s],
+        Value::Array(a) if a.len() == 2 => a
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => Ok(s),
+                _ => Err(de::Error::custom("expected a path")),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => Err(de::Error::custom(
+            "expected a path or an array of two paths",
+        ))?,
+    };
+    let mut curves = paths
+        .iter()
+        .map(|p| p.parse::<Curve>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(de::Error::custom)?;
+    let night = curves.pop().unwrap_or_default();
+    let day = curves.pop().unwrap_or_default();
+    Ok(Some(DayNight { day, night }))
+}
+
+/// Closest valid key to a misspelled one
+fn suggest_key(key: &str) -> Option<&'static str> {
+    let key = key.to_lowercase().replace('_', "-");
+    field_names::<ConfigFile>()
+        .iter()
+        .copied()
+        .chain(["include"])
+        .map(|k| (k, edit_distance(&key, k)))
+        .filter(|(k, d)| *d <= (k.len() / 3).max(2))
+        .min_by_key(|(_, d)| *d)
+        .map(|(k, _)| k)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = prev + usize::from(ca != *cb);
+            prev = row[j + 1];
+            row[j + 1] = cost.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl ConfigDiagnostic {
+    fn new(
+        path: &Path,
+        s: &str,
+        span: Range<usize>,
+        kind: ConfigDiagnosticKind,
+    ) -> Self {
+        let start = span.start.min(s.len());
+        let line_start = s[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = s[start..].find('\n').map_or(s.len(), |i| start + i);
+        let end = span.end.clamp(start, line_end);
+        Self {
+            path: path.into(),
+            line: s[..start].matches('\n').count() + 1,
+            column: s[line_start..start].chars().count() + 1,
+            source_line: s[line_start..line_end].trim_end().into(),
+            len: s[start..end].chars().count().max(1),
+            kind,
         }
     }
 }
@@ -540,25 +1663,361 @@ impl Default for ConfigBuilder {
                 DEFAULT_SLEEP_DURATION_SHORT,
             ),
             sleep_duration: Duration::from_millis(DEFAULT_SLEEP_DURATION),
+            monitor_ramps: Default::default(),
+            adopt_foreign_ramps: Default::default(),
+            fade: Default::default(),
             method: Default::default(),
             location: Default::default(),
+            color_matrix: Default::default(),
+            presets: Default::default(),
+            day_preset: Default::default(),
+            night_preset: Default::default(),
+            set_args: Default::default(),
+            sources: Default::default(),
+            files: Default::default(),
         }
     }
 }
 
-impl From<ColorSettingsArgs> for ColorSettings {
-    fn from(t: ColorSettingsArgs) -> Self {
-        let ColorSettingsArgs {
-            temperature,
-            gamma,
-            brightness,
-        } = t;
+/// Prints the fields in config file syntax, each followed by its source
+impl ConfigBuilder {
+    /// Keys of the merged config file with their values if they are set
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        let Self {
+            mode: _,
+            day,
+            night,
+            reset_ramps,
+            disable_fade,
+            scheme,
+            sleep_duration,
+            sleep_duration_short,
+            monitor_ramps,
+            adopt_foreign_ramps,
+            fade: _,
+            location,
+            method,
+            color_matrix,
+            presets: _,
+            day_preset,
+            night_preset,
+            set_args: _,
+            sources: _,
+            files: _,
+        } = self;
 
-        Self {
-            temp: temperature,
+        let quoted = |v: &dyn Display| Some(format!("\"{v}\""));
+        let ms = |d: &Duration| Some(d.as_millis().to_string());
+        let mut fields = color_fields(day, night);
+        fields.extend([
+            ("scheme", quoted(&scheme_value(scheme))),
+            ("location", quoted(&location_value(location))),
+            ("method", method.as_ref().and_then(|m| quoted(m))),
+            ("color-matrix", color_matrix.as_ref().map(matrix_value)),
+            ("day", day_preset.as_ref().and_then(|p| quoted(p))),
+            ("night", night_preset.as_ref().and_then(|p| quoted(p))),
+            ("reset-ramps", Some(reset_ramps.to_string())),
+            ("disable-fade", Some(disable_fade.to_string())),
+            ("sleep-duration", ms(sleep_duration)),
+            ("sleep-duration-short", ms(sleep_duration_short)),
+            ("monitor-ramps", Some(monitor_ramps.to_string())),
+            ("adopt-foreign-ramps", Some(adopt_foreign_ramps.to_string())),
+        ]);
+        fields
+    }
+}
+
+impl Display for ConfigBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let lines = self
+            .fields()
+            .into_iter()
+            .map(|(key, value)| {
+                let line = match value {
+                    Some(v) => format!("{key} = {v}"),
+                    None => format!("# {key} is not set"),
+                };
+                let source = match self.sources.get(key) {
+                    Some(s) => s.to_string(),
+                    None => "default".into(),
+                };
+                (line, source)
+            })
+            .collect::<Vec<_>>();
+        // long values such as color-matrix are not aligned
+        let width = lines.iter().map(|(l, _)| l.len()).filter(|l| *l <= 48);
+        let width = width.max().unwrap_or(0);
+        let s = lines
+            .iter()
+            .map(|(line, source)| format!("{line:width$}  # {source}"))
+            .join("\n");
+        f.write_str(&s)?;
+
+        for (name, cs) in &self.presets {
+            write_preset(f, name, cs)?;
+        }
+        Ok(())
+    }
+}
+
+fn color_fields(
+    day: &ColorSettings,
+    night: &ColorSettings,
+) -> Vec<(&'static str, Option<String>)> {
+    let range =
+        |d: &dyn Display, n: &dyn Display| Some(format!("\"{d}-{n}\""));
+    vec![
+        ("temperature", range(&day.temp, &night.temp)),
+        ("tint", range(&day.tint, &night.tint)),
+        ("gamma", range(&day.gamma, &night.gamma)),
+        ("brightness", range(&day.brght, &night.brght)),
+        ("channels", range(&day.channels, &night.channels)),
+        ("invert", range(&day.invert, &night.invert)),
+        ("black-level", range(&day.black, &night.black)),
+        ("contrast", range(&day.contrast, &night.contrast)),
+        (
+            "curve",
+            Some(format!("[\"{}\", \"{}\"]", day.curve, night.curve)),
+        ),
+    ]
+}
+
+fn scheme_value(scheme: &TransitionScheme) -> String {
+    match scheme {
+        TransitionScheme::Time(TimeRanges { dawn, dusk }) => {
+            format!("{}-{}-{}-{}", dawn.start, dawn.end, dusk.start, dusk.end)
+        }
+        TransitionScheme::Elev(ElevationRange { high, low }) => {
+            format!("{}:{}", **high, **low)
+        }
+    }
+}
+
+fn location_value(location: &LocationProviderType) -> String {
+    match location {
+        LocationProviderType::Manual(l) => format!("{}:{}", *l.lat, *l.lon),
+        LocationProviderType::Geoclue2 => "geoclue2".into(),
+        LocationProviderType::Timezone => "timezone".into(),
+    }
+}
+
+fn matrix_value(m: &ColorMatrix) -> String {
+    format!("[{}]", m.iter().map(|v| format!("{v:?}")).join(", "))
+}
+
+fn write_preset(
+    f: &mut Formatter<'_>,
+    name: &str,
+    cs: &ColorSettings,
+) -> fmt::Result {
+    let ColorSettings {
+        temp,
+        tint,
+        gamma,
+        brght,
+        channels,
+        invert,
+        black,
+        contrast,
+        curve,
+    } = cs;
+    write!(f, "\n\n[preset.{name}]")?;
+    write!(f, "\ntemperature = {temp}")?;
+    write!(f, "\ntint = {tint}")?;
+    write!(f, "\ngamma = \"{gamma}\"")?;
+    write!(f, "\nbrightness = {brght}")?;
+    // effects are only written when they are used
+    let d = ColorSettings::default();
+    if *channels != d.channels {
+        write!(f, "\nchannels = \"{channels}\"")?;
+    }
+    if *invert != d.invert {
+        write!(f, "\ninvert = {invert}")?;
+    }
+    if *black != d.black {
+        write!(f, "\nblack-level = {black}")?;
+    }
+    if *contrast != d.contrast {
+        write!(f, "\ncontrast = {contrast}")?;
+    }
+    if !curve.is_linear() {
+        write!(f, "\ncurve = \"{curve}\"")?;
+    }
+    Ok(())
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(var) => write!(f, "environment variable {var}"),
+            Source::Set => write!(f, "--set"),
+            Source::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ColorSettings {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields, rename_all = "kebab-case")]
+        struct Preset {
+            temperature: Option<Value>,
+            tint: Option<Value>,
+            gamma: Option<Value>,
+            brightness: Option<Value>,
+            channels: Option<Value>,
+            invert: Option<Value>,
+            black_level: Option<Value>,
+            contrast: Option<Value>,
+            curve: Option<Value>,
+        }
+
+        // numbers are parsed like strings to reuse the FromStr impls
+        fn parse<T: FromStr, E: de::Error>(
+            v: Option<Value>,
+        ) -> Result<Option<T>, E>
+        where
+            T::Err: Display,
+        {
+            let s = match v {
+                None => return Ok(None),
+                Some(Value::String(s)) => s,
+                Some(v) => v.to_string(),
+            };
+            s.parse().map(Some).map_err(de::Error::custom)
+        }
+
+        let p = Preset::deserialize(d)?;
+        let mut cs = Self::default();
+        cs.temp = parse(p.temperature)?.unwrap_or(cs.temp);
+        cs.tint = parse(p.tint)?.unwrap_or(cs.tint);
+        cs.gamma = parse(p.gamma)?.unwrap_or(cs.gamma);
+        cs.brght = parse(p.brightness)?.unwrap_or(cs.brght);
+        cs.channels = parse(p.channels)?.unwrap_or(cs.channels);
+        cs.invert = parse(p.invert)?.unwrap_or(cs.invert);
+        cs.black = parse(p.black_level)?.unwrap_or(cs.black);
+        cs.contrast = parse(p.contrast)?.unwrap_or(cs.contrast);
+        if let Some(v) = p.curve {
+            cs.curve = Curve::deserialize(v).map_err(de::Error::custom)?;
+        }
+        Ok(cs)
+    }
+}
+
+/// Serialized with the keys of a preset, e.g. to save the state
+impl Serialize for ColorSettings {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let ColorSettings {
+            temp,
+            tint,
             gamma,
-            brght: brightness,
+            brght,
+            channels,
+            invert,
+            black,
+            contrast,
+            curve,
+        } = self;
+        let gamma = format!("{}:{}:{}", gamma[0], gamma[1], gamma[2]);
+        let channels =
+            format!("{}:{}:{}", channels[0], channels[1], channels[2]);
+        let mut entry = BTreeMap::from([
+            ("temperature", Value::Integer(**temp as i64)),
+            ("tint", Value::Float(**tint)),
+            ("gamma", Value::String(gamma)),
+            ("brightness", Value::Float(**brght)),
+            ("channels", Value::String(channels)),
+            ("invert", Value::Float(**invert)),
+            ("black-level", Value::Float(**black)),
+            ("contrast", Value::Float(**contrast)),
+        ]);
+        if !curve.is_linear() {
+            let curve = Value::try_from(curve).map_err(ser::Error::custom)?;
+            entry.insert("curve", curve);
         }
+        entry.serialize(s)
+    }
+}
+
+// The samples of a curve are saved with it, so that the saved color
+// settings do not depend on the curve file, which may change or be removed
+#[derive(Serialize, Deserialize)]
+struct CurveSamples {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    samples: [Vec<f64>; 3],
+}
+
+/// Saved curves are tables of their samples and curves of config files are
+/// paths of curve files
+impl<'de> Deserialize<'de> for Curve {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        match Value::deserialize(d)? {
+            Value::Table(t) => {
+                let CurveSamples { path, samples } =
+                    CurveSamples::deserialize(Value::Table(t))
+                        .map_err(de::Error::custom)?;
+                Curve::from_samples(path, samples).ok_or_else(|| {
+                    de::Error::custom(
+                        "expected at least two samples between 0 and 1",
+                    )
+                })
+            }
+            Value::String(s) => s.parse().map_err(de::Error::custom),
+            v => Err(de::Error::custom(format!(
+                "expected a path of a curve, found {}",
+                v.type_str()
+            ))),
+        }
+    }
+}
+
+impl Serialize for Curve {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        CurveSamples {
+            path: self.path().map(Path::to_path_buf),
+            samples: self.samples().clone(),
+        }
+        .serialize(s)
+    }
+}
+
+/// Names of the fields of a struct, as named by its Deserialize impl
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// Deserializer that only records the field names of the struct that is
+/// deserialized from it
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        _: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("expected a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("only the field names are deserialized"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
     }
 }
 
@@ -604,8 +2063,374 @@ impl<'de> Deserialize<'de> for LocationProviderType {
     }
 }
 
+impl<'de> Deserialize<'de> for ColorMatrix {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        match Value::deserialize(d)? {
+            Value::String(s) => s.parse().map_err(de::Error::custom),
+            Value::Float(f) => Ok(Self::saturation(f)),
+            Value::Integer(i) => Ok(Self::saturation(i as f64)),
+            v => {
+                let m =
+                    <[f64; 9]>::deserialize(v).map_err(de::Error::custom)?;
+                Ok(Self::new(m))
+            }
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for AdjustmentMethodType {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
         String::deserialize(d)?.parse().map_err(de::Error::custom)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        error::ReddishError,
+        types::{Brightness, Gamma},
+    };
+    use anyhow::{anyhow, Result};
+    use clap::Parser;
+    use std::cell::Cell;
+
+    /// An empty directory for the config files of a test
+    fn test_dir(name: &str) -> Result<PathBuf> {
+        let name = format!("reddish-shift-test-{}-{name}", std::process::id());
+        let dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    fn layer(s: &str, path: &str) -> Result<ConfigFile> {
+        let (cfg, _) = ConfigFile::parse(s, Path::new(path))
+            .map_err(|e| anyhow!("{}", VecError(e)))?;
+        Ok(cfg)
+    }
+
+    /// Merge the arguments of a command, e.g. "set --preset x"
+    fn merge_args(cfg: &mut ConfigBuilder, args: &str) -> Result<()> {
+        let args = [PKG_NAME].into_iter().chain(args.split_whitespace());
+        cfg.merge_with_cli_args(CliArgs::try_parse_from(args)?)?;
+        Ok(())
+    }
+
+    /// Source of a key as printed by config show
+    fn shown_source(cfg: &ConfigBuilder, key: &str) -> Option<String> {
+        let shown = cfg.to_string();
+        let line =
+            shown.lines().find(|l| l.starts_with(&format!("{key} ")))?;
+        Some(line.rsplit_once("  # ")?.1.to_string())
+    }
+
+    #[test]
+    fn diagnostic_spans() {
+        let s = r#"
+color-matrix = [
+  1, 0,
+]
+[preset.x]
+temperature = 4000
+gamma = "bad"
+[preset.y]
+brightnes = 1
+"#;
+        let errs = ConfigFile::parse(s, Path::new("config.toml"))
+            .err()
+            .unwrap_or_default();
+        let spans = errs.iter().map(|e| (e.line, e.column, e.len));
+        assert_eq!(
+            spans.collect::<Vec<_>>(),
+            [(2, 16, 1), (7, 9, 5), (9, 1, 9)]
+        );
+    }
+
+    #[test]
+    fn saved_curve() -> Result<()> {
+        // the file is not read when the color settings are loaded
+        let path = Some("missing.cube".into());
+        let samples = [vec![0.0, 0.5, 1.0], vec![0.0, 1.0], vec![0.2, 1.0]];
+        let curve = Curve::from_samples(path, samples)
+            .ok_or_else(|| anyhow!("invalid samples"))?;
+        let cs = ColorSettings {
+            curve,
+            ..Default::default()
+        };
+
+        let json: ColorSettings =
+            serde_json::from_str(&serde_json::to_string(&cs)?)?;
+        let toml: ColorSettings = Value::try_from(&cs)?.try_into()?;
+        for saved in [json, toml] {
+            assert_eq!(saved.curve, cs.curve);
+            assert_eq!(saved.curve.path(), cs.curve.path());
+        }
+        Ok(())
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        let var = |(k, v): &(&str, &str)| {
+            (format!("{ENV_PREFIX}{k}").into(), (*v).into())
+        };
+        vars.iter().map(var).collect()
+    }
+
+    #[test]
+    fn unknown_overrides() {
+        // only the --set arguments are errors
+        let mut file = ConfigFile::default();
+        assert!(file
+            .merge_overrides(env(&[("UNKNOWN_KEY", "1")]), &[])
+            .is_ok());
+        let set = [("unknown-key".into(), "1".into())];
+        assert!(ConfigFile::default().merge_overrides([], &set).is_err());
+    }
+
+    #[test]
+    fn override_precedence() -> Result<()> {
+        let mut file = layer(
+            "temperature = \"6000-4000\"\ngamma = 0.9\nbrightness = 0.9",
+            "config.toml",
+        )?;
+        let vars = [
+            ("GAMMA", "0.8"),
+            ("BRIGHTNESS", "0.8"),
+            ("TEMPERATURE", "6500-3000"),
+        ];
+        let set = [
+            ("brightness".into(), "0.7".into()),
+            ("temperature".into(), "\"5000-3500\"".into()),
+        ];
+        file.merge_overrides(env(&vars), &set)?;
+        let mut cfg = ConfigBuilder::default();
+        cfg.merge_with_config_file(file)?;
+        let temperature = DayNight {
+            day: Temperature::try_from(5500)?,
+            night: Temperature::try_from(2500)?,
+        };
+        cfg.set_range("temperature", Some(temperature), |c| &mut c.temp);
+
+        assert_eq!(cfg.night.gamma, 0.8.try_into()?);
+        assert_eq!(cfg.night.brght, 0.7.try_into()?);
+        assert_eq!(cfg.night.temp, 2500.try_into()?);
+        let source = |key| shown_source(&cfg, key);
+        let env_source = format!("environment variable {ENV_PREFIX}GAMMA");
+        assert_eq!(source("gamma"), Some(env_source));
+        assert_eq!(source("brightness").as_deref(), Some("--set"));
+        assert_eq!(source("temperature").as_deref(), Some("command line"));
+        assert_eq!(source("tint").as_deref(), Some("default"));
+        Ok(())
+    }
+
+    #[test]
+    fn merged_layers() -> Result<()> {
+        let mut file = layer(
+            "reset-ramps = true\ndisable-fade = true\nsleep-duration = 1000",
+            "a.toml",
+        )?;
+        // fields that a later layer does not set are kept
+        file.merge(layer("sleep-duration = 2000", "b.toml")?);
+        let mut cfg = ConfigBuilder::default();
+        cfg.merge_with_config_file(file)?;
+
+        assert!(cfg.reset_ramps && cfg.disable_fade);
+        assert_eq!(cfg.sleep_duration, Duration::from_millis(2000));
+        let source = |key| shown_source(&cfg, key);
+        assert_eq!(source("reset-ramps").as_deref(), Some("a.toml"));
+        assert_eq!(source("disable-fade").as_deref(), Some("a.toml"));
+        assert_eq!(source("sleep-duration").as_deref(), Some("b.toml"));
+        assert_eq!(source("monitor-ramps").as_deref(), Some("default"));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_config_exit_code() -> Result<()> {
+        let path = test_dir("invalid")?.join("config.toml");
+        fs::write(&path, "reset-ramps = 1\n")?;
+        let r = ConfigFile::new(Some(&path));
+        fs::remove_dir_all(path.parent().unwrap_or(&path))?;
+
+        let e = ReddishError::from(ConfigError::from(
+            r.err().ok_or_else(|| anyhow!("the file is valid"))?,
+        ));
+        assert_eq!(e.exit_code(), exitcode::CONFIG);
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostic_rendering() {
+        let s = "gamma = \"x\"\ntemprature = 4000\n";
+        let errs = ConfigFile::parse(s, Path::new("config.toml"))
+            .err()
+            .unwrap_or_default();
+        let expected = "\
+- config.toml:1:9: invalid value of `gamma`
+    |
+  1 | gamma = \"x\"
+    |         ^^^
+  invalid float literal
+- config.toml:2:1: unknown key `temprature`, did you mean `temperature`?
+    |
+  2 | temprature = 4000
+    | ^^^^^^^^^^";
+        assert_eq!(VecError(errs).to_string(), expected);
+
+        assert_eq!(suggest_key("REDDISH_SHIFT_X"), None);
+        assert_eq!(suggest_key("sleep_duration"), Some("sleep-duration"));
+        assert_eq!(suggest_key("includ"), Some("include"));
+    }
+
+    #[test]
+    fn includes_and_drop_ins() -> Result<()> {
+        let dir = test_dir("includes")?;
+        let config = dir.join("config.toml");
+        let drop_ins = dir.join("config.d");
+        fs::create_dir(&drop_ins)?;
+        fs::write(dir.join("base.toml"), "gamma = 0.8\nbrightness = 0.8\n")?;
+        fs::write(&config, "include = [\"base.toml\"]\nbrightness = 0.9\n")?;
+        fs::write(drop_ins.join("20.toml"), "sleep-duration = 2000\n")?;
+        fs::write(drop_ins.join("10.toml"), "sleep-duration = 1000\n")?;
+        fs::write(drop_ins.join("ignored.conf"), "sleep-duration = 3000\n")?;
+
+        let mut file = ConfigFile::default();
+        let mut errs = Vec::new();
+        file.read_with_drop_ins(&config, &mut errs)?;
+        fs::remove_dir_all(&dir)?;
+        assert!(errs.is_empty());
+        let names = file.files.iter().filter_map(|p| p.file_name());
+        let names = names.filter_map(|n| n.to_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["base.toml", "config.toml", "10.toml", "20.toml"]);
+
+        let mut cfg = ConfigBuilder::default();
+        cfg.merge_with_config_file(file)?;
+        // the including file overrides the included one
+        assert_eq!(cfg.day.gamma, 0.8.try_into()?);
+        assert_eq!(cfg.day.brght, 0.9.try_into()?);
+        assert_eq!(cfg.sleep_duration, Duration::from_millis(2000));
+        let source = shown_source(&cfg, "brightness");
+        assert_eq!(source, Some(config.display().to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn include_cycle() -> Result<()> {
+        let dir = test_dir("cycle")?;
+        let (a, b) = (dir.join("a.toml"), dir.join("b.toml"));
+        fs::write(&a, "include = [\"b.toml\"]\n")?;
+        fs::write(&b, "gamma = 0.8\ninclude = [\"a.toml\"]\n")?;
+
+        let mut errs = Vec::new();
+        ConfigFile::default().read(&a, &mut Vec::new(), &mut errs)?;
+        let (a, b) = (fs::canonicalize(a)?, fs::canonicalize(b)?);
+        fs::remove_dir_all(&dir)?;
+        // reported at the include of the file that closes the cycle
+        let [e] = errs.as_slice() else {
+            return Err(anyhow!("expected one error: {}", VecError(errs)));
+        };
+        assert_eq!(e.path, dir.join("b.toml"));
+        assert_eq!((e.line, e.column, e.len), (2, 11, 10));
+        let chain = [&a, &b, &a].map(|p| p.display()).iter().join(" -> ");
+        assert_eq!(e.kind.to_string(), format!("include cycle: {chain}"));
+        Ok(())
+    }
+
+    #[test]
+    fn presets_per_layer() -> Result<()> {
+        let build = |layers: &[(&str, &str)]| -> Result<ConfigBuilder> {
+            let mut file = ConfigFile::default();
+            for (s, path) in layers {
+                file.merge(layer(s, path)?);
+            }
+            let mut cfg = ConfigBuilder::default();
+            cfg.merge_with_config_file(file)?;
+            Ok(cfg)
+        };
+        let preset = ("night = \"candle\"", "base.toml");
+        let temperature = ("temperature = \"6500-3000\"", "user.toml");
+
+        // a later layer overrides the preset
+        let cfg = build(&[preset, temperature])?;
+        assert_eq!(cfg.night.temp, 3000.try_into()?);
+        let source = shown_source(&cfg, "temperature");
+        assert_eq!(source.as_deref(), Some("user.toml"));
+
+        // a preset overrides the earlier layers and its own layer
+        let user = ("night = \"candle\"\ngamma = 0.9", "user.toml");
+        let cfg = build(&[temperature, user])?;
+        assert_eq!(cfg.night.temp, 1900.try_into()?);
+        assert_eq!(cfg.night.gamma, Gamma::default());
+        assert_eq!(cfg.day.temp, 6500.try_into()?);
+        let source = shown_source(&cfg, "temperature");
+        assert_eq!(source.as_deref(), Some("user.toml"));
+        Ok(())
+    }
+
+    #[test]
+    fn set_preset() -> Result<()> {
+        let presets = "[preset.movie]\ntemperature = 4500\nbrightness = 0.7";
+        let mut cfg = ConfigBuilder::default();
+        cfg.merge_with_config_file(layer(presets, "config.toml")?)?;
+
+        merge_args(&mut cfg, "set --preset movie --gamma 0.9")?;
+        let args = cfg.set_args.take().ok_or_else(|| anyhow!("no args"))?;
+        let cs = ConfigBuilder::apply_set_args(args, cfg.day.clone(), || None);
+        assert_eq!(cs.temp, 4500.try_into()?);
+        assert_eq!(cs.brght, 0.7.try_into()?);
+        assert_eq!(cs.gamma, 0.9.try_into()?);
+
+        let halogen = cfg.preset("halogen")?;
+        assert_eq!(halogen.temp, 3400.try_into()?);
+        assert_eq!(halogen.brght, Brightness::default());
+        let e = cfg.preset("cinema").err().map(|e| e.to_string());
+        let expected = "unknown preset `cinema` (available presets: movie, \
+                        candle, incandescent, halogen, daylight)";
+        assert_eq!(e.as_deref(), Some(expected));
+        Ok(())
+    }
+
+    #[test]
+    fn set_relative() -> Result<()> {
+        let last = ColorSettings {
+            temp: 4000.try_into()?,
+            brght: 0.8.try_into()?,
+            ..Default::default()
+        };
+        let set = |args: &str| -> Result<(ColorSettings, bool)> {
+            let mut cfg = ConfigBuilder::default();
+            cfg.merge_with_config_file(layer(
+                "[preset.dim]\nbrightness = 0.5",
+                "config.toml",
+            )?)?;
+            merge_args(&mut cfg, args)?;
+            let args =
+                cfg.set_args.take().ok_or_else(|| anyhow!("no args"))?;
+            let loaded = Cell::new(false);
+            let cs = ConfigBuilder::apply_set_args(args, cfg.day, || {
+                loaded.set(true);
+                Some(last.clone())
+            });
+            Ok((cs, loaded.get()))
+        };
+
+        // relative values apply to the persisted color settings
+        let (cs, loaded) = set("set --temperature +300 --brightness -10%")?;
+        assert!(loaded);
+        assert_eq!(cs.temp, 4300.try_into()?);
+        assert_eq!(cs.brght, 0.72.try_into()?);
+        let (cs, loaded) = set("set --temperature=-500 --tint 0.02")?;
+        assert!(loaded);
+        assert_eq!(cs.temp, 3500.try_into()?);
+        assert_eq!(cs.brght, 0.8.try_into()?);
+
+        // absolute values and presets replace them
+        let (cs, loaded) = set("set --temperature 5000")?;
+        assert!(!loaded);
+        assert_eq!(cs.brght, Brightness::default());
+        let (cs, loaded) = set("set --preset dim --temperature +300")?;
+        assert!(!loaded);
+        assert_eq!(cs.temp, 6800.try_into()?);
+        assert_eq!(cs.brght, 0.5.try_into()?);
+        Ok(())
+    }
+}
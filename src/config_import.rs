@@ -0,0 +1,418 @@
+/*  config_import.rs -- Import of Redshift and Gammastep configuration files
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    error::config::{ConfigError, ConfigFileError},
+    print_document,
+    types::{
+        BrightnessRange, GammaRange, LocationProviderType, TemperatureRange,
+        TransitionScheme,
+    },
+    types_display::WARN,
+};
+use itertools::Itertools;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use toml::Value;
+use tracing::warn;
+
+// Paths of the config files relative to the config directory, in the order
+// they are searched
+const CONFIG_PATHS: [&str; 3] = [
+    "redshift/redshift.conf",
+    "redshift.conf",
+    "gammastep/config.ini",
+];
+
+/// Sections of an INI file with the value and line number of each key
+#[derive(Debug, Default)]
+struct Ini {
+    path: PathBuf,
+    sections: BTreeMap<String, BTreeMap<String, (String, usize)>>,
+}
+
+/// Config file keys with the comment that is written above them
+#[derive(Debug, Default)]
+struct Imported(Vec<(&'static str, String, Value)>);
+
+pub fn run_config_import_mode(path: Option<&Path>) -> Result<(), ConfigError> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => find_config_file().ok_or(ConfigFileError::ImportNotFound)?,
+    };
+    let s = fs::read_to_string(&path)
+        .map_err(|e| ConfigFileError::OpenFailed(e, path.clone()))?;
+
+    let mut ini = Ini::new(&path, &s);
+    let imported = Imported::new(&mut ini);
+    ini.warn_unused();
+    print_document(imported.to_toml(&path));
+    Ok(())
+}
+
+/// Value of a key that is either set for both day and night or separately
+/// with the -day and -night suffixes
+fn day_night(ini: &mut Ini, name: &str) -> Option<(String, String)> {
+    let keys = [
+        name.to_string(),
+        format!("{name}-day"),
+        format!("{name}-night"),
+    ];
+    let [both, day, night] = keys.clone().map(|k| ini.take("redshift", &k));
+    let from = keys
+        .iter()
+        .zip([&both, &day, &night])
+        .filter_map(|(k, v)| v.as_ref().map(|v| format!("{k}={v}")))
+        .join(", ");
+
+    // the default value of gamma and brightness is 1.0
+    let day = day.or_else(|| both.clone());
+    let night = night.or(both);
+    match (day, night) {
+        (None, None) => None,
+        (day, night) => {
+            let day = day.unwrap_or_else(|| "1.0".into());
+            let night = night.unwrap_or_else(|| "1.0".into());
+            if day == night {
+                Some((from, day))
+            } else {
+                Some((from, format!("{day}-{night}")))
+            }
+        }
+    }
+}
+
+/// Adjustment method in the syntax of the config file, from the Redshift
+/// method name and its screen or card number and crtcs
+pub fn method_arg(
+    kind: &str,
+    num: Option<String>,
+    crtcs: Option<String>,
+) -> String {
+    match (num, crtcs) {
+        (None, None) => kind.to_string(),
+        (num, None) => format!("{kind}:{}", num.unwrap_or_default()),
+        (num, Some(crtcs)) => {
+            format!("{kind}:{}:{crtcs}", num.unwrap_or_default())
+        }
+    }
+}
+
+fn find_config_file() -> Option<PathBuf> {
+    let dir = dirs::config_dir()?;
+    CONFIG_PATHS
+        .iter()
+        .map(|p| dir.join(p))
+        .find(|p| p.is_file())
+}
+
+impl Ini {
+    fn new(path: &Path, s: &str) -> Self {
+        let mut ini = Self {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        let mut section = None;
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with([';', '#']) {
+                continue;
+            }
+
+            if let Some(name) =
+                line.strip_prefix('[').and_then(|l| l.strip_suffix(']'))
+            {
+                // gammastep renamed the redshift section
+                let name = match name.trim() {
+                    "general" => "redshift",
+                    name => name,
+                };
+                section = Some(name.to_string());
+            } else if let (Some((key, value)), Some(section)) =
+                (line.split_once('='), &section)
+            {
+                ini.sections.entry(section.clone()).or_default().insert(
+                    key.trim().to_string(),
+                    (value.trim().to_string(), i + 1),
+                );
+            } else {
+                ini.warn(i + 1, "invalid line is ignored");
+            }
+        }
+        ini
+    }
+
+    /// Remove a key and return its value
+    fn take(&mut self, section: &str, key: &str) -> Option<String> {
+        let keys = self.sections.get_mut(section)?;
+        keys.remove(key).map(|(value, _)| value)
+    }
+
+    fn contains(&self, section: &str, key: &str) -> bool {
+        self.sections
+            .get(section)
+            .is_some_and(|s| s.contains_key(key))
+    }
+
+    fn warn(&self, line: usize, msg: &str) {
+        let path = self.path.display();
+        warn!("{WARN}warning:{WARN:#} {path}:{line}: {msg}");
+    }
+
+    fn warn_unused(&self) {
+        for (section, keys) in &self.sections {
+            for (key, (_, line)) in keys {
+                let msg = format!("unsupported option `{key}` in [{section}]");
+                self.warn(*line, &msg);
+            }
+        }
+    }
+}
+
+impl Imported {
+    fn new(ini: &mut Ini) -> Self {
+        let mut imported = Self::default();
+        imported.color_settings(ini);
+        imported.scheme(ini);
+        imported.location(ini);
+        imported.method(ini);
+
+        imported.push_negated(ini, "reset-ramps", "preserve-gamma");
+        // transition was renamed to fade in redshift 1.12
+        if ini.contains("redshift", "fade") {
+            imported.push_negated(ini, "disable-fade", "fade");
+        } else {
+            imported.push_negated(ini, "disable-fade", "transition");
+        }
+
+        imported
+    }
+
+    fn push(&mut self, key: &'static str, from: &str, value: Value) {
+        self.0.push((key, from.to_string(), value));
+    }
+
+    /// Add a boolean key that is the negation of a 0 or 1 value
+    fn push_negated(&mut self, ini: &mut Ini, key: &'static str, name: &str) {
+        let Some(v) = ini.take("redshift", name) else {
+            return;
+        };
+        match v.parse::<i64>() {
+            Ok(n) => {
+                self.push(key, &format!("{name}={v}"), Value::Boolean(n == 0));
+            }
+            Err(_) => self.warn(key, &v, "expected 0 or 1"),
+        }
+    }
+
+    fn warn(&self, key: &str, value: &str, msg: &str) {
+        let s = format!("`{value}` is not a valid {key}: {msg}");
+        warn!("{WARN}warning:{WARN:#} {s}");
+    }
+
+    /// Add a key if its value is valid
+    fn push_parsed<T>(&mut self, key: &'static str, from: &str, value: String)
+    where
+        T: FromStr,
+        T::Err: Error,
+    {
+        match value.parse::<T>() {
+            Ok(_) => self.push(key, from, Value::String(value)),
+            Err(e) => self.warn(key, &value, &e.to_string()),
+        }
+    }
+
+    fn color_settings(&mut self, ini: &mut Ini) {
+        let temp = match (
+            ini.take("redshift", "temp-day"),
+            ini.take("redshift", "temp-night"),
+        ) {
+            (None, None) => None,
+            (day, night) => {
+                let from = format!(
+                    "temp-day={}, temp-night={}",
+                    day.as_deref().unwrap_or("(default)"),
+                    night.as_deref().unwrap_or("(default)")
+                );
+                let day = day.unwrap_or_else(|| "6500".into());
+                let night = night.unwrap_or_else(|| "4500".into());
+                Some((from, format!("{day}-{night}")))
+            }
+        };
+        if let Some((from, v)) = temp {
+            self.push_parsed::<TemperatureRange>("temperature", &from, v);
+        }
+
+        if let Some((from, v)) = day_night(ini, "gamma") {
+            self.push_parsed::<GammaRange>("gamma", &from, v);
+        }
+        if let Some((from, v)) = day_night(ini, "brightness") {
+            self.push_parsed::<BrightnessRange>("brightness", &from, v);
+        }
+    }
+
+    fn scheme(&mut self, ini: &mut Ini) {
+        let dawn = ini.take("redshift", "dawn-time");
+        let dusk = ini.take("redshift", "dusk-time");
+        let high = ini.take("redshift", "elevation-high");
+        let low = ini.take("redshift", "elevation-low");
+
+        match (dawn, dusk) {
+            (Some(dawn), Some(dusk)) => {
+                let from = format!("dawn-time={dawn}, dusk-time={dusk}");
+                let value = format!("{dawn} - {dusk}");
+                self.push_parsed::<TransitionScheme>("scheme", &from, value);
+                return;
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                let s = "dawn-time and dusk-time must be set together";
+                warn!("{WARN}warning:{WARN:#} {s}");
+            }
+            (None, None) => {}
+        }
+
+        if high.is_some() || low.is_some() {
+            let high = high.unwrap_or_else(|| "3".into());
+            let low = low.unwrap_or_else(|| "-6".into());
+            let from = format!("elevation-high={high}, elevation-low={low}");
+            let value = format!("{high}:{low}");
+            self.push_parsed::<TransitionScheme>("scheme", &from, value);
+        }
+    }
+
+    fn location(&mut self, ini: &mut Ini) {
+        let provider = ini.take("redshift", "location-provider");
+        let lat = ini.take("manual", "lat");
+        let lon = ini.take("manual", "lon");
+
+        match (provider.as_deref(), lat, lon) {
+            (Some("geoclue2"), _, _) => self.push(
+                "location",
+                "location-provider=geoclue2",
+                Value::String("geoclue2".into()),
+            ),
+            (Some("manual") | None, Some(lat), Some(lon)) => {
+                let from = format!("[manual] lat={lat}, lon={lon}");
+                let value = format!("{lat}:{lon}");
+                self.push_parsed::<LocationProviderType>(
+                    "location", &from, value,
+                );
+            }
+            (Some("manual"), _, _) => {
+                let s = "manual location requires both lat and lon";
+                warn!("{WARN}warning:{WARN:#} {s}");
+            }
+            (Some(p), _, _) => {
+                let s = format!("unsupported location provider `{p}`");
+                warn!("{WARN}warning:{WARN:#} {s}");
+            }
+            (None, _, _) => {}
+        }
+    }
+
+    fn method(&mut self, ini: &mut Ini) {
+        let Some(method) = ini.take("redshift", "adjustment-method") else {
+            return;
+        };
+        let from = format!("adjustment-method={method}");
+
+        let value = match method.as_str() {
+            "dummy" => Some("dummy".to_string()),
+            "w32gdi" | "wingdi" => Some("win32gdi".to_string()),
+            "randr" | "vidmode" | "drm" => {
+                let num = if method == "drm" { "card" } else { "screen" };
+                let num = ini.take(&method, num);
+                let crtcs = ini.take(&method, "crtc");
+                Some(method_arg(&method, num, crtcs))
+            }
+            m => {
+                let s = format!("unsupported adjustment method `{m}`");
+                warn!("{WARN}warning:{WARN:#} {s}");
+                None
+            }
+        };
+        if let Some(value) = value {
+            self.push("method", &from, Value::String(value));
+        }
+    }
+
+    fn to_toml(&self, path: &Path) -> String {
+        let mut s = format!(
+            "# Imported from {} by `reddish-shift config import`\n",
+            path.display()
+        );
+        for (key, from, value) in &self.0 {
+            let _ = write!(s, "\n# {from}\n{key} = {value}\n");
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn config_import() -> Result<()> {
+        let s = "
+; Global settings for redshift
+[redshift]
+temp-day=5700
+temp-night=3500
+fade=1
+gamma=0.8
+gamma-night=0.8:0.7:0.8
+dawn-time=6:00-7:45
+dusk-time=18:35-20:15
+location-provider=manual
+adjustment-method=randr
+unknown-option=1
+
+[manual]
+lat=48.1
+lon=11.6
+
+[randr]
+screen=0
+crtc=62,63
+";
+        let mut ini = Ini::new(Path::new("redshift.conf"), s);
+        let toml = Imported::new(&mut ini).to_toml(Path::new("redshift.conf"));
+        let table = toml.parse::<toml::Table>()?;
+        let get = |k: &str| table[k].to_string();
+
+        assert_eq!(get("temperature"), r#""5700-3500""#);
+        assert_eq!(get("gamma"), r#""0.8-0.8:0.7:0.8""#);
+        assert_eq!(get("scheme"), r#""6:00-7:45 - 18:35-20:15""#);
+        assert_eq!(get("location"), r#""48.1:11.6""#);
+        assert_eq!(get("method"), r#""randr:0:62,63""#);
+        assert_eq!(get("disable-fade"), "false");
+        assert!(!table.contains_key("brightness"));
+
+        let unused = ini.sections.values().flat_map(|s| s.keys());
+        assert_eq!(unused.collect_vec(), ["unknown-option"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+/*  curve.rs -- Custom curves and 1D LUT files
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::CurveError;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// Number of samples that control points are sampled at
+const POINTS_SIZE: usize = 1024;
+
+/// A curve of each channel that maps the values of the ramps before the
+/// white point and brightness are applied. The curves are evenly spaced
+/// samples between 0 and 1 and are linearly interpolated
+#[derive(Debug, Clone)]
+pub struct Curve {
+    // None if the curve is linear or blended from two curves
+    path: Option<PathBuf>,
+    samples: Arc<[Vec<f64>; 3]>,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self {
+            path: None,
+            samples: Arc::new([
+                vec![0.0, 1.0],
+                vec![0.0, 1.0],
+                vec![0.0, 1.0],
+            ]),
+        }
+    }
+}
+
+impl Curve {
+    /// Read a curve from a file. The format depends on the extension:
+    ///   .cube  a 1D LUT (LUT_1D_SIZE)
+    ///   .csv   rows of evenly spaced samples with a value for all channels
+    ///          or one for each channel
+    ///   other  lines of control points, x followed by a value for all
+    ///          channels or one for each channel
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CurveError> {
+        let path = path.as_ref().to_path_buf();
+        let s = fs::read_to_string(&path)
+            .map_err(|e| CurveError::ReadFailed(e, path.clone()))?;
+        let ext = path.extension().and_then(|e| e.to_str());
+        let rows = match ext.map(str::to_lowercase).as_deref() {
+            Some("cube") => parse_cube(&s),
+            Some("csv") => parse_csv(&s),
+            _ => parse_points(&s),
+        }
+        .map_err(|(line, msg)| CurveError::Invalid {
+            path: path.clone(),
+            line,
+            msg: msg.into(),
+        })?;
+        if rows.len() < 2 {
+            return Err(CurveError::TooShort(path));
+        }
+
+        let samples = [0, 1, 2].map(|c| rows.iter().map(|r| r[c]).collect());
+        Ok(Self {
+            path: Some(path),
+            samples: Arc::new(samples),
+        })
+    }
+
+    /// A curve from the samples of each channel, e.g. of a curve that was
+    /// saved with the color settings. Returns None if the samples are invalid
+    pub fn from_samples(
+        path: Option<PathBuf>,
+        samples: [Vec<f64>; 3],
+    ) -> Option<Self> {
+        let valid = samples.iter().all(|s| {
+            s.len() >= 2 && s.iter().all(|v| (0.0..=1.0).contains(v))
+        });
+        valid.then(|| Self {
+            path,
+            samples: Arc::new(samples),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn samples(&self) -> &[Vec<f64>; 3] {
+        &self.samples
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.samples
+            .iter()
+            .all(|s| s.len() == 2 && s[0] == 0.0 && s[1] == 1.0)
+    }
+
+    /// Value of the curve of a channel at x between 0 and 1
+    pub fn apply(&self, x: f64, c: usize) -> f64 {
+        sample(&self.samples[c], x)
+    }
+
+    /// Blend two curves linearly. Curves of different sizes are resampled
+    /// to the larger size first
+    pub fn interpolate_with(&self, other: &Self, alpha: f64) -> Self {
+        if alpha <= 0.0 || Arc::ptr_eq(&self.samples, &other.samples) {
+            return self.clone();
+        } else if alpha >= 1.0 {
+            return other.clone();
+        }
+
+        let samples = [0, 1, 2].map(|c| {
+            let (a, b) = (&self.samples[c], &other.samples[c]);
+            let n = a.len().max(b.len());
+            (0..n)
+                .map(|i| {
+                    let x = i as f64 / (n - 1) as f64;
+                    (1.0 - alpha) * sample(a, x) + alpha * sample(b, x)
+                })
+                .collect()
+        });
+        Self {
+            path: None,
+            samples: Arc::new(samples),
+        }
+    }
+
+    /// Largest difference between the values of two curves
+    pub fn max_diff(&self, other: &Self) -> f64 {
+        (0..3)
+            .flat_map(|c| {
+                let (a, b) = (&self.samples[c], &other.samples[c]);
+                let n = a.len().max(b.len());
+                (0..n).map(move |i| {
+                    let x = i as f64 / (n - 1) as f64;
+                    (sample(a, x) - sample(b, x)).abs()
+                })
+            })
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Linearly interpolate evenly spaced samples
+fn sample(samples: &[f64], x: f64) -> f64 {
+    let n = samples.len();
+    let x = x.clamp(0.0, 1.0) * (n - 1) as f64;
+    let (i, t) = (x as usize, x.fract());
+    let (a, b) = (samples[i], samples[(i + 1).min(n - 1)]);
+    a + (b - a) * t
+}
+
+type Rows = Vec<[f64; 3]>;
+type ParseError = (usize, &'static str);
+
+/// Numbers of a line with a value for all channels or one for each channel
+fn parse_values<'a>(
+    values: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<[f64; 3], ParseError> {
+    let values = values
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| (line, "expected numbers"))?;
+    if values.iter().any(|v| !(0.0..=1.0).contains(v)) {
+        return Err((line, "values must be between 0 and 1"));
+    }
+    match *values.as_slice() {
+        [v] => Ok([v; 3]),
+        [r, g, b] => Ok([r, g, b]),
+        _ => Err((line, "expected one or three values")),
+    }
+}
+
+/// Lines that are not empty or comments, with their line numbers
+fn lines(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'))
+}
+
+fn parse_cube(s: &str) -> Result<Rows, ParseError> {
+    let mut size = None;
+    let mut rows = Vec::new();
+    for (n, l) in lines(s) {
+        let mut words = l.split_whitespace();
+        match words.next() {
+            Some("TITLE") => {}
+            Some("LUT_1D_SIZE") => {
+                let v = words.next().and_then(|v| v.parse::<usize>().ok());
+                size = Some((v.ok_or((n, "invalid LUT_1D_SIZE"))?, n));
+            }
+            Some("LUT_3D_SIZE") => {
+                return Err((n, "3D LUTs are not supported"));
+            }
+            Some(k @ ("DOMAIN_MIN" | "DOMAIN_MAX" | "LUT_1D_INPUT_RANGE")) => {
+                let v = words
+                    .map(str::parse::<f64>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| (n, "expected numbers"))?;
+                let ok = match k {
+                    "DOMAIN_MIN" => v.iter().all(|v| *v == 0.0),
+                    "DOMAIN_MAX" => v.iter().all(|v| *v == 1.0),
+                    _ => v == [0.0, 1.0],
+                };
+                if !ok {
+                    return Err((
+                        n,
+                        "only the domain from 0 to 1 is supported",
+                    ));
+                }
+            }
+            _ => rows.push(parse_values(l.split_whitespace(), n)?),
+        }
+    }
+
+    match size {
+        Some((size, n)) if size != rows.len() => {
+            Err((n, "number of values does not match LUT_1D_SIZE"))
+        }
+        None => Err((1, "missing LUT_1D_SIZE")),
+        _ => Ok(rows),
+    }
+}
+
+fn parse_csv(s: &str) -> Result<Rows, ParseError> {
+    let mut rows = Vec::new();
+    for (n, l) in lines(s) {
+        match parse_values(l.split(','), n) {
+            Ok(r) => rows.push(r),
+            // a header
+            Err(_) if rows.is_empty() && l.contains(char::is_alphabetic) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(rows)
+}
+
+fn parse_points(s: &str) -> Result<Rows, ParseError> {
+    let mut points = Vec::<(f64, [f64; 3])>::new();
+    for (n, l) in lines(s) {
+        let mut words = l.split(|c: char| c.is_whitespace() || c == ',');
+        let mut words = words.by_ref().filter(|w| !w.is_empty());
+        let x = words
+            .next()
+            .and_then(|x| x.parse::<f64>().ok())
+            .ok_or((n, "expected an x value followed by the values"))?;
+        if !(0.0..=1.0).contains(&x) {
+            return Err((n, "values must be between 0 and 1"));
+        }
+        if points.last().is_some_and(|(x0, _)| x <= *x0) {
+            return Err((n, "x values must be increasing"));
+        }
+        points.push((x, parse_values(words, n)?));
+    }
+    if points.len() < 2 {
+        return Ok(points.into_iter().map(|(_, y)| y).collect());
+    }
+
+    // values before the first point and after the last one are constant
+    Ok((0..POINTS_SIZE)
+        .map(|i| {
+            let x = i as f64 / (POINTS_SIZE - 1) as f64;
+            let j = points.partition_point(|(x0, _)| *x0 <= x);
+            match j {
+                0 => points[0].1,
+                j if j == points.len() => points[j - 1].1,
+                j => {
+                    let ((x0, y0), (x1, y1)) = (points[j - 1], points[j]);
+                    let t = (x - x0) / (x1 - x0);
+                    [0, 1, 2].map(|c| y0[c] + (y1[c] - y0[c]) * t)
+                }
+            }
+        })
+        .collect())
+}
+
+impl FromStr for Curve {
+    type Err = CurveError;
+
+    /// A path of a curve file or linear
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "linear" => Ok(Self::default()),
+            s => Self::load(s),
+        }
+    }
+}
+
+impl PartialEq for Curve {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.samples, &other.samples)
+            || self.max_diff(other) < 1e-6
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curve_formats() -> Result<(), (usize, &'static str)> {
+        let cube = "# comment\nTITLE \"lift\"\nLUT_1D_SIZE 3\n\
+                    0.1 0.0 0.0\n0.5 0.5 0.5\n1.0 1.0 1.0\n";
+        assert_eq!(parse_cube(cube)?[0], [0.1, 0.0, 0.0]);
+        assert!(parse_cube("LUT_1D_SIZE 3\n0 0 0\n1 1 1\n").is_err());
+        assert!(parse_cube("LUT_3D_SIZE 2\n").is_err());
+
+        let csv = "r,g,b\n0,0,0\n0.6,0.5,0.4\n1,1,1\n";
+        assert_eq!(parse_csv(csv)?[1], [0.6, 0.5, 0.4]);
+        assert_eq!(parse_csv("0\n1\n")?, [[0.0; 3], [1.0; 3]]);
+
+        let rows = parse_points("0 0.05\n0.5 0.5 0.5 0.6\n1 1\n")?;
+        assert_eq!(rows.len(), POINTS_SIZE);
+        assert_eq!(rows[0], [0.05; 3]);
+        assert_eq!(rows[POINTS_SIZE - 1], [1.0; 3]);
+        assert!(parse_points("0.5 0\n0.2 1\n").is_err());
+
+        let samples = [0, 1, 2].map(|c| rows.iter().map(|r| r[c]).collect());
+        let curve = Curve {
+            path: None,
+            samples: Arc::new(samples),
+        };
+        assert!((curve.apply(0.0, 0) - 0.05).abs() < 1e-9);
+        assert!((curve.apply(0.5, 2) - 0.6).abs() < 1e-3);
+        let linear = Curve::default();
+        assert!(linear.is_linear() && !curve.is_linear());
+        let half = linear.interpolate_with(&curve, 0.5);
+        assert!((half.apply(0.0, 0) - 0.025).abs() < 1e-9);
+        assert!((half.max_diff(&linear) - 0.05).abs() < 1e-3);
+        Ok(())
+    }
+}
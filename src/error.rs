@@ -66,6 +66,19 @@ impl Display for ReddishError {
     }
 }
 
+impl ReddishError {
+    pub fn exit_code(&self) -> exitcode::ExitCode {
+        match &self.0 {
+            ReddishErrorKind::Config(
+                config::ConfigError::File(_)
+                | config::ConfigError::Override(_),
+            ) => exitcode::CONFIG,
+            ReddishErrorKind::Config(_) => exitcode::UNAVAILABLE,
+            _ => exitcode::SOFTWARE,
+        }
+    }
+}
+
 //
 
 #[derive(Debug, Error)]
@@ -78,8 +91,19 @@ pub enum ReddishErrorKind {
     Provider(#[from] ProviderError),
     #[error("failed to set CTRL-C handler:\n{0}")]
     Ctrlc(#[from] ctrlc::Error),
+    #[cfg(unix)]
+    #[error("failed to set signal handler:\n{0}")]
+    Signal(#[from] nix::Error),
     #[error("failed to handle CTRL-C:\n{0}")]
     Mpsc(#[from] std::sync::mpsc::RecvTimeoutError),
+    #[error("failed to serialize output:\n{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("gamma ramps snapshot failed:\n{0}")]
+    Snapshot(#[from] SnapshotError),
+    #[error("export failed:\n{0}")]
+    Export(#[from] ExportError),
+    #[error("replay failed:\n{0}")]
+    Recording(#[from] RecordingError),
 }
 
 #[derive(Debug, Error)]
@@ -87,12 +111,94 @@ pub enum ReddishErrorKind {
 // #[error("Unable to get location from provider")]
 pub struct ProviderError;
 
+#[derive(Debug, Error)]
+pub enum TimezoneError {
+    #[error(
+        "unable to find the timezone in TZ, /etc/localtime or /etc/timezone"
+    )]
+    ZoneNotFound,
+    #[error("timezone {0} has no location in the tz database")]
+    NoLocation(String),
+}
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("unable to find the state directory")]
+    StateDirNotFound,
+    #[error("unable to read file ({1}):\n{0}")]
+    ReadFailed(io::Error, PathBuf),
+    #[error("unable to write file ({1}):\n{0}")]
+    WriteFailed(io::Error, PathBuf),
+    #[error("unable to deserialize file ({1}):\n{0}")]
+    DeserializeFailed(toml::de::Error, PathBuf),
+    #[error("unable to serialize state:\n{0}")]
+    SerializeFailed(#[from] toml::ser::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum CurveError {
+    #[error("unable to read file ({1}):\n{0}")]
+    ReadFailed(io::Error, PathBuf),
+    #[error("invalid curve ({path}:{line}): {msg}")]
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        msg: String,
+    },
+    #[error("curve must have at least two values ({0})")]
+    TooShort(PathBuf),
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("unable to write file ({1}):\n{0}")]
+    WriteFailed(io::Error, PathBuf),
+    #[error("unable to write to stdout:\n{0}")]
+    StdoutFailed(io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("unable to read file ({1}):\n{0}")]
+    ReadFailed(io::Error, PathBuf),
+    #[error("unable to write file ({1}):\n{0}")]
+    WriteFailed(io::Error, PathBuf),
+    #[error("invalid record ({1}:{2}):\n{0}")]
+    InvalidRecord(serde_json::Error, PathBuf, usize),
+    #[error("unable to serialize record:\n{0}")]
+    SerializeFailed(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("unable to find the runtime directory")]
+    RuntimeDirNotFound,
+    #[error("unable to find the data directory")]
+    DataDirNotFound,
+    #[error("invalid snapshot name: {0}")]
+    InvalidName(String),
+    #[error("unable to read directory ({1}):\n{0}")]
+    ReadDirFailed(io::Error, PathBuf),
+    #[error("snapshot not found ({0})")]
+    NotFound(PathBuf),
+    #[error("unable to read file ({1}):\n{0}")]
+    ReadFailed(io::Error, PathBuf),
+    #[error("unable to write file ({1}):\n{0}")]
+    WriteFailed(io::Error, PathBuf),
+    #[error("unable to deserialize file ({1}):\n{0}")]
+    DeserializeFailed(toml::de::Error, PathBuf),
+    #[error("unable to serialize snapshot:\n{0}")]
+    SerializeFailed(#[from] toml::ser::Error),
+}
+
 #[derive(Debug, Error)]
 pub enum AdjusterError {
     #[error("set gamma ramps:\n{0}")]
     Set(AdjusterErrorInner),
     #[error("restore gamma ramps:\n{0}")]
     Restore(AdjusterErrorInner),
+    #[error("get gamma ramps:\n{0}")]
+    Get(AdjusterErrorInner),
 }
 
 #[derive(Debug, Error)]
@@ -109,6 +215,22 @@ pub enum AdjusterErrorInner {
     #[error("drm:\n{0}")]
     Drm(#[from] VecError<io::Error>),
 
+    #[cfg(unix_without_macos)]
+    #[error("mutter:\n{0}")]
+    Mutter(VecError<zbus::Error>),
+
+    #[cfg(unix_without_macos)]
+    #[error("kwin:\n{0}")]
+    Kwin(Box<zbus::Error>),
+
+    #[cfg(unix_without_macos)]
+    #[error("vt:\n{0}")]
+    Vt(io::Error),
+
+    #[cfg(unix_without_macos)]
+    #[error("file:\n{0}")]
+    File(#[from] RecordingError),
+
     #[cfg(windows)]
     #[error("win32gdi:\n{0}")]
     Win32Gdi(#[from] Win32GdiError),
@@ -130,7 +252,9 @@ pub mod config {
     #[cfg(windows)]
     use gamma::Win32GdiError;
     #[cfg(unix_without_macos)]
-    use gamma::{DrmError, RandrError, VidmodeError};
+    use gamma::{
+        DrmError, KwinError, MutterError, RandrError, VidmodeError, VtError,
+    };
 
     #[derive(Debug, Error)]
     pub enum ConfigError {
@@ -140,6 +264,8 @@ pub mod config {
         MethodInit(#[from] AdjustmentMethodError),
         #[error("{0}")]
         File(#[from] ConfigFileError),
+        #[error("invalid configuration:\n{0}")]
+        Override(VecError<OverrideError>),
     }
 
     #[derive(Debug, Error)]
@@ -148,10 +274,93 @@ pub mod config {
         PathNotFile(PathBuf),
         #[error("unable to find configuration file. Use the -c flag.")]
         ConfigDirNotFound,
+        #[error("unable to find a Redshift or Gammastep configuration file. Give its path as an argument.")]
+        ImportNotFound,
         #[error("unable to read file ({1}):\n{0}")]
         OpenFailed(io::Error, PathBuf),
-        #[error("unable to deserialize file ({1}):\n{0}")]
-        DeserializeFailed(toml::de::Error, PathBuf),
+        #[error("invalid configuration:\n{0}")]
+        Invalid(VecError<ConfigDiagnostic>),
+        #[error("unknown preset `{0}` (available presets: {1})")]
+        UnknownPreset(String, String),
+    }
+
+    /// An error in a config file with the location of the offending span
+    #[derive(Debug)]
+    pub struct ConfigDiagnostic {
+        pub path: PathBuf,
+        pub line: usize,
+        pub column: usize,
+        pub source_line: String,
+        pub len: usize,
+        pub kind: ConfigDiagnosticKind,
+    }
+
+    #[derive(Debug, Error)]
+    pub enum ConfigDiagnosticKind {
+        #[error("{0}")]
+        Syntax(String),
+        #[error("unknown key `{key}`{}", .suggestion.map(|s| format!(", did you mean `{s}`?")).unwrap_or_default())]
+        UnknownKey {
+            key: String,
+            suggestion: Option<&'static str>,
+        },
+        #[error("invalid value of `{key}`")]
+        InvalidValue { key: String, msg: String },
+        #[error("included file not found ({})", .0.display())]
+        IncludeNotFound(PathBuf),
+        #[error("include cycle: {0}")]
+        IncludeCycle(String),
+    }
+
+    impl Error for ConfigDiagnostic {}
+
+    /// An error in an environment variable or a --set argument
+    #[derive(Debug)]
+    pub struct OverrideError {
+        pub origin: String,
+        pub kind: ConfigDiagnosticKind,
+    }
+
+    impl Error for OverrideError {}
+
+    impl Display for OverrideError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            let Self { origin, kind } = self;
+            write!(f, "{origin}: {kind}")?;
+            if let ConfigDiagnosticKind::InvalidValue { msg, .. } = kind {
+                write!(f, "\n{}", flatten(msg))?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Errors of a value are listed without bullets, since the diagnostic
+    /// is already an item of a list
+    fn flatten(msg: &str) -> String {
+        msg.lines().map(|l| l.trim_start_matches("- ")).join("\n")
+    }
+
+    impl Display for ConfigDiagnostic {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            let Self {
+                path,
+                line,
+                column,
+                source_line,
+                len,
+                kind,
+            } = self;
+            let pad = " ".repeat(line.to_string().len());
+            let caret = " ".repeat(column - 1) + &"^".repeat(*len);
+            writeln!(f, "{}:{line}:{column}: {kind}", path.display())?;
+            writeln!(f, "{pad} |")?;
+            writeln!(f, "{line} | {source_line}")?;
+            write!(f, "{pad} | {caret}")?;
+            if let ConfigDiagnosticKind::InvalidValue { msg, .. } = kind {
+                write!(f, "\n{}", flatten(msg))?;
+            }
+            Ok(())
+        }
     }
 
     #[cfg(unix_without_macos)]
@@ -175,6 +384,34 @@ pub mod config {
         }
     }
 
+    #[cfg(unix_without_macos)]
+    impl From<MutterError> for ConfigError {
+        fn from(e: MutterError) -> Self {
+            Self::MethodInit(AdjustmentMethodError::Mutter(e))
+        }
+    }
+
+    #[cfg(unix_without_macos)]
+    impl From<KwinError> for ConfigError {
+        fn from(e: KwinError) -> Self {
+            Self::MethodInit(AdjustmentMethodError::Kwin(e))
+        }
+    }
+
+    #[cfg(unix_without_macos)]
+    impl From<VtError> for ConfigError {
+        fn from(e: VtError) -> Self {
+            Self::MethodInit(AdjustmentMethodError::Vt(e))
+        }
+    }
+
+    #[cfg(unix_without_macos)]
+    impl From<RecordingError> for ConfigError {
+        fn from(e: RecordingError) -> Self {
+            Self::MethodInit(AdjustmentMethodError::File(e))
+        }
+    }
+
     #[cfg(windows)]
     impl From<Win32GdiError> for ConfigError {
         fn from(e: Win32GdiError) -> Self {
@@ -202,6 +439,22 @@ pub mod gamma {
         #[error("drm:\n{0}")]
         Drm(#[from] DrmError),
 
+        #[cfg(unix_without_macos)]
+        #[error("mutter:\n{0}")]
+        Mutter(#[from] MutterError),
+
+        #[cfg(unix_without_macos)]
+        #[error("kwin:\n{0}")]
+        Kwin(#[from] KwinError),
+
+        #[cfg(unix_without_macos)]
+        #[error("vt:\n{0}")]
+        Vt(#[from] VtError),
+
+        #[cfg(unix_without_macos)]
+        #[error("file:\n{0}")]
+        File(#[from] RecordingError),
+
         #[cfg(windows)]
         #[error("drm:\n{0}")]
         Win32Gdi(#[from] Win32GdiError),
@@ -214,6 +467,17 @@ pub mod gamma {
         pub err: E,
     }
 
+    #[cfg(unix_without_macos)]
+    #[derive(Debug, Error)]
+    pub enum SelectorError {
+        #[error("crtc numbers must be unique")]
+        NonUniqueCrtc,
+        #[error("valid crtcs are: {0:?}")]
+        InvalidCrtc(Vec<u32>),
+        #[error("no output matches {0}, valid outputs are: {1:?}")]
+        InvalidOutput(String, Vec<String>),
+    }
+
     //
 
     #[cfg(unix_without_macos)]
@@ -244,10 +508,10 @@ pub mod gamma {
         GetResourcesFailed(X11Error),
         #[error("unsupported version ({major}.{minor})")]
         UnsupportedVersion { major: u32, minor: u32 },
-        #[error("crtc numbers must be unique")]
-        NonUniqueCrtc,
-        #[error("valid crtcs are: {0:?}")]
-        InvalidCrtc(Vec<u32>),
+        #[error("unable to get outputs:\n{0}")]
+        GetOutputsFailed(X11Error),
+        #[error("{0}")]
+        Selector(#[from] SelectorError),
         #[error("unable to send requests:\n{0}")]
         SendRequestFailed(VecError<x11rb::errors::ConnectionError>),
         #[error("crtc:\n{0}")]
@@ -276,10 +540,10 @@ pub mod gamma {
         GetResourcesFailed(io::Error),
         #[error("crtc numbers must be non zero")]
         ZeroValueCrtc,
-        #[error("crtc numbers must be unique")]
-        NonUniqueCrtc,
-        #[error("valid crtcs are: {0:?}")]
-        InvalidCrtc(Vec<u32>),
+        #[error("unable to get connectors:\n{0}")]
+        GetConnectorsFailed(io::Error),
+        #[error("{0}")]
+        Selector(#[from] SelectorError),
         #[error("crtc:\n{0}")]
         GetCrtcs(VecError<CrtcError<u32, DrmCrtcError>>),
     }
@@ -289,6 +553,8 @@ pub mod gamma {
     pub enum DrmCrtcError {
         #[error("unable to get gamma ramp:\n{0}")]
         GetRampFailed(io::Error),
+        #[error("unable to get properties:\n{0}")]
+        GetPropertiesFailed(io::Error),
         #[error("unable to get gamma ramp size:\n{0}")]
         GetRampSizeFailed(io::Error),
         #[error("gamma ramp size too small: {0}")]
@@ -297,6 +563,54 @@ pub mod gamma {
 
     //
 
+    #[cfg(unix_without_macos)]
+    #[derive(Debug, Error)]
+    pub enum MutterError {
+        #[error("connection failed:\n{0}")]
+        ConnectFailed(Box<zbus::Error>),
+        #[error("unable to get resources:\n{0}")]
+        GetResourcesFailed(Box<zbus::Error>),
+        #[error("{0}")]
+        Selector(#[from] SelectorError),
+        #[error("crtc:\n{0}")]
+        GetCrtcs(VecError<CrtcError<u32, MutterCrtcError>>),
+    }
+
+    #[cfg(unix_without_macos)]
+    #[derive(Debug, Error)]
+    pub enum MutterCrtcError {
+        #[error("unable to get gamma ramp:\n{0}")]
+        GetRampFailed(Box<zbus::Error>),
+        #[error("gamma ramp size too small: {0}")]
+        InvalidRampSize(u32),
+    }
+
+    //
+
+    #[cfg(unix_without_macos)]
+    #[derive(Debug, Error)]
+    pub enum KwinError {
+        #[error("connection failed:\n{0}")]
+        ConnectFailed(Box<zbus::Error>),
+        #[error("unable to get night light properties:\n{0}")]
+        GetPropertyFailed(Box<zbus::Error>),
+        #[error("night light is not available")]
+        NotAvailable,
+    }
+
+    //
+
+    #[cfg(unix_without_macos)]
+    #[derive(Debug, Error)]
+    pub enum VtError {
+        #[error("failed to open device ({1}):\n{0}")]
+        OpenDeviceFailed(io::Error, PathBuf),
+        #[error("unable to get console palette ({1}):\n{0}")]
+        GetPaletteFailed(io::Error, PathBuf),
+    }
+
+    //
+
     #[cfg(windows)]
     #[derive(Debug, Error)]
     pub enum Win32GdiError {
@@ -314,10 +628,11 @@ pub mod gamma {
 pub mod types {
     use super::*;
     use crate::types::{
-        Elevation, TimeOffset, MAX_BRIGHTNESS, MAX_ELEVATION, MAX_GAMMA,
-        MAX_LATITUDE, MAX_LONGITUDE, MAX_TEMPERATURE, MIN_BRIGHTNESS,
+        Elevation, TimeOffset, MAX_BLACK_LEVEL, MAX_BRIGHTNESS, MAX_CONTRAST,
+        MAX_ELEVATION, MAX_GAMMA, MAX_LATITUDE, MAX_LONGITUDE,
+        MAX_TEMPERATURE, MAX_TINT, MIN_BRIGHTNESS, MIN_CONTRAST,
         MIN_ELEVATION, MIN_GAMMA, MIN_LATITUDE, MIN_LONGITUDE,
-        MIN_TEMPERATURE,
+        MIN_TEMPERATURE, MIN_TINT,
     };
 
     #[derive(Debug, Error)]
@@ -333,6 +648,28 @@ pub mod types {
     #[error("gamma must be between {MIN_GAMMA} and {MAX_GAMMA} ({0})")]
     pub struct GammaError(pub f64);
 
+    #[derive(Debug, Error)]
+    #[error("tint must be between {MIN_TINT} and {MAX_TINT} ({0})")]
+    pub struct TintError(pub f64);
+
+    #[derive(Debug, Error)]
+    #[error("channel gain must be between 0 and 1 ({0})")]
+    pub struct ChannelError(pub f64);
+
+    #[derive(Debug, Error)]
+    #[error("invert must be true, false or between 0 and 1 ({0})")]
+    pub struct InvertError(pub f64);
+
+    #[derive(Debug, Error)]
+    #[error("black level must be between 0 and {MAX_BLACK_LEVEL} ({0})")]
+    pub struct BlackLevelError(pub f64);
+
+    #[derive(Debug, Error)]
+    #[error(
+        "contrast must be between {MIN_CONTRAST} and {MAX_CONTRAST} ({0})"
+    )]
+    pub struct ContrastError(pub f64);
+
     #[derive(Debug, Error)]
     #[error(
         "latitude must be between {MAX_LATITUDE}° and {MIN_LATITUDE}° ({0})"
@@ -384,6 +721,10 @@ pub mod types {
     #[error("gamma:\n{0}")]
     pub struct GammaRgbError(#[from] VecError<GammaError>);
 
+    #[derive(Debug, Error)]
+    #[error("channels:\n{0}")]
+    pub struct ChannelsRgbError(#[from] VecError<ChannelError>);
+
     type TimeErrorT = Coprod!(HourError, MinuteError);
     #[derive(Debug, Error)]
     #[error("time:\n{0}")]
@@ -400,6 +741,12 @@ pub mod types {
         }
     }
 
+    impl From<Vec<ChannelError>> for ChannelsRgbError {
+        fn from(v: Vec<ChannelError>) -> Self {
+            Self(VecError(v))
+        }
+    }
+
     impl From<Vec<LocationT>> for LocationError {
         fn from(v: Vec<LocationT>) -> Self {
             Self(VecError(v))
@@ -460,6 +807,62 @@ pub mod parse {
     }
     impl DayNightErrorType for GammaError {}
 
+    #[derive(Debug, Error)]
+    pub enum TintError {
+        #[error("{0} ({1})")]
+        Parse(ParseFloatError, String),
+        #[error("{0}")]
+        Type(#[from] types::TintError),
+    }
+    impl DayNightErrorType for TintError {}
+
+    pub type ChannelsErrorT = Coprod!(ParseFloatError, types::ChannelError);
+    #[derive(Debug, Error)]
+    pub enum ChannelsError {
+        #[error("{0}")]
+        Multiple(#[from] VecError<ChannelsErrorT>),
+        #[error("- {0}")]
+        Single(#[from] ChannelsErrorT),
+        #[error("- invalid format")]
+        Fmt,
+    }
+    impl DayNightErrorType for ChannelsError {}
+
+    #[derive(Debug, Error)]
+    pub enum InvertError {
+        #[error("{0} ({1})")]
+        Parse(ParseFloatError, String),
+        #[error("{0}")]
+        Type(#[from] types::InvertError),
+    }
+    impl DayNightErrorType for InvertError {}
+
+    #[derive(Debug, Error)]
+    pub enum BlackLevelError {
+        #[error("{0} ({1})")]
+        Parse(ParseFloatError, String),
+        #[error("{0}")]
+        Type(#[from] types::BlackLevelError),
+    }
+    impl DayNightErrorType for BlackLevelError {}
+
+    #[derive(Debug, Error)]
+    pub enum ContrastError {
+        #[error("{0} ({1})")]
+        Parse(ParseFloatError, String),
+        #[error("{0}")]
+        Type(#[from] types::ContrastError),
+    }
+    impl DayNightErrorType for ContrastError {}
+
+    #[derive(Debug, Error)]
+    pub enum ColorMatrixError {
+        #[error("- {0} ({1})")]
+        Parse(ParseFloatError, String),
+        #[error("- expected a saturation or nine comma separated numbers")]
+        Fmt,
+    }
+
     #[derive(Debug, Error)]
     pub enum LatitudeError {
         #[error("{0} ({1})")]
@@ -542,6 +945,10 @@ pub mod parse {
         pub elev: ElevationRangeError,
     }
 
+    #[derive(Debug, Error)]
+    #[error("unknown format `{0}` (available formats: csv, cube, icc)")]
+    pub struct ExportFormatError(pub String);
+
     #[derive(Debug, Error)]
     #[error("as automatic:\n- did not match any provider\nas manual:\n{loc}")]
     pub struct LocationProviderError {
@@ -554,8 +961,34 @@ pub mod parse {
         InvalidName(String),
         #[error("display number ({1}):\n{0}")]
         Display(ParseIntError, String),
-        #[error("crtc numbers:\n{0}")]
-        Crtcs(#[from] VecError<CrtcError<String, ParseIntError>>),
+        #[error("ramp size must be a number of at least 2 ({0})")]
+        RampSize(String),
+        #[error("crtcs:\n{0}")]
+        Crtcs(#[from] VecError<CrtcError<String, CrtcSelectorError>>),
+    }
+
+    #[derive(Debug, Error)]
+    pub enum CrtcSelectorError {
+        #[error("edid manufacturer id must be three letters")]
+        Manufacturer,
+        #[error("edid serial number must not be empty")]
+        Serial,
+        #[error(
+            "expected a crtc number, an output name or edid=MFG[/SERIAL]"
+        )]
+        Fmt,
+    }
+
+    #[derive(Debug, Error)]
+    #[error("expected KEY=VALUE")]
+    pub struct KeyValueError;
+
+    #[derive(Debug, Error)]
+    pub enum SetValueError<E: Error> {
+        #[error("{0}")]
+        Absolute(E),
+        #[error("{0} ({1})")]
+        Relative(ParseFloatError, String),
     }
 
     #[derive(Debug, Error)]
@@ -565,10 +998,15 @@ pub mod parse {
         #[error("selecting crtcs is not supported")]
         SelectingCrtcNotSupported,
 
-        #[cfg(windows)]
         #[error("selecting display is not supported")]
         SelectingDisplayNotSupported,
 
+        #[error("missing the path of the file")]
+        MissingPath,
+
+        #[error("emulated crtcs must be selected by their ids")]
+        EmulatedCrtcs,
+
         #[error("invalid format")]
         Fmt,
     }
@@ -585,6 +1023,12 @@ pub mod parse {
         }
     }
 
+    impl From<Vec<ChannelsErrorT>> for ChannelsError {
+        fn from(v: Vec<ChannelsErrorT>) -> Self {
+            Self::Multiple(VecError(v))
+        }
+    }
+
     impl From<Vec<LocationErrorT>> for LocationError {
         fn from(v: Vec<LocationErrorT>) -> Self {
             Self::Multiple(VecError(v))
@@ -646,8 +1090,39 @@ impl From<ctrlc::Error> for ReddishError {
     }
 }
 
+#[cfg(unix)]
+impl From<nix::Error> for ReddishError {
+    fn from(e: nix::Error) -> Self {
+        Self(ReddishErrorKind::Signal(e))
+    }
+}
+
 impl From<std::sync::mpsc::RecvTimeoutError> for ReddishError {
     fn from(e: std::sync::mpsc::RecvTimeoutError) -> Self {
         Self(ReddishErrorKind::Mpsc(e))
     }
 }
+
+impl From<serde_json::Error> for ReddishError {
+    fn from(e: serde_json::Error) -> Self {
+        Self(ReddishErrorKind::Json(e))
+    }
+}
+
+impl From<SnapshotError> for ReddishError {
+    fn from(e: SnapshotError) -> Self {
+        Self(ReddishErrorKind::Snapshot(e))
+    }
+}
+
+impl From<ExportError> for ReddishError {
+    fn from(e: ExportError) -> Self {
+        Self(ReddishErrorKind::Export(e))
+    }
+}
+
+impl From<RecordingError> for ReddishError {
+    fn from(e: RecordingError) -> Self {
+        Self(ReddishErrorKind::Recording(e))
+    }
+}
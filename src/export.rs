@@ -0,0 +1,243 @@
+/*  export.rs -- Export gamma ramps to files
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    calc_colorramp::GammaRampsFloat,
+    config::{Config, PKG_NAME},
+    error::ExportError,
+    types::{ColorSettings, ExportFormat},
+};
+use chrono::{Datelike, Timelike, Utc};
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+use tracing::info;
+
+// D50 white point and the sRGB primaries adapted to D50, as used by the
+// profile connection space
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const COLORANTS: [[f64; 3]; 3] = [
+    [0.4361, 0.2225, 0.0139],
+    [0.3851, 0.7169, 0.0971],
+    [0.1431, 0.0606, 0.7141],
+];
+// u8Fixed8Number of the sRGB approximate gamma 2.2
+const TRC_GAMMA: u16 = 0x0233;
+
+pub fn run_export_mode(
+    c: &Config,
+    format: Option<ExportFormat>,
+    size: u16,
+    output: Option<&Path>,
+) -> Result<(), ExportError> {
+    let format = format
+        .or_else(|| {
+            let ext = output?.extension()?.to_str()?;
+            ext.parse().ok()
+        })
+        .unwrap_or(ExportFormat::Csv);
+
+    // for this command, color settings are stored in the day field
+    let mut ramps = GammaRampsFloat::new(size as u32);
+    ramps.colorramp_fill(&c.day);
+    let bytes = match format {
+        ExportFormat::Csv => to_csv(&ramps).into_bytes(),
+        ExportFormat::Cube => to_cube(&ramps).into_bytes(),
+        ExportFormat::Icc => to_icc(&ramps, &description(&c.day)),
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(path, bytes)
+                .map_err(|e| ExportError::WriteFailed(e, path.into()))?;
+            info!("Exported the gamma ramps to {}", path.display());
+        }
+        None => io::stdout()
+            .lock()
+            .write_all(&bytes)
+            .map_err(ExportError::StdoutFailed)?,
+    }
+    Ok(())
+}
+
+fn description(cs: &ColorSettings) -> String {
+    format!("{PKG_NAME} {}K", cs.temp)
+}
+
+/// Values of an entry of all channels, clamped between 0 and 1
+fn rows(ramps: &GammaRampsFloat) -> impl Iterator<Item = [f64; 3]> + '_ {
+    (0..ramps[0].len()).map(|i| [0, 1, 2].map(|c| ramps[c][i].clamp(0.0, 1.0)))
+}
+
+/// Can be read back as a curve
+fn to_csv(ramps: &GammaRampsFloat) -> String {
+    let mut s = String::from("red,green,blue\n");
+    for [r, g, b] in rows(ramps) {
+        let _ = writeln!(s, "{r:.6},{g:.6},{b:.6}");
+    }
+    s
+}
+
+fn to_cube(ramps: &GammaRampsFloat) -> String {
+    let mut s =
+        format!("TITLE \"{PKG_NAME}\"\nLUT_1D_SIZE {}\n", ramps[0].len());
+    for [r, g, b] in rows(ramps) {
+        let _ = writeln!(s, "{r:.6} {g:.6} {b:.6}");
+    }
+    s
+}
+
+/// An ICC v2 display profile of sRGB with the ramps in a vcgt tag. Color
+/// management tools load the vcgt tag into the video card, which has the
+/// same effect as the set command
+fn to_icc(ramps: &GammaRampsFloat, desc: &str) -> Vec<u8> {
+    let text = |s: &str| tag_data(b"text", s.bytes().chain([0]));
+    let xyz =
+        |v: [f64; 3]| tag_data(b"XYZ ", v.into_iter().flat_map(s15_fixed16));
+    let curv = tag_data(
+        b"curv",
+        1u32.to_be_bytes()
+            .into_iter()
+            .chain(TRC_GAMMA.to_be_bytes()),
+    );
+
+    let tags: [(&[u8; 4], Vec<u8>); 10] = [
+        (b"desc", desc_tag(desc)),
+        (b"cprt", text("No copyright, use freely")),
+        (b"wtpt", xyz(D50)),
+        (b"rXYZ", xyz(COLORANTS[0])),
+        (b"gXYZ", xyz(COLORANTS[1])),
+        (b"bXYZ", xyz(COLORANTS[2])),
+        (b"rTRC", curv.clone()),
+        (b"gTRC", curv.clone()),
+        (b"bTRC", curv),
+        (b"vcgt", vcgt_tag(ramps)),
+    ];
+
+    // tag data starts after the header and the tag table, aligned to 4 bytes
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let start = 128 + 4 + 12 * tags.len();
+    for (sig, tag) in &tags {
+        table.extend(*sig);
+        table.extend(((start + data.len()) as u32).to_be_bytes());
+        table.extend((tag.len() as u32).to_be_bytes());
+        data.extend(tag);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let header = icc_header(start + data.len());
+    [header, table, data].concat()
+}
+
+/// Header of a display profile of the given size, created now
+fn icc_header(size: usize) -> Vec<u8> {
+    let now = Utc::now();
+    let date = [
+        now.year() as u16,
+        now.month() as u16,
+        now.day() as u16,
+        now.hour() as u16,
+        now.minute() as u16,
+        now.second() as u16,
+    ];
+    let mut header = Vec::with_capacity(128);
+    header.extend((size as u32).to_be_bytes());
+    header.extend([0; 4]); // preferred cmm
+    header.extend(0x0240_0000u32.to_be_bytes()); // version 2.4
+    header.extend(b"mntrRGB XYZ ");
+    header.extend(date.iter().flat_map(|v| v.to_be_bytes()));
+    header.extend(b"acsp");
+    // platform, flags, manufacturer, model, attributes and intent
+    header.extend([0; 4 + 4 + 4 + 4 + 8 + 4]);
+    header.extend(D50.iter().flat_map(|&v| s15_fixed16(v)));
+    header.resize(128, 0);
+    header
+}
+
+/// textDescriptionType with empty unicode and scriptcode descriptions
+fn desc_tag(desc: &str) -> Vec<u8> {
+    let mut tag = tag_data(b"desc", (desc.len() as u32 + 1).to_be_bytes());
+    tag.extend(desc.as_bytes());
+    tag.extend([0; 1 + 8 + 3 + 67]);
+    tag
+}
+
+/// A table of 16-bit entries for each channel
+fn vcgt_tag(ramps: &GammaRampsFloat) -> Vec<u8> {
+    let n = ramps[0].len() as u16;
+    let mut tag = tag_data(b"vcgt", [0; 4]);
+    for v in [3, n, 2] {
+        tag.extend(v.to_be_bytes());
+    }
+    for c in 0..3 {
+        for v in &ramps[c] {
+            let v = (v.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16;
+            tag.extend(v.to_be_bytes());
+        }
+    }
+    tag
+}
+
+/// Type signature and reserved bytes followed by the content of a tag
+fn tag_data(sig: &[u8; 4], content: impl IntoIterator<Item = u8>) -> Vec<u8> {
+    sig.iter().copied().chain([0; 4]).chain(content).collect()
+}
+
+fn s15_fixed16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn export_icc() {
+        let mut ramps = GammaRampsFloat::new(4);
+        ramps.colorramp_fill(&ColorSettings::default());
+        let icc = to_icc(&ramps, "test");
+        let u32_at = |i: usize| {
+            u32::from_be_bytes([icc[i], icc[i + 1], icc[i + 2], icc[i + 3]])
+        };
+        assert_eq!(u32_at(0) as usize, icc.len());
+        assert_eq!(&icc[36..40], b"acsp");
+        assert_eq!(u32_at(68), 0xF6D6);
+        assert_eq!(u32_at(128), 10);
+
+        // the vcgt tag is the last one
+        let entry = 128 + 4 + 12 * 9;
+        assert_eq!(&icc[entry..entry + 4], b"vcgt");
+        let offset = u32_at(entry + 4) as usize;
+        let vcgt = &icc[offset..offset + u32_at(entry + 8) as usize];
+        assert_eq!(vcgt.len(), 18 + 3 * 4 * 2);
+        assert_eq!(&vcgt[12..18], [0, 3, 0, 4, 0, 2]);
+        assert_eq!(&vcgt[18..20], [0, 0]);
+        assert_eq!(&vcgt[24..26], [0xFF, 0xFF]);
+
+        let cube = to_cube(&ramps);
+        assert!(cube.contains("LUT_1D_SIZE 4\n"));
+        assert_eq!(
+            to_csv(&ramps).lines().nth(4),
+            Some("1.000000,1.000000,1.000000")
+        );
+    }
+}
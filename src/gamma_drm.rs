@@ -20,28 +20,45 @@
 */
 
 use crate::{
-    calc_colorramp::GammaRamps,
+    calc_colorramp::{GammaRamps, FULL_DEPTH},
     error::{
         gamma::{CrtcError, DrmCrtcError, DrmError},
-        AdjusterError, AdjusterErrorInner,
+        AdjusterError, AdjusterErrorInner, VecError,
     },
-    types::ColorSettings,
+    gamma_monitor::RampsState,
+    gamma_output::{has_outputs, resolve_crtcs, Edid, Output},
+    list::CrtcInfo,
+    snapshot::ramps_of,
+    types::{ColorMatrix, ColorSettings, CrtcSelector, RampsStatus},
+    types_display::WARN,
     utils::CollectResult,
     Adjuster,
 };
 use drm::{
     control::{
-        crtc::Handle as CrtcHandle, from_u32 as handle_from_u32,
-        Device as ControlDevice,
+        atomic::AtomicModeReq,
+        connector::{Handle as ConnectorHandle, State as ConnectorState},
+        crtc::Handle as CrtcHandle,
+        from_u32 as handle_from_u32,
+        property::{Handle as PropertyHandle, Value as PropertyValue},
+        AtomicCommitFlags, Device as ControlDevice,
     },
-    Device,
+    ClientCapability, Device,
 };
+use itertools::Itertools;
 use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
     fs::{File, OpenOptions},
     io,
     os::fd::{AsFd, BorrowedFd},
     path::Path,
+    time::{Duration, Instant},
 };
+use tracing::{info, warn};
+
+// Minimum time between checks for output changes
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 struct Card(File);
@@ -49,14 +66,40 @@ struct Card(File);
 #[derive(Debug)]
 pub struct Drm {
     card: Card,
-    crtcs: Vec<Crtc>,
+    selectors: Vec<CrtcSelector>,
+    atomic: bool,
+    // Changes when outputs are plugged or unplugged
+    crtcs: RefCell<Vec<Crtc>>,
+    last_refresh: Cell<Instant>,
+    color_matrix: Option<ColorMatrix>,
 }
 
 #[derive(Debug)]
 struct Crtc {
     handle: CrtcHandle,
     ramp_size: u32,
-    saved_ramps: GammaRamps,
+    ramps: RampsState,
+    // None if the legacy gamma ioctls are used
+    atomic: Option<AtomicProps>,
+}
+
+/// Color management properties of a crtc that are set with atomic commits.
+/// Unlike the legacy gamma ramps, GAMMA_LUT is usually larger (1024 to 4096
+/// entries) and the CTM allows transformations that mix the channels
+#[derive(Debug)]
+struct AtomicProps {
+    gamma_lut: PropertyHandle,
+    // True if GAMMA_LUT was not set at startup
+    gamma_lut_unset: bool,
+    degamma_lut: Option<BlobProp>,
+    ctm: Option<BlobProp>,
+}
+
+#[derive(Debug)]
+struct BlobProp {
+    handle: PropertyHandle,
+    // Blob data at startup. None if the property was not set
+    saved: Option<Vec<u8>>,
 }
 
 impl AsFd for Card {
@@ -87,63 +130,137 @@ impl Card {
 impl Drm {
     pub fn new(
         card_num: Option<usize>,
-        crtc_ids: Vec<u32>,
+        selectors: Vec<CrtcSelector>,
+        color_matrix: Option<ColorMatrix>,
     ) -> Result<Self, DrmError> {
         let path = format!("/dev/dri/card{}", card_num.unwrap_or_default());
         let card = Card::open(path)?;
-        let crtcs = Self::get_crtcs(&card, crtc_ids)?;
-        Ok(Self { card, crtcs })
+        // Drivers without atomic modesetting only support the legacy ioctls
+        let atomic = card
+            .set_client_capability(ClientCapability::Atomic, true)
+            .is_ok();
+        if selectors.contains(&CrtcSelector::Id(0)) {
+            Err(DrmError::ZeroValueCrtc)?
+        }
+        let handles = Self::resolve(&card, &selectors, false)?;
+        let crtcs = Self::get_crtcs(&card, handles, atomic)
+            .map_err(DrmError::GetCrtcs)?;
+
+        if color_matrix.is_some() {
+            crtcs.iter().filter(|c| !c.has_ctm()).for_each(|c| {
+                let id: u32 = c.handle.into();
+                let s = "color matrix is not supported on crtc";
+                warn!("{WARN}warning:{WARN:#} {s} {id}");
+            });
+        }
+
+        Ok(Self {
+            card,
+            selectors,
+            atomic,
+            crtcs: RefCell::new(crtcs),
+            last_refresh: Cell::new(Instant::now()),
+            color_matrix,
+        })
     }
 
-    fn get_crtcs(
+    fn resolve(
         card: &Card,
-        mut crtc_ids: Vec<u32>,
-    ) -> Result<Vec<Crtc>, DrmError> {
-        let all_crtcs = card
+        selectors: &[CrtcSelector],
+        skip_missing: bool,
+    ) -> Result<Vec<CrtcHandle>, DrmError> {
+        let res = card
             .resource_handles()
-            .map_err(DrmError::GetResourcesFailed)?
-            .crtcs;
-
-        let crtcs = if crtc_ids.is_empty() {
-            all_crtcs
+            .map_err(DrmError::GetResourcesFailed)?;
+        let all_crtcs = res.crtcs.iter().map(|&h| h.into()).collect_vec();
+        let outputs = if has_outputs(selectors) {
+            Self::get_outputs(card, &res.connectors)
+                .map_err(DrmError::GetConnectorsFailed)?
         } else {
-            let len = crtc_ids.len();
-            crtc_ids.sort();
-            crtc_ids.dedup();
-            if len > crtc_ids.len() {
-                Err(DrmError::NonUniqueCrtc)?
-            }
-            let f = |h| Self::validate_crtc(&all_crtcs, h);
-            crtc_ids.into_iter().map(f).collect::<Result<Vec<_>, _>>()?
+            Vec::new()
         };
 
+        Ok(
+            resolve_crtcs(selectors, &all_crtcs, &outputs, skip_missing)?
+                .into_iter()
+                .filter_map(handle_from_u32)
+                .collect(),
+        )
+    }
+
+    fn get_outputs(
+        card: &Card,
+        connectors: &[ConnectorHandle],
+    ) -> io::Result<Vec<Output>> {
+        connectors
+            .iter()
+            .map(|&handle| {
+                let info = card.get_connector(handle, false)?;
+                let name = info.interface().as_str();
+                let name = format!("{name}-{}", info.interface_id());
+                let crtc = match info.current_encoder() {
+                    Some(e) => card.get_encoder(e)?.crtc().map(Into::into),
+                    None => None,
+                };
+
+                let mut edid = None;
+                for (&prop, &value) in &card.get_properties(handle)? {
+                    if card.get_property(prop)?.name().to_bytes() == b"EDID" {
+                        edid = get_blob(card, value)?
+                            .and_then(|data| Edid::parse(&data));
+                    }
+                }
+
+                Ok(Output {
+                    name,
+                    connected: info.state() == ConnectorState::Connected,
+                    crtc,
+                    edid,
+                })
+            })
+            .collect()
+    }
+
+    pub fn crtcs_info(&self) -> Vec<CrtcInfo> {
+        let crtcs = self.crtcs.borrow();
         crtcs
+            .iter()
+            .map(|c| CrtcInfo::new(c.handle.into(), &c.ramps.saved()))
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Result<Vec<Output>, DrmError> {
+        let res = self
+            .card
+            .resource_handles()
+            .map_err(DrmError::GetResourcesFailed)?;
+        Self::get_outputs(&self.card, &res.connectors)
+            .map_err(DrmError::GetConnectorsFailed)
+    }
+
+    fn get_crtcs(
+        card: &Card,
+        handles: Vec<CrtcHandle>,
+        atomic: bool,
+    ) -> Result<Vec<Crtc>, VecError<CrtcError<u32, DrmCrtcError>>> {
+        handles
             .into_iter()
-            .map(|h| Self::get_crtc(card, h))
+            .map(|h| Self::get_crtc(card, h, atomic))
             .collect_result()
-            .map_err(DrmError::GetCrtcs)
-    }
-
-    fn validate_crtc(
-        all_crtcs: &[CrtcHandle],
-        id: u32,
-    ) -> Result<CrtcHandle, DrmError> {
-        let crtcs =
-            || all_crtcs.iter().map(|&h| h.into()).collect::<Vec<u32>>();
-        let handle: CrtcHandle =
-            handle_from_u32(id).ok_or(DrmError::ZeroValueCrtc)?;
-        if all_crtcs.iter().any(|&h| handle == h) {
-            Ok(handle)
-        } else {
-            Err(DrmError::InvalidCrtc(crtcs()))
-        }
     }
 
     fn get_crtc(
         card: &Card,
         handle: CrtcHandle,
+        atomic: bool,
     ) -> Result<Crtc, CrtcError<u32, DrmCrtcError>> {
         let f = || -> Result<Crtc, DrmCrtcError> {
+            if atomic {
+                if let Some(crtc) = Self::get_atomic_crtc(card, handle)? {
+                    return Ok(crtc);
+                }
+            }
+
             let info = card
                 .get_crtc(handle)
                 .map_err(DrmCrtcError::GetRampSizeFailed)?;
@@ -152,36 +269,22 @@ impl Drm {
                 Err(DrmCrtcError::InvalidRampSize(ramp_size))?
             }
 
-            let (mut r, mut g, mut b) = (Vec::new(), Vec::new(), Vec::new());
-            // FIX: Error: Bad address (os error 14)
-            // drm_ffi::mode::get_gamma(
-            //     card.as_fd(),
-            //     handle.into(),
-            //     ramp_size as usize,
-            //     &mut r,
-            //     &mut g,
-            //     &mut b,
-            // )?;
-            //
-            // The C function drmModeCrtcGetGamma works on my system
-            // Test here: https://github.com/mahor1221/redshift
-            // build and run: ./redshift -m drm:card=<your card> -x
-            //
-            // everything is similar to the C function, why it doesn't work
-            // https://gitlab.freedesktop.org/mesa/drm/-/blob/main/xf86drmMode.c#L1000
-            // https://gitlab.freedesktop.org/mesa/drm/-/blob/main/include/drm/drm.h#L1155
-            //
-            // FIX: Error: Invalid argument (os error 22)
+            // The buffers must be large enough to hold the whole ramps,
+            // otherwise the ioctl fails with: Invalid argument (os error 22)
+            let size = ramp_size as usize;
+            let (mut r, mut g, mut b) =
+                (vec![0; size], vec![0; size], vec![0; size]);
             card.get_gamma(handle, &mut r, &mut g, &mut b)
                 .map_err(DrmCrtcError::GetRampFailed)?;
-            let saved_ramps = GammaRamps([r, g, b]);
+            let ramps = RampsState::new(GammaRamps([r, g, b]));
             // _("DRM could not read gamma ramps on CRTC %i on\n"
             // "graphics card %i, ignoring device.\n"),
 
             Ok(Crtc {
                 handle,
                 ramp_size,
-                saved_ramps,
+                ramps,
+                atomic: None,
             })
         };
 
@@ -191,26 +294,244 @@ impl Drm {
         })
     }
 
+    /// Returns None if the crtc does not have the GAMMA_LUT property
+    fn get_atomic_crtc(
+        card: &Card,
+        handle: CrtcHandle,
+    ) -> Result<Option<Crtc>, DrmCrtcError> {
+        let props = Self::get_crtc_props(card, handle)?;
+        let blob = |name: &[u8]| -> Result<_, DrmCrtcError> {
+            let Some(&(handle, value)) = props.get(name) else {
+                return Ok(None);
+            };
+            let saved =
+                get_blob(card, value).map_err(DrmCrtcError::GetRampFailed)?;
+            Ok(Some(BlobProp { handle, saved }))
+        };
+
+        let (Some(&(gamma_lut, value)), Some(&(_, ramp_size))) = (
+            props.get(&b"GAMMA_LUT"[..]),
+            props.get(&b"GAMMA_LUT_SIZE"[..]),
+        ) else {
+            return Ok(None);
+        };
+        let ramp_size = ramp_size as u32;
+        if ramp_size <= 1 {
+            Err(DrmCrtcError::InvalidRampSize(ramp_size))?
+        }
+        let (degamma_lut, ctm) = (blob(b"DEGAMMA_LUT")?, blob(b"CTM")?);
+
+        // An unset GAMMA_LUT passes the colors through unchanged
+        let saved = get_blob(card, value)
+            .map_err(DrmCrtcError::GetRampFailed)?
+            .and_then(|data| lut_to_ramps(&data, ramp_size));
+        let gamma_lut_unset = saved.is_none();
+        let saved = saved.unwrap_or_else(|| GammaRamps::new(ramp_size));
+
+        Ok(Some(Crtc {
+            handle,
+            ramp_size,
+            ramps: RampsState::new(saved),
+            atomic: Some(AtomicProps {
+                gamma_lut,
+                gamma_lut_unset,
+                degamma_lut,
+                ctm,
+            }),
+        }))
+    }
+
+    /// Handles and values of the properties of a crtc by their name
+    fn get_crtc_props(
+        card: &Card,
+        handle: CrtcHandle,
+    ) -> Result<HashMap<Vec<u8>, (PropertyHandle, u64)>, DrmCrtcError> {
+        let props = card
+            .get_properties(handle)
+            .map_err(DrmCrtcError::GetPropertiesFailed)?;
+        let mut named = HashMap::new();
+        for (&prop, &value) in &props {
+            let info = card
+                .get_property(prop)
+                .map_err(DrmCrtcError::GetPropertiesFailed)?;
+            named.insert(info.name().to_bytes().to_vec(), (prop, value));
+        }
+        Ok(named)
+    }
+
+    /// Select the crtcs again if outputs were plugged or unplugged
+    fn refresh(&self) {
+        // DRM has no events for output changes and reading the connectors
+        // and their EDIDs on every step of a fade is too slow
+        let now = Instant::now();
+        if !has_outputs(&self.selectors)
+            || now.duration_since(self.last_refresh.get()) < REFRESH_INTERVAL
+        {
+            return;
+        }
+        self.last_refresh.set(now);
+        let handles = match Self::resolve(&self.card, &self.selectors, true) {
+            Ok(handles) => handles,
+            Err(e) => {
+                let s = "unable to select crtcs after outputs changed";
+                warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+                return;
+            }
+        };
+        self.update_crtcs(&handles);
+    }
+
+    /// Crtcs that are no longer selected are restored
+    fn update_crtcs(&self, handles: &[CrtcHandle]) {
+        let mut crtcs = self.crtcs.borrow_mut();
+        if crtcs.iter().map(|c| c.handle).eq(handles.iter().copied()) {
+            return;
+        }
+        for crtc in crtcs.iter().filter(|c| !handles.contains(&c.handle)) {
+            let _ = self.restore_crtc(crtc);
+        }
+
+        let new = handles
+            .iter()
+            .filter(|&h| !crtcs.iter().any(|c| c.handle == *h))
+            .copied()
+            .collect();
+        match Self::get_crtcs(&self.card, new, self.atomic) {
+            Ok(new) => {
+                let mut old = std::mem::take(&mut *crtcs);
+                old.retain(|c| handles.contains(&c.handle));
+                old.extend(new);
+                old.sort_by_key(|c| {
+                    handles.iter().position(|&h| h == c.handle)
+                });
+                *crtcs = old;
+                let ids = handles.iter().map(|&h| u32::from(h)).collect_vec();
+                info!("Outputs changed, using crtcs {ids:?}");
+            }
+            Err(e) => {
+                let s = "unable to get crtcs after outputs changed";
+                warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+            }
+        }
+    }
+
     fn set_gamma_ramps(
         &self,
         f: impl Fn(&Crtc) -> io::Result<()>,
     ) -> Result<(), AdjusterErrorInner> {
-        self.crtcs.iter().map(f).collect_result()?;
+        self.crtcs.borrow().iter().map(f).collect_result()?;
         Ok(())
     }
+
+    fn get_gamma_ramps(&self) -> Result<Vec<GammaRamps>, AdjusterErrorInner> {
+        Ok(self
+            .crtcs
+            .borrow()
+            .iter()
+            .map(|crtc| {
+                if let Some(props) = &crtc.atomic {
+                    let value = self
+                        .card
+                        .get_properties(crtc.handle)?
+                        .iter()
+                        .find(|(&h, _)| h == props.gamma_lut)
+                        .map_or(0, |(_, &v)| v);
+                    let ramps = get_blob(&self.card, value)?
+                        .and_then(|d| lut_to_ramps(&d, crtc.ramp_size))
+                        .unwrap_or_else(|| GammaRamps::new(crtc.ramp_size));
+                    return Ok(ramps);
+                }
+
+                let size = crtc.ramp_size as usize;
+                let (mut r, mut g, mut b) =
+                    (vec![0; size], vec![0; size], vec![0; size]);
+                self.card.get_gamma(crtc.handle, &mut r, &mut g, &mut b)?;
+                Ok(GammaRamps([r, g, b]))
+            })
+            .collect_result()?)
+    }
+
+    /// Set the color management properties of a crtc in a single atomic
+    /// commit. Properties that are None are left unchanged. GAMMA_LUT is
+    /// unset if ramps is None
+    fn commit(
+        &self,
+        crtc: &Crtc,
+        props: &AtomicProps,
+        ramps: Option<&GammaRamps>,
+        degamma_lut: Option<Option<&[u8]>>,
+        ctm: Option<Option<&[u8]>>,
+    ) -> io::Result<()> {
+        let lut = ramps.map(ramps_to_lut);
+        let mut values = vec![(props.gamma_lut, lut.as_deref())];
+        if let (Some(p), Some(data)) = (&props.degamma_lut, degamma_lut) {
+            values.push((p.handle, data));
+        }
+        if let (Some(p), Some(data)) = (&props.ctm, ctm) {
+            values.push((p.handle, data));
+        }
+
+        let mut req = AtomicModeReq::new();
+        let mut blobs = Vec::new();
+        let r = (|| {
+            for (prop, data) in values {
+                let id = match data {
+                    Some(data) => {
+                        let id = create_blob(&self.card, data)?;
+                        blobs.push(id);
+                        id
+                    }
+                    None => 0,
+                };
+                req.add_property(crtc.handle, prop, PropertyValue::Blob(id));
+            }
+            self.card.atomic_commit(AtomicCommitFlags::empty(), req)
+        })();
+
+        // The committed state holds its own references to the blobs
+        for id in blobs {
+            let _ = self.card.destroy_property_blob(id);
+        }
+        r
+    }
+
+    fn restore_crtc(&self, crtc: &Crtc) -> io::Result<()> {
+        let saved_ramps = crtc.ramps.saved();
+        if let Some(props) = &crtc.atomic {
+            fn saved(p: &Option<BlobProp>) -> Option<Option<&[u8]>> {
+                p.as_ref().map(|p| p.saved.as_deref())
+            }
+            // Unless foreign ramps were adopted in the meantime
+            let unset = props.gamma_lut_unset
+                && saved_ramps.0 == GammaRamps::new(crtc.ramp_size).0;
+            return self.commit(
+                crtc,
+                props,
+                (!unset).then_some(&*saved_ramps),
+                saved(&props.degamma_lut),
+                saved(&props.ctm),
+            );
+        }
+
+        self.card.set_gamma(
+            crtc.handle,
+            &saved_ramps[0],
+            &saved_ramps[1],
+            &saved_ramps[2],
+        )
+    }
+}
+
+impl Crtc {
+    fn has_ctm(&self) -> bool {
+        self.atomic.as_ref().is_some_and(|p| p.ctm.is_some())
+    }
 }
 
 impl Adjuster for Drm {
     fn restore(&self) -> Result<(), AdjusterError> {
-        self.set_gamma_ramps(|crtc| {
-            self.card.set_gamma(
-                crtc.handle,
-                &crtc.saved_ramps[0],
-                &crtc.saved_ramps[1],
-                &crtc.saved_ramps[2],
-            )
-        })
-        .map_err(AdjusterError::Restore)
+        self.set_gamma_ramps(|crtc| self.restore_crtc(crtc))
+            .map_err(AdjusterError::Restore)
     }
 
     fn set(
@@ -218,17 +539,226 @@ impl Adjuster for Drm {
         reset_ramps: bool,
         cs: &ColorSettings,
     ) -> Result<(), AdjusterError> {
+        self.refresh();
         self.set_gamma_ramps(|crtc| {
-            let mut ramps = if reset_ramps {
-                GammaRamps::new(crtc.ramp_size)
-            } else {
-                crtc.saved_ramps.clone()
-            };
-
+            let mut ramps = crtc.ramps.base(reset_ramps, crtc.ramp_size);
             ramps.colorramp_fill(cs);
-            self.card
-                .set_gamma(crtc.handle, &ramps[0], &ramps[1], &ramps[2])
+            // The bit depth of GAMMA_LUT is not reported and the kernel
+            // rounds the entries to the precision of the hardware itself
+            let ramps = ramps.to_ramps(crtc.ramp_size, FULL_DEPTH);
+            match &crtc.atomic {
+                Some(props) => {
+                    // DEGAMMA_LUT belongs to the compositor or the previous
+                    // owner of the crtc and is left as it is
+                    let ctm = self.color_matrix.as_ref().map(ctm_to_blob);
+                    let ctm = ctm.as_ref().map(|c| Some(c.as_slice()));
+                    self.commit(crtc, props, Some(&ramps), None, ctm)?;
+                }
+                None => self.card.set_gamma(
+                    crtc.handle,
+                    &ramps[0],
+                    &ramps[1],
+                    &ramps[2],
+                )?,
+            }
+            crtc.ramps.written(ramps);
+            Ok(())
         })
         .map_err(AdjusterError::Set)
     }
+
+    fn verify(
+        &self,
+        adopt_foreign: bool,
+    ) -> Result<Vec<(u32, RampsStatus)>, AdjusterError> {
+        let current = self.get_gamma_ramps().map_err(AdjusterError::Get)?;
+        Ok(self
+            .crtcs
+            .borrow()
+            .iter()
+            .zip(current)
+            .map(|(crtc, r)| {
+                let status = crtc.ramps.check(r, adopt_foreign);
+                (crtc.handle.into(), status)
+            })
+            .collect())
+    }
+
+    fn saved_ramps(&self) -> Vec<(u32, GammaRamps)> {
+        let crtcs = self.crtcs.borrow();
+        crtcs
+            .iter()
+            .map(|c| (c.handle.into(), c.ramps.saved().clone()))
+            .collect()
+    }
+
+    fn set_ramps(
+        &self,
+        ramps: &[(u32, GammaRamps)],
+    ) -> Result<(), AdjusterError> {
+        self.set_gamma_ramps(|crtc| {
+            let ramps =
+                ramps_of(ramps, crtc.handle.into(), &crtc.ramps.saved());
+            match &crtc.atomic {
+                // Only the gamma stage is part of the ramps
+                Some(props) => {
+                    self.commit(crtc, props, Some(&ramps), None, None)
+                }
+                None => self.card.set_gamma(
+                    crtc.handle,
+                    &ramps[0],
+                    &ramps[1],
+                    &ramps[2],
+                ),
+            }
+        })
+        .map_err(AdjusterError::Set)
+    }
+
+    fn set_base(&self, ramps: &[(u32, GammaRamps)]) {
+        for crtc in self.crtcs.borrow().iter() {
+            crtc.ramps.rebase(ramps, crtc.handle.into());
+        }
+    }
+}
+
+fn get_blob(card: &Card, id: u64) -> io::Result<Option<Vec<u8>>> {
+    match id {
+        0 => Ok(None),
+        id => card.get_property_blob(id).map(Some),
+    }
+}
+
+fn create_blob(card: &Card, data: &[u8]) -> io::Result<u64> {
+    let mut data = data.to_vec();
+    let blob = drm_ffi::mode::create_property_blob(card.as_fd(), &mut data)?;
+    Ok(blob.blob_id.into())
+}
+
+// Layout of struct drm_color_lut: red, green, blue and a reserved u16
+fn ramps_to_lut(ramps: &GammaRamps) -> Vec<u8> {
+    let [r, g, b] = &ramps.0;
+    r.iter()
+        .zip(g)
+        .zip(b)
+        .flat_map(|((r, g), b)| [*r, *g, *b, 0])
+        .flat_map(u16::to_ne_bytes)
+        .collect()
+}
+
+fn lut_to_ramps(data: &[u8], ramp_size: u32) -> Option<GammaRamps> {
+    if data.len() != ramp_size as usize * 8 {
+        return None;
+    }
+    let mut ramps = GammaRamps([Vec::new(), Vec::new(), Vec::new()]);
+    for entry in data.chunks_exact(8) {
+        for (c, ramp) in ramps.0.iter_mut().enumerate() {
+            ramp.push(u16::from_ne_bytes([entry[2 * c], entry[2 * c + 1]]));
+        }
+    }
+    Some(ramps)
+}
+
+// Layout of struct drm_color_ctm: nine S31.32 sign-magnitude fixed point
+// numbers in row-major order
+fn ctm_to_blob(m: &ColorMatrix) -> Vec<u8> {
+    m.iter()
+        .map(|&v| {
+            let magnitude =
+                (v.abs() * (1u64 << 32) as f64) as u64 & !(1 << 63);
+            let sign = if v.is_sign_negative() { 1 << 63 } else { 0 };
+            magnitude | sign
+        })
+        .flat_map(u64::to_ne_bytes)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::calc_colorramp::GammaRampsFloat;
+    use crate::types::{Brightness, Temperature};
+    use anyhow::Result;
+
+    // Requires a card with atomic color management and permission to
+    // modeset, e.g. with the vkms module loaded on a virtual terminal:
+    //   REDDISH_SHIFT_DRM_CARD=1 cargo test -- --ignored drm_atomic
+    #[test]
+    #[ignore]
+    fn drm_atomic_vkms() -> Result<()> {
+        let card = std::env::var("REDDISH_SHIFT_DRM_CARD")?.parse()?;
+        let m = ColorMatrix::saturation(0.5);
+        let drm = Drm::new(Some(card), Vec::new(), Some(m))?;
+        assert!(drm.crtcs.borrow().iter().all(|c| c.atomic.is_some()));
+
+        let cs = ColorSettings {
+            temp: Temperature::try_from(3000)?,
+            brght: Brightness::try_from(0.8)?,
+            ..Default::default()
+        };
+        drm.set(true, &cs)?;
+        let status = drm.verify(false)?;
+        assert!(status.iter().all(|(_, s)| *s == RampsStatus::Intact));
+        drm.restore()?;
+        Ok(())
+    }
+
+    #[test]
+    fn drm_lut() {
+        let ramps = GammaRamps([
+            vec![0, 0x1234, 0xFFFF],
+            vec![1, 2, 3],
+            vec![0xFFFF, 0x8000, 0],
+        ]);
+        let lut = ramps_to_lut(&ramps);
+        assert_eq!(lut.len(), 3 * 8);
+        // the reserved field of each entry is zero
+        assert_eq!(lut[6..8], [0, 0]);
+        let back = lut_to_ramps(&lut, 3).unwrap_or_else(|| GammaRamps::new(3));
+        assert_eq!(back.0, ramps.0);
+        assert!(lut_to_ramps(&lut, 4).is_none());
+    }
+
+    #[test]
+    fn drm_ctm() {
+        let values = |m: [f64; 9]| {
+            let blob = ctm_to_blob(&ColorMatrix::new(m));
+            let chunks = blob.chunks_exact(8);
+            let values = chunks
+                .map(|c| u64::from_ne_bytes(c.try_into().unwrap_or_default()));
+            values.collect::<Vec<_>>()
+        };
+
+        let one = 1 << 32;
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let expected = [one, 0, 0, 0, one, 0, 0, 0, one];
+        assert_eq!(values(identity), expected);
+
+        // sign-magnitude, not two's complement
+        let m = [-0.5, 0.25, 0.0, 0.0, 1.5, 0.0, 0.0, 0.0, -2.0];
+        let v = values(m);
+        assert_eq!(v[0], 1 << 63 | 1 << 31);
+        assert_eq!(v[1], 1 << 30);
+        assert_eq!(v[4], one + (1 << 31));
+        assert_eq!(v[8], 1 << 63 | 2 << 32);
+    }
+
+    #[test]
+    fn drm_lut_depth() -> Result<()> {
+        // a LUT with 256 entries may still be applied by a 10 or 12 bit
+        // pipeline, so its entries are not rounded to 8 bits
+        let cs = ColorSettings {
+            temp: Temperature::try_from(3500)?,
+            ..Default::default()
+        };
+        let mut ramps = GammaRampsFloat::new(256);
+        ramps.colorramp_fill(&cs);
+        let ramps = ramps.to_ramps(256, FULL_DEPTH);
+        let lut = ramps_to_lut(&ramps);
+        let back =
+            lut_to_ramps(&lut, 256).unwrap_or_else(|| GammaRamps::new(1));
+        assert_eq!(back.0, ramps.0);
+        assert!(back.0.iter().flatten().any(|v| v % 257 != 0));
+        Ok(())
+    }
 }
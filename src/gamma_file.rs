@@ -0,0 +1,414 @@
+/*  gamma_file.rs -- Emulated crtcs recorded to a file
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    calc_colorramp::{GammaRamps, FULL_DEPTH},
+    config::Config,
+    error::{AdjusterError, RecordingError, ReddishError},
+    gamma_monitor::RampsState,
+    snapshot::{ramps_of, CrtcRamps, Snapshot},
+    state,
+    types::ColorSettings,
+    types_display::{BODY, WARN},
+    Adjuster,
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+use tracing::{info, warn};
+
+const DEFAULT_RAMP_SIZE: u32 = 256;
+
+/// A line of a recording, e.g.:
+/// ```json
+/// {"time":"2024-07-17T21:00:00.000+02:00","event":"set","reset-ramps":false,
+///  "color-settings":{"temperature":4500,...},
+///  "crtcs":[{"id":0,"red":[0,...],"green":[0,...],"blue":[0,...]}]}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Record {
+    pub time: String,
+    pub event: Event,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reset_ramps: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_settings: Option<ColorSettings>,
+    pub crtcs: Vec<CrtcRamps>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Event {
+    Set,
+    Restore,
+    /// Ramps that were set directly, e.g. by snapshot restore
+    SetRamps,
+}
+
+/// Emulates crtcs and appends everything that is applied to them to a file
+/// in JSON lines format. The crtcs start with the ramps of the last record
+/// of the file, so that subsequent commands see the adjustments of the
+/// previous ones like on a real display. New crtcs start with linear ramps,
+/// a recording starts from other ramps by restoring a snapshot first
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    crtcs: Vec<(u32, u32, RampsState)>,
+}
+
+impl Recorder {
+    pub fn new(
+        path: PathBuf,
+        ramp_size: Option<u32>,
+        crtcs: Vec<u32>,
+    ) -> Result<Self, RecordingError> {
+        let recorded = match read(&path) {
+            Ok(records) => {
+                records.last().map(Record::ramps).unwrap_or_default()
+            }
+            Err(RecordingError::ReadFailed(e, _))
+                if e.kind() == ErrorKind::NotFound =>
+            {
+                Vec::new()
+            }
+            Err(e) => Err(e)?,
+        };
+
+        let ids = match (crtcs.is_empty(), recorded.is_empty()) {
+            (false, _) => crtcs,
+            (true, true) => vec![0],
+            (true, false) => recorded.iter().map(|(id, _)| *id).collect(),
+        };
+        let crtcs = ids
+            .into_iter()
+            .map(|id| {
+                let ramps = match recorded.iter().find(|(i, _)| *i == id) {
+                    Some((_, r)) => match ramp_size {
+                        Some(n) => r.resample(n),
+                        None => r.clone(),
+                    },
+                    None => {
+                        GammaRamps::new(ramp_size.unwrap_or(DEFAULT_RAMP_SIZE))
+                    }
+                };
+                (id, ramps[0].len() as u32, RampsState::new(ramps))
+            })
+            .collect();
+
+        Ok(Self { path, crtcs })
+    }
+
+    fn record(
+        &self,
+        event: Event,
+        reset_ramps: Option<bool>,
+        cs: Option<&ColorSettings>,
+        ramps: Vec<(u32, GammaRamps)>,
+    ) -> Result<(), RecordingError> {
+        let record = Record {
+            time: Local::now().to_rfc3339(),
+            event,
+            reset_ramps,
+            color_settings: cs.cloned(),
+            crtcs: Snapshot::new(String::new(), ramps).crtcs,
+        };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        let err = |e| RecordingError::WriteFailed(e, self.path.clone());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(err)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| f.write_all(line.as_bytes()))
+            .map_err(err)
+    }
+}
+
+impl Adjuster for Recorder {
+    fn restore(&self) -> Result<(), AdjusterError> {
+        let ramps = self.saved_ramps();
+        self.record(Event::Restore, None, None, ramps)
+            .map_err(|e| AdjusterError::Restore(e.into()))
+    }
+
+    fn set(
+        &self,
+        reset_ramps: bool,
+        cs: &ColorSettings,
+    ) -> Result<(), AdjusterError> {
+        let ramps = self
+            .crtcs
+            .iter()
+            .map(|(id, ramp_size, state)| {
+                let mut ramps = state.base(reset_ramps, *ramp_size);
+                ramps.colorramp_fill(cs);
+                (*id, ramps.to_ramps(*ramp_size, FULL_DEPTH))
+            })
+            .collect();
+        self.record(Event::Set, Some(reset_ramps), Some(cs), ramps)
+            .map_err(|e| AdjusterError::Set(e.into()))
+    }
+
+    fn saved_ramps(&self) -> Vec<(u32, GammaRamps)> {
+        let ramps = self.crtcs.iter();
+        ramps.map(|(id, _, s)| (*id, s.saved().clone())).collect()
+    }
+
+    fn set_ramps(
+        &self,
+        ramps: &[(u32, GammaRamps)],
+    ) -> Result<(), AdjusterError> {
+        let ramps = self
+            .crtcs
+            .iter()
+            .map(|(id, _, state)| (*id, ramps_of(ramps, *id, &state.saved())))
+            .collect();
+        self.record(Event::SetRamps, None, None, ramps)
+            .map_err(|e| AdjusterError::Set(e.into()))
+    }
+
+    fn set_base(&self, ramps: &[(u32, GammaRamps)]) {
+        for (id, _, state) in &self.crtcs {
+            state.rebase(ramps, *id);
+        }
+    }
+}
+
+impl Record {
+    /// Crtcs with ramps of different or zero sizes are ignored
+    fn ramps(&self) -> Vec<(u32, GammaRamps)> {
+        let crtcs = self.crtcs.clone();
+        Snapshot {
+            method: String::new(),
+            crtcs,
+        }
+        .ramps()
+    }
+}
+
+/// Records of a file in order. Empty lines are ignored
+pub fn read(path: &Path) -> Result<Vec<Record>, RecordingError> {
+    let s = fs::read_to_string(path)
+        .map_err(|e| RecordingError::ReadFailed(e, path.into()))?;
+    s.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            serde_json::from_str(l).map_err(|e| {
+                RecordingError::InvalidRecord(e, path.into(), i + 1)
+            })
+        })
+        .collect()
+}
+
+/// Apply the records of a file with the selected adjustment method. The time
+/// between the records is kept unless a delay is set. Recorded color
+/// settings are applied, so the ramps are calculated for the crtcs of the
+/// method. Recorded ramps of a single crtc are applied to all of the crtcs
+pub fn run_replay_mode(
+    c: &Config,
+    sig: &Receiver<()>,
+    path: &Path,
+    delay: Option<u64>,
+) -> Result<(), ReddishError> {
+    let records = read(path)?;
+    let time = |r: &Record| DateTime::parse_from_rfc3339(&r.time).ok();
+
+    let mut last: Option<&Record> = None;
+    let mut state = None;
+    for r in &records {
+        let wait = match (delay, last.and_then(time), time(r)) {
+            (Some(t), _, _) if last.is_some() => Duration::from_millis(t),
+            (None, Some(t0), Some(t1)) => {
+                (t1 - t0).to_std().unwrap_or_default()
+            }
+            _ => Duration::ZERO,
+        };
+        match sig.recv_timeout(wait) {
+            Ok(()) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+            // setting the signal handler is allowed to fail in this mode
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(wait),
+        }
+        last = Some(r);
+
+        state = Some(replay(c, r)?);
+    }
+
+    if records.is_empty() {
+        let s = format!("no records found in {}", path.display());
+        warn!("{WARN}warning:{WARN:#} {s}");
+    }
+    if let Some(cs) = state {
+        state::save(&c.state_key, cs.as_ref());
+    }
+    Ok(())
+}
+
+/// Apply a record and return the color settings it applied, if any
+fn replay(
+    c: &Config,
+    r: &Record,
+) -> Result<Option<ColorSettings>, ReddishError> {
+    info!("{BODY}{}{BODY:#}: {:?}", r.time, r.event);
+    match (r.event, &r.color_settings) {
+        (Event::Set, Some(cs)) => {
+            let reset_ramps = r.reset_ramps.unwrap_or(c.reset_ramps);
+            c.method.set(reset_ramps, cs)?;
+            Ok(Some(cs.clone()))
+        }
+        (Event::Restore, _) => {
+            c.method.restore()?;
+            Ok(None)
+        }
+        (Event::SetRamps | Event::Set, _) => {
+            let mut ramps = r.ramps();
+            if let [(_, single)] = ramps.as_slice() {
+                let ids = c.method.saved_ramps().into_iter();
+                let single = single.clone();
+                ramps = ids.map(|(id, _)| (id, single.clone())).collect();
+            }
+            c.method.set_ramps(&ramps)?;
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::SetValue;
+    use anyhow::Result;
+
+    #[test]
+    fn file_recording() -> Result<()> {
+        let path = std::env::temp_dir()
+            .join(format!("reddish-shift-test-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let cs = ColorSettings {
+            temp: 4500.try_into()?,
+            ..Default::default()
+        };
+        let m = Recorder::new(path.clone(), Some(16), vec![0, 1])?;
+        m.set(true, &cs)?;
+        m.restore()?;
+        m.set(true, &cs)?;
+
+        // the crtcs continue from the last record
+        let records = read(&path)?;
+        let m = Recorder::new(path.clone(), None, Vec::new())?;
+        fs::remove_file(&path)?;
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].event, Event::Restore);
+        assert_eq!(records[2].color_settings.as_ref(), Some(&cs));
+        let (saved, recorded) = (m.saved_ramps(), records[2].ramps());
+        assert_eq!(saved.len(), 2);
+        for ((id1, r1), (id2, r2)) in saved.iter().zip(&recorded) {
+            assert!(id1 == id2 && r1[0].len() == 16 && r1.0 == r2.0);
+        }
+        Ok(())
+    }
+
+    fn test_path(name: &str) -> PathBuf {
+        let name = format!("reddish-shift-test-{}-{name}", std::process::id());
+        let path = std::env::temp_dir().join(name).with_extension("jsonl");
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    // Apply color settings like the set command, which runs in a process of
+    // its own that continues from the ramps of the last record
+    fn set(
+        path: &Path,
+        last: Option<&ColorSettings>,
+        cs: &ColorSettings,
+    ) -> Result<()> {
+        let m = Recorder::new(path.into(), Some(16), vec![0])?;
+        if last.is_some() {
+            crate::rebase(&m, &[]);
+        }
+        Ok(m.set(false, cs)?)
+    }
+
+    fn last_ramps(path: &Path) -> Result<Vec<(u32, GammaRamps)>> {
+        let records = read(path)?;
+        fs::remove_file(path)?;
+        Ok(records.last().map(Record::ramps).unwrap_or_default())
+    }
+
+    #[test]
+    fn file_relative_set() -> Result<()> {
+        let (relative, absolute) =
+            (test_path("relative"), test_path("absolute"));
+        let warmer = |cs: &ColorSettings| ColorSettings {
+            temp: SetValue::Relative(-300.0).apply(cs.temp),
+            ..cs.clone()
+        };
+
+        let first = warmer(&ColorSettings::default());
+        set(&relative, None, &first)?;
+        let second = warmer(&first);
+        set(&relative, Some(&first), &second)?;
+        set(&absolute, None, &second)?;
+
+        assert_eq!(*second.temp, 5900);
+        let (a, b) = (last_ramps(&relative)?, last_ramps(&absolute)?);
+        assert_eq!(a.len(), 1);
+        assert!(a
+            .iter()
+            .zip(&b)
+            .all(|((i, r1), (j, r2))| i == j && r1.0 == r2.0));
+        Ok(())
+    }
+
+    #[test]
+    fn file_fade_start() -> Result<()> {
+        let path = test_path("fade");
+        let current = ColorSettings {
+            temp: 4000.try_into()?,
+            ..Default::default()
+        };
+        set(&path, None, &current)?;
+        let target = ColorSettings::default();
+        let step = crate::DaemonMode::interpolate(&current, &target, 0);
+        set(&path, Some(&current), &step)?;
+
+        let records = read(&path)?;
+        fs::remove_file(&path)?;
+        let [a, b] = [&records[0], &records[1]].map(Record::ramps);
+        assert_eq!(a.len(), 1);
+        assert!(a
+            .iter()
+            .zip(&b)
+            .all(|((i, r1), (j, r2))| i == j && r1.0 == r2.0));
+        Ok(())
+    }
+}
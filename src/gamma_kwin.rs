@@ -0,0 +1,177 @@
+/*  gamma_kwin.rs -- KDE KWin night light adjustment through D-Bus
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    error::{gamma::KwinError, AdjusterError, AdjusterErrorInner},
+    types::ColorSettings,
+    types_display::WARN,
+    Adjuster,
+};
+use std::{cell::Cell, time::Duration};
+use tracing::warn;
+use zbus::{blocking::Connection, proxy};
+
+#[proxy(
+    interface = "org.kde.KWin.NightLight",
+    default_service = "org.kde.KWin",
+    default_path = "/org/kde/KWin/NightLight"
+)]
+trait NightLight {
+    /// Show a color temperature until stopPreview is called or a timeout
+    #[zbus(name = "preview")]
+    fn preview(&self, temperature: u32) -> zbus::Result<()>;
+
+    #[zbus(name = "stopPreview")]
+    fn stop_preview(&self) -> zbus::Result<()>;
+
+    /// Whether the compositor supports adjusting the color temperature
+    #[zbus(property, name = "available")]
+    fn available(&self) -> zbus::Result<bool>;
+}
+
+/// KWin ends a preview after this timeout
+pub const PREVIEW_TIMEOUT: Duration = Duration::from_secs(15);
+/// Longest time between two updates of the daemon, so that the preview is
+/// set again before it times out
+pub const MAX_SLEEP_DURATION: Duration = Duration::from_secs(10);
+
+/// Drives the color temperature of KWin's night light through its preview.
+/// KWin ends a preview after a timeout of 15 seconds, so the color
+/// temperature is only kept by the daemon which sets it on every update
+#[derive(Debug)]
+pub struct Kwin {
+    proxy: NightLightProxyBlocking<'static>,
+    // Gamma, brightness and effects can not be applied, it is reported once
+    warned: Cell<bool>,
+}
+
+impl Kwin {
+    pub fn new() -> Result<Self, KwinError> {
+        let conn = Connection::session()
+            .map_err(|e| KwinError::ConnectFailed(Box::new(e)))?;
+        Self::with_connection(&conn)
+    }
+
+    pub fn with_connection(conn: &Connection) -> Result<Self, KwinError> {
+        let proxy = NightLightProxyBlocking::new(conn)
+            .map_err(|e| KwinError::ConnectFailed(Box::new(e)))?;
+        if !proxy
+            .available()
+            .map_err(|e| KwinError::GetPropertyFailed(Box::new(e)))?
+        {
+            Err(KwinError::NotAvailable)?
+        }
+
+        Ok(Self {
+            proxy,
+            warned: Cell::new(false),
+        })
+    }
+}
+
+impl Adjuster for Kwin {
+    fn restore(&self) -> Result<(), AdjusterError> {
+        self.proxy.stop_preview().map_err(|e| {
+            AdjusterError::Restore(AdjusterErrorInner::Kwin(Box::new(e)))
+        })
+    }
+
+    fn set(
+        &self,
+        _reset_ramps: bool,
+        cs: &ColorSettings,
+    ) -> Result<(), AdjusterError> {
+        let temp_only = ColorSettings {
+            temp: cs.temp,
+            ..Default::default()
+        };
+        if cs.is_very_diff_from(&temp_only) && !self.warned.replace(true) {
+            let s = "kwin only supports the color temperature, tint, gamma, \
+                     brightness and effects are ignored";
+            warn!("{WARN}warning:{WARN:#} {s}");
+        }
+
+        self.proxy.preview(*cs.temp as u32).map_err(|e| {
+            AdjusterError::Set(AdjusterErrorInner::Kwin(Box::new(e)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{gamma_mutter::test::fake_bus, types::Temperature};
+    use anyhow::Result;
+    use zbus::interface;
+
+    #[derive(Debug, Default)]
+    struct FakeNightLight {
+        available: bool,
+        // None if the preview is stopped
+        preview: Option<u32>,
+    }
+
+    #[interface(name = "org.kde.KWin.NightLight")]
+    impl FakeNightLight {
+        #[zbus(name = "preview")]
+        fn preview(&mut self, temperature: u32) {
+            self.preview = Some(temperature);
+        }
+
+        #[zbus(name = "stopPreview")]
+        fn stop_preview(&mut self) {
+            self.preview = None;
+        }
+
+        #[zbus(property, name = "available")]
+        fn available(&self) -> bool {
+            self.available
+        }
+    }
+
+    #[test]
+    fn kwin_fake_bus() -> Result<()> {
+        let path = "/org/kde/KWin/NightLight";
+        let fake = FakeNightLight::default();
+        let (client, _server) = fake_bus(path, fake)?;
+        assert!(matches!(
+            Kwin::with_connection(&client),
+            Err(KwinError::NotAvailable)
+        ));
+
+        let fake = FakeNightLight {
+            available: true,
+            ..Default::default()
+        };
+        let (client, server) = fake_bus(path, fake)?;
+        let kwin = Kwin::with_connection(&client)?;
+        let fake = server
+            .object_server()
+            .interface::<_, FakeNightLight>(path)?;
+
+        let cs = ColorSettings {
+            temp: Temperature::try_from(3500)?,
+            ..Default::default()
+        };
+        kwin.set(false, &cs)?;
+        assert_eq!(fake.get().preview, Some(3500));
+        kwin.restore()?;
+        assert_eq!(fake.get().preview, None);
+        Ok(())
+    }
+}
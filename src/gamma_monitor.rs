@@ -0,0 +1,159 @@
+/*  gamma_monitor.rs -- Gamma ramps integrity monitoring
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    calc_colorramp::{GammaRamps, GammaRampsFloat},
+    snapshot::ramps_of,
+    types::RampsStatus,
+};
+use std::cell::{Cell, Ref, RefCell};
+
+// Hardware may quantize the ramps it is given (e.g. 10-bit LUTs), so small
+// differences between the written and the read back ramps are ignored
+const RAMPS_TOLERANCE: u16 = u16::MAX / 256;
+
+/// Keeps track of the ramps of a single crtc so they can be compared with
+/// the ramps that are read back from the display
+#[derive(Debug)]
+pub struct RampsState {
+    saved: RefCell<GammaRamps>,
+    written: RefCell<Option<GammaRamps>>,
+    foreign: RefCell<Option<GammaRamps>>,
+    // The saved ramps were replaced by foreign ramps
+    adopted: Cell<bool>,
+}
+
+impl RampsState {
+    pub fn new(saved: GammaRamps) -> Self {
+        Self {
+            saved: RefCell::new(saved),
+            written: Default::default(),
+            foreign: Default::default(),
+            adopted: Default::default(),
+        }
+    }
+
+    /// Ramps that will be restored on exit
+    pub fn saved(&self) -> Ref<'_, GammaRamps> {
+        self.saved.borrow()
+    }
+
+    /// Ramps that new color settings are applied on top of. Adopted foreign
+    /// ramps take precedence over resetting the ramps, they were adopted on
+    /// purpose
+    pub fn base(&self, reset_ramps: bool, ramp_size: u32) -> GammaRampsFloat {
+        if reset_ramps && !self.adopted.get() {
+            GammaRampsFloat::new(ramp_size)
+        } else {
+            GammaRampsFloat::from(&*self.saved.borrow())
+        }
+    }
+
+    /// Replace the saved ramps with the ramps of the crtc in a list, e.g. the
+    /// original ramps. The saved ramps are kept if the crtc is not in the list
+    pub fn rebase(&self, ramps: &[(u32, GammaRamps)], id: u32) {
+        let base = ramps_of(ramps, id, &self.saved());
+        *self.saved.borrow_mut() = base;
+        self.adopted.set(false);
+    }
+
+    /// Remember the ramps that were successfully written to the display
+    pub fn written(&self, ramps: GammaRamps) {
+        *self.written.borrow_mut() = Some(ramps);
+    }
+
+    /// Compare the ramps read back from the display with the last ramps that
+    /// were written. Ramps of other programs that persist after being
+    /// overwritten once are reported as foreign and adopted as the new base
+    /// if `adopt` is true
+    pub fn check(&self, current: GammaRamps, adopt: bool) -> RampsStatus {
+        let written = self.written.borrow();
+        let Some(written) = written.as_ref() else {
+            return RampsStatus::Intact;
+        };
+
+        let mut foreign = self.foreign.borrow_mut();
+        if current.is_close_to(written) {
+            *foreign = None;
+            RampsStatus::Intact
+        } else if foreign.as_ref().is_some_and(|f| current.is_close_to(f)) {
+            if adopt {
+                *self.saved.borrow_mut() = current;
+                *foreign = None;
+                self.adopted.set(true);
+                RampsStatus::Adopted
+            } else {
+                RampsStatus::Foreign
+            }
+        } else {
+            *foreign = Some(current);
+            RampsStatus::Overwritten
+        }
+    }
+}
+
+impl GammaRamps {
+    pub fn is_close_to(&self, other: &Self) -> bool {
+        self.iter().zip(other.iter()).all(|(a, b)| {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| a.abs_diff(*b) <= RAMPS_TOLERANCE)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::calc_colorramp::FULL_DEPTH;
+
+    fn ramps(v: u16) -> GammaRamps {
+        GammaRamps([vec![v; 4], vec![v; 4], vec![v; 4]])
+    }
+
+    #[test]
+    fn is_close_to() {
+        assert!(ramps(1000).is_close_to(&ramps(1000 + RAMPS_TOLERANCE)));
+        assert!(!ramps(1000).is_close_to(&ramps(1001 + RAMPS_TOLERANCE)));
+        let short = GammaRamps([vec![0; 2], vec![0; 2], vec![0; 2]]);
+        assert!(!ramps(0).is_close_to(&short));
+    }
+
+    #[test]
+    fn check() {
+        let state = RampsState::new(ramps(0));
+        assert_eq!(state.check(ramps(5000), true), RampsStatus::Intact);
+        assert_eq!(state.base(true, 4).0, GammaRampsFloat::new(4).0);
+
+        state.written(ramps(1000));
+        assert_eq!(state.check(ramps(1001), true), RampsStatus::Intact);
+        assert_eq!(state.check(ramps(5000), true), RampsStatus::Overwritten);
+        assert_eq!(state.check(ramps(1000), true), RampsStatus::Intact);
+
+        assert_eq!(state.check(ramps(5000), false), RampsStatus::Overwritten);
+        assert_eq!(state.check(ramps(5000), false), RampsStatus::Foreign);
+        assert_eq!(state.check(ramps(9000), false), RampsStatus::Overwritten);
+        assert_eq!(state.check(ramps(9000), true), RampsStatus::Adopted);
+        assert!(state.saved().is_close_to(&ramps(9000)));
+        // adopted ramps are the base even if the ramps are reset
+        let base = state.base(true, 4).to_ramps(4, FULL_DEPTH);
+        assert!(base.is_close_to(&ramps(9000)));
+        assert_eq!(state.check(ramps(9000), true), RampsStatus::Overwritten);
+    }
+}
@@ -0,0 +1,489 @@
+/*  gamma_mutter.rs -- GNOME Mutter gamma adjustment through D-Bus
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    calc_colorramp::{GammaRamps, FULL_DEPTH},
+    error::{
+        gamma::{CrtcError, MutterCrtcError, MutterError},
+        AdjusterError, AdjusterErrorInner,
+    },
+    gamma_monitor::RampsState,
+    gamma_output::{resolve_crtcs, Edid, Output},
+    list::CrtcInfo,
+    snapshot::ramps_of,
+    types::{ColorSettings, CrtcSelector, RampsStatus},
+    types_display::WARN,
+    utils::CollectResult,
+    Adjuster,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+use tracing::{info, warn};
+use zbus::{blocking::Connection, proxy, zvariant::OwnedValue};
+
+// Red, green and blue ramps of a crtc
+type CrtcGamma = (Vec<u16>, Vec<u16>, Vec<u16>);
+// Id, winsys id, x, y, width, height, current mode, current transform,
+// transforms and properties of a crtc
+type CrtcResource = (
+    u32,
+    i64,
+    i32,
+    i32,
+    i32,
+    i32,
+    i32,
+    u32,
+    Vec<u32>,
+    HashMap<String, OwnedValue>,
+);
+// Id, winsys id, current crtc, possible crtcs, name, modes, clones and
+// properties of an output
+type OutputResource = (
+    u32,
+    i64,
+    i32,
+    Vec<u32>,
+    String,
+    Vec<u32>,
+    Vec<u32>,
+    HashMap<String, OwnedValue>,
+);
+// Id, winsys id, width, height, frequency and flags of a mode
+type ModeResource = (u32, i64, u32, u32, f64, u32);
+type Resources = (
+    u32,
+    Vec<CrtcResource>,
+    Vec<OutputResource>,
+    Vec<ModeResource>,
+    i32,
+    i32,
+);
+
+#[proxy(
+    interface = "org.gnome.Mutter.DisplayConfig",
+    default_service = "org.gnome.Mutter.DisplayConfig",
+    default_path = "/org/gnome/Mutter/DisplayConfig"
+)]
+trait DisplayConfig {
+    /// Returns the serial, crtcs, outputs, modes and the maximum screen size
+    fn get_resources(&self) -> zbus::Result<Resources>;
+
+    fn get_crtc_gamma(
+        &self,
+        serial: u32,
+        crtc: u32,
+    ) -> zbus::Result<CrtcGamma>;
+
+    fn set_crtc_gamma(
+        &self,
+        serial: u32,
+        crtc: u32,
+        red: &[u16],
+        green: &[u16],
+        blue: &[u16],
+    ) -> zbus::Result<()>;
+}
+
+#[derive(Debug)]
+pub struct Mutter {
+    proxy: DisplayConfigProxyBlocking<'static>,
+    selectors: Vec<CrtcSelector>,
+    // Changes when the monitor configuration changes. Requests with an old
+    // serial are rejected
+    serial: Cell<u32>,
+    crtcs: RefCell<Vec<Crtc>>,
+}
+
+#[derive(Debug)]
+struct Crtc {
+    id: u32,
+    ramp_size: u32,
+    ramps: RampsState,
+}
+
+impl Mutter {
+    pub fn new(selectors: Vec<CrtcSelector>) -> Result<Self, MutterError> {
+        let conn = Connection::session()
+            .map_err(|e| MutterError::ConnectFailed(Box::new(e)))?;
+        Self::with_connection(&conn, selectors)
+    }
+
+    pub fn with_connection(
+        conn: &Connection,
+        selectors: Vec<CrtcSelector>,
+    ) -> Result<Self, MutterError> {
+        let proxy = DisplayConfigProxyBlocking::new(conn)
+            .map_err(|e| MutterError::ConnectFailed(Box::new(e)))?;
+        let (serial, ids) = Self::resolve(&proxy, &selectors, false)?;
+        let crtcs = Self::get_crtcs(&proxy, serial, ids)?;
+
+        Ok(Self {
+            proxy,
+            selectors,
+            serial: Cell::new(serial),
+            crtcs: RefCell::new(crtcs),
+        })
+    }
+
+    fn resolve(
+        proxy: &DisplayConfigProxyBlocking,
+        selectors: &[CrtcSelector],
+        skip_missing: bool,
+    ) -> Result<(u32, Vec<u32>), MutterError> {
+        let (serial, crtcs, outputs, ..) = proxy
+            .get_resources()
+            .map_err(|e| MutterError::GetResourcesFailed(Box::new(e)))?;
+        let crtcs = crtcs.iter().map(|c| c.0).collect::<Vec<_>>();
+        let outputs = outputs.iter().map(Self::output).collect::<Vec<_>>();
+        let crtcs = resolve_crtcs(selectors, &crtcs, &outputs, skip_missing)?;
+        Ok((serial, crtcs))
+    }
+
+    fn output(o: &OutputResource) -> Output {
+        let (_, _, crtc, _, name, _, _, props) = o;
+        let prop = |k: &str| {
+            let v = props.get(k)?;
+            <&str>::try_from(v).ok().map(str::to_string)
+        };
+        Output {
+            name: name.clone(),
+            // disconnected outputs are not listed
+            connected: true,
+            crtc: u32::try_from(*crtc).ok(),
+            edid: prop("vendor").map(|manufacturer| Edid {
+                manufacturer,
+                serial: prop("serial"),
+                model: prop("product"),
+            }),
+        }
+    }
+
+    fn get_crtcs(
+        proxy: &DisplayConfigProxyBlocking,
+        serial: u32,
+        ids: Vec<u32>,
+    ) -> Result<Vec<Crtc>, MutterError> {
+        ids.into_iter()
+            .map(|id| {
+                let f = || -> Result<Crtc, MutterCrtcError> {
+                    let (r, g, b) =
+                        proxy.get_crtc_gamma(serial, id).map_err(|e| {
+                            MutterCrtcError::GetRampFailed(Box::new(e))
+                        })?;
+                    let ramp_size = r.len() as u32;
+                    if ramp_size == 0
+                        || g.len() != r.len()
+                        || b.len() != r.len()
+                    {
+                        Err(MutterCrtcError::InvalidRampSize(ramp_size))?
+                    }
+                    Ok(Crtc {
+                        id,
+                        ramp_size,
+                        ramps: RampsState::new(GammaRamps([r, g, b])),
+                    })
+                };
+                f().map_err(|err| CrtcError { id, err })
+            })
+            .collect_result()
+            .map_err(MutterError::GetCrtcs)
+    }
+
+    pub fn crtcs_info(&self) -> Vec<CrtcInfo> {
+        let crtcs = self.crtcs.borrow();
+        crtcs
+            .iter()
+            .map(|c| CrtcInfo::new(c.id, &c.ramps.saved()))
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Result<Vec<Output>, MutterError> {
+        let (_, _, outputs, ..) = self
+            .proxy
+            .get_resources()
+            .map_err(|e| MutterError::GetResourcesFailed(Box::new(e)))?;
+        Ok(outputs.iter().map(Self::output).collect())
+    }
+
+    /// Select the crtcs again if the monitor configuration has changed.
+    /// Crtcs that are no longer selected are left as they are, since their
+    /// ids are not valid anymore
+    fn refresh(&self) {
+        let r = Self::resolve(&self.proxy, &self.selectors, true);
+        let (serial, ids) = match r {
+            Ok(r) => r,
+            Err(e) => {
+                let s = "unable to select crtcs";
+                warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+                return;
+            }
+        };
+        if serial == self.serial.get() {
+            return;
+        }
+        self.serial.set(serial);
+
+        let mut crtcs = self.crtcs.borrow_mut();
+        let new = ids
+            .iter()
+            .filter(|&id| !crtcs.iter().any(|c| c.id == *id))
+            .copied()
+            .collect();
+        match Self::get_crtcs(&self.proxy, serial, new) {
+            Ok(new) => {
+                crtcs.retain(|c| ids.contains(&c.id));
+                crtcs.extend(new);
+                crtcs.sort_by_key(|c| ids.iter().position(|&id| id == c.id));
+                info!("Monitors changed, using crtcs {ids:?}");
+            }
+            Err(e) => {
+                let s = "unable to get crtcs after monitors changed";
+                warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+            }
+        }
+    }
+
+    /// Set the ramps of each crtc. Returns the ramps once all of them are set
+    fn set_gamma_ramps(
+        &self,
+        f: impl Fn(&Crtc) -> GammaRamps,
+    ) -> Result<Vec<GammaRamps>, AdjusterErrorInner> {
+        let serial = self.serial.get();
+        self.crtcs
+            .borrow()
+            .iter()
+            .map(|crtc| {
+                let ramps = f(crtc);
+                self.proxy
+                    .set_crtc_gamma(
+                        serial, crtc.id, &ramps[0], &ramps[1], &ramps[2],
+                    )
+                    .map(|()| ramps)
+            })
+            .collect_result()
+            .map_err(AdjusterErrorInner::Mutter)
+    }
+
+    fn get_gamma_ramps(&self) -> Result<Vec<GammaRamps>, AdjusterErrorInner> {
+        let serial = self.serial.get();
+        self.crtcs
+            .borrow()
+            .iter()
+            .map(|crtc| {
+                let r = self.proxy.get_crtc_gamma(serial, crtc.id);
+                r.map(|(r, g, b)| GammaRamps([r, g, b]))
+            })
+            .collect_result()
+            .map_err(AdjusterErrorInner::Mutter)
+    }
+}
+
+impl Adjuster for Mutter {
+    fn restore(&self) -> Result<(), AdjusterError> {
+        self.set_gamma_ramps(|crtc| crtc.ramps.saved().clone())
+            .map_err(AdjusterError::Restore)?;
+        Ok(())
+    }
+
+    fn set(
+        &self,
+        reset_ramps: bool,
+        cs: &ColorSettings,
+    ) -> Result<(), AdjusterError> {
+        self.refresh();
+        let ramps = self
+            .set_gamma_ramps(|crtc| {
+                let mut ramps = crtc.ramps.base(reset_ramps, crtc.ramp_size);
+                ramps.colorramp_fill(cs);
+                ramps.to_ramps(crtc.ramp_size, FULL_DEPTH)
+            })
+            .map_err(AdjusterError::Set)?;
+        // only ramps that were applied are compared with the current ones
+        for (crtc, r) in self.crtcs.borrow().iter().zip(ramps) {
+            crtc.ramps.written(r);
+        }
+        Ok(())
+    }
+
+    fn verify(
+        &self,
+        adopt_foreign: bool,
+    ) -> Result<Vec<(u32, RampsStatus)>, AdjusterError> {
+        let current = self.get_gamma_ramps().map_err(AdjusterError::Get)?;
+        Ok(self
+            .crtcs
+            .borrow()
+            .iter()
+            .zip(current)
+            .map(|(crtc, r)| (crtc.id, crtc.ramps.check(r, adopt_foreign)))
+            .collect())
+    }
+
+    fn saved_ramps(&self) -> Vec<(u32, GammaRamps)> {
+        let crtcs = self.crtcs.borrow();
+        crtcs
+            .iter()
+            .map(|c| (c.id, c.ramps.saved().clone()))
+            .collect()
+    }
+
+    fn set_ramps(
+        &self,
+        ramps: &[(u32, GammaRamps)],
+    ) -> Result<(), AdjusterError> {
+        self.set_gamma_ramps(|crtc| {
+            ramps_of(ramps, crtc.id, &crtc.ramps.saved())
+        })
+        .map_err(AdjusterError::Set)?;
+        Ok(())
+    }
+
+    fn set_base(&self, ramps: &[(u32, GammaRamps)]) {
+        for crtc in self.crtcs.borrow().iter() {
+            crtc.ramps.rebase(ramps, crtc.id);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::types::{Brightness, Temperature};
+    use anyhow::{anyhow, Result};
+    use std::{os::unix::net::UnixStream, sync::Mutex, thread};
+    use zbus::{blocking::connection::Builder, interface, Guid};
+
+    const RAMP_SIZE: usize = 4;
+
+    /// Mutter with two crtcs, the second one driving HDMI-1
+    #[derive(Debug, Default)]
+    struct FakeMutter {
+        ramps: Mutex<HashMap<u32, CrtcGamma>>,
+    }
+
+    #[interface(name = "org.gnome.Mutter.DisplayConfig")]
+    impl FakeMutter {
+        fn get_resources(&self) -> Resources {
+            let crtc = |id| (id, 0, 0, 0, 0, 0, 0, 0, vec![], HashMap::new());
+            let output = |crtc, name: &str| {
+                let name = name.to_string();
+                (0, 0, crtc, vec![], name, vec![], vec![], HashMap::new())
+            };
+            let outputs = vec![output(-1, "eDP-1"), output(1, "HDMI-1")];
+            (7, vec![crtc(0), crtc(1)], outputs, vec![], 0, 0)
+        }
+
+        fn get_crtc_gamma(
+            &self,
+            serial: u32,
+            crtc: u32,
+        ) -> zbus::fdo::Result<CrtcGamma> {
+            if serial != 7 {
+                return Err(zbus::fdo::Error::AccessDenied("serial".into()));
+            }
+            let ramps = self
+                .ramps
+                .lock()
+                .map_err(|_| zbus::fdo::Error::Failed("lock".into()))?;
+            let linear = GammaRamps::new(RAMP_SIZE as u32);
+            Ok(ramps.get(&crtc).cloned().unwrap_or_else(|| {
+                let [r, g, b] = linear.0;
+                (r, g, b)
+            }))
+        }
+
+        fn set_crtc_gamma(
+            &self,
+            serial: u32,
+            crtc: u32,
+            red: Vec<u16>,
+            green: Vec<u16>,
+            blue: Vec<u16>,
+        ) -> zbus::fdo::Result<()> {
+            if serial != 7 {
+                return Err(zbus::fdo::Error::AccessDenied("serial".into()));
+            }
+            let mut ramps = self
+                .ramps
+                .lock()
+                .map_err(|_| zbus::fdo::Error::Failed("lock".into()))?;
+            ramps.insert(crtc, (red, green, blue));
+            Ok(())
+        }
+    }
+
+    /// Connect to an object served on a peer to peer connection
+    pub fn fake_bus<T: zbus::object_server::Interface>(
+        path: &str,
+        object: T,
+    ) -> Result<(Connection, Connection)> {
+        let (a, b) = UnixStream::pair()?;
+        let guid = Guid::generate();
+        let path = path.to_string();
+        let server = thread::spawn(move || {
+            Builder::async_io_unix_stream(a)
+                .server(guid)?
+                .p2p()
+                .serve_at(path, object)?
+                .build()
+        });
+        let client = Builder::async_io_unix_stream(b).p2p().build()?;
+        let server = server.join().map_err(|_| anyhow!("server panicked"))?;
+        Ok((client, server?))
+    }
+
+    #[test]
+    fn mutter_fake_bus() -> Result<()> {
+        let path = "/org/gnome/Mutter/DisplayConfig";
+        let (client, server) = fake_bus(path, FakeMutter::default())?;
+        let selector = CrtcSelector::Output("HDMI-1".into());
+        let m = Mutter::with_connection(&client, vec![selector])?;
+        assert_eq!(m.saved_ramps().len(), 1);
+        assert_eq!(m.saved_ramps()[0].0, 1);
+
+        let cs = ColorSettings {
+            temp: Temperature::try_from(3000)?,
+            brght: Brightness::try_from(0.5)?,
+            ..Default::default()
+        };
+        m.set(true, &cs)?;
+        let fake = server.object_server().interface::<_, FakeMutter>(path)?;
+        let ramps = || -> Result<HashMap<u32, CrtcGamma>> {
+            let fake = fake.get();
+            let ramps = fake.ramps.lock().map_err(|_| anyhow!("lock"))?;
+            Ok(ramps.clone())
+        };
+        let ramps1 = ramps()?;
+        let (r, g, b) = &ramps1[&1];
+        assert!(!ramps1.contains_key(&0));
+        assert!(r[RAMP_SIZE - 1] > g[RAMP_SIZE - 1]);
+        assert!(g[RAMP_SIZE - 1] > b[RAMP_SIZE - 1]);
+        assert!(r[RAMP_SIZE - 1] <= u16::MAX / 2 + 1);
+        assert!(m
+            .verify(false)?
+            .iter()
+            .all(|(_, s)| *s == RampsStatus::Intact));
+
+        m.restore()?;
+        assert_eq!(ramps()?[&1].0, GammaRamps::new(RAMP_SIZE as u32)[0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+/*  gamma_output.rs -- Selecting crtcs through outputs
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{error::gamma::SelectorError, types::CrtcSelector};
+use itertools::Itertools;
+use serde::Serialize;
+
+const EDID_HEADER: [u8; 8] = [0, 255, 255, 255, 255, 255, 255, 0];
+const EDID_DESCRIPTOR_SERIAL: u8 = 0xFF;
+const EDID_DESCRIPTOR_NAME: u8 = 0xFC;
+
+/// A RandR output or a DRM connector
+#[derive(Debug, Clone, Serialize)]
+pub struct Output {
+    pub name: String,
+    pub connected: bool,
+    /// None if the output is disconnected or disabled
+    pub crtc: Option<u32>,
+    pub edid: Option<Edid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edid {
+    /// Three letter PNP id, e.g. DEL
+    pub manufacturer: String,
+    /// Serial number descriptor, or the numeric serial number if the
+    /// descriptor is missing
+    pub serial: Option<String>,
+    pub model: Option<String>,
+}
+
+impl Edid {
+    /// Parse the base block of an EDID. Extension blocks are ignored
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 128 || data[..8] != EDID_HEADER {
+            return None;
+        }
+
+        let id = u16::from_be_bytes([data[8], data[9]]);
+        let manufacturer = [10, 5, 0]
+            .iter()
+            .map(|shift| ((id >> shift) & 0x1F) as u8 + b'A' - 1)
+            .map(char::from)
+            .collect();
+
+        let descriptor = |tag: u8| {
+            data[54..126]
+                .chunks_exact(18)
+                .find(|d| d[..3] == [0, 0, 0] && d[3] == tag)
+                .map(|d| {
+                    let text = d[5..].split(|&c| c == b'\n').next();
+                    String::from_utf8_lossy(text.unwrap_or_default())
+                        .trim()
+                        .to_string()
+                })
+        };
+        let serial =
+            u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+        let serial = descriptor(EDID_DESCRIPTOR_SERIAL)
+            .or_else(|| (serial != 0).then(|| serial.to_string()));
+
+        Some(Self {
+            manufacturer,
+            serial,
+            model: descriptor(EDID_DESCRIPTOR_NAME),
+        })
+    }
+}
+
+impl CrtcSelector {
+    fn is_output(&self) -> bool {
+        !matches!(self, Self::Id(_))
+    }
+
+    /// The manufacturer and the serial number of an edid are compared
+    /// ignoring the case
+    fn matches(&self, output: &Output) -> bool {
+        match self {
+            Self::Id(_) => false,
+            Self::Output(name) => output.name == *name,
+            Self::Edid {
+                manufacturer,
+                serial,
+            } => output.edid.as_ref().is_some_and(|edid| {
+                let eq = |a: &str, b: &str| a.eq_ignore_ascii_case(b);
+                eq(&edid.manufacturer, manufacturer)
+                    && match (serial, &edid.serial) {
+                        (None, _) => true,
+                        (Some(a), Some(b)) => eq(a, b),
+                        (Some(_), None) => false,
+                    }
+            }),
+        }
+    }
+}
+
+impl Output {
+    fn describe(&self) -> String {
+        match &self.edid {
+            Some(Edid {
+                manufacturer,
+                serial: Some(serial),
+                ..
+            }) => format!("{} (edid={manufacturer}/{serial})", self.name),
+            Some(Edid { manufacturer, .. }) => {
+                format!("{} (edid={manufacturer})", self.name)
+            }
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Returns true if the selectors need the outputs to be resolved
+pub fn has_outputs(selectors: &[CrtcSelector]) -> bool {
+    selectors.iter().any(CrtcSelector::is_output)
+}
+
+/// Resolve the selectors to crtc ids. All crtcs are selected if there are no
+/// selectors. Outputs that are not driven by a crtc are skipped. Selected
+/// outputs that are missing are skipped too if `skip_missing` is true, e.g.
+/// when the outputs change after an output was unplugged
+pub fn resolve_crtcs(
+    selectors: &[CrtcSelector],
+    all_crtcs: &[u32],
+    outputs: &[Output],
+    skip_missing: bool,
+) -> Result<Vec<u32>, SelectorError> {
+    if selectors.is_empty() {
+        return Ok(all_crtcs.to_vec());
+    }
+    if !selectors.iter().all_unique() {
+        Err(SelectorError::NonUniqueCrtc)?
+    }
+
+    let mut crtcs = Vec::new();
+    for selector in selectors {
+        match selector {
+            CrtcSelector::Id(id) if all_crtcs.contains(id) => crtcs.push(*id),
+            CrtcSelector::Id(_) => {
+                Err(SelectorError::InvalidCrtc(all_crtcs.to_vec()))?
+            }
+            _ => {
+                let mut matched =
+                    outputs.iter().filter(|o| selector.matches(o)).peekable();
+                if matched.peek().is_none() && !skip_missing {
+                    let valid = outputs.iter().map(Output::describe).collect();
+                    Err(SelectorError::InvalidOutput(
+                        selector.to_string(),
+                        valid,
+                    ))?
+                }
+                crtcs.extend(matched.filter_map(|o| o.crtc));
+            }
+        }
+    }
+
+    // Cloned outputs share the same crtc
+    Ok(crtcs.into_iter().unique().collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Result;
+
+    fn edid(serial: u32, descriptors: &[(u8, &str)]) -> Vec<u8> {
+        let mut data = vec![0; 128];
+        data[..8].copy_from_slice(&EDID_HEADER);
+        // DEL: 00100 00101 01100
+        data[8..10].copy_from_slice(&0x10ACu16.to_be_bytes());
+        data[12..16].copy_from_slice(&serial.to_le_bytes());
+        for (i, (tag, text)) in descriptors.iter().enumerate() {
+            let d = &mut data[54 + i * 18..][..18];
+            d[3] = *tag;
+            d[5..].fill(b' ');
+            d[5..5 + text.len()].copy_from_slice(text.as_bytes());
+            d[5 + text.len()] = b'\n';
+        }
+        data
+    }
+
+    fn output(name: &str, crtc: Option<u32>, edid: Option<Edid>) -> Output {
+        Output {
+            name: name.into(),
+            connected: crtc.is_some(),
+            crtc,
+            edid,
+        }
+    }
+
+    #[test]
+    fn edid_parse() {
+        let descriptors = [
+            (EDID_DESCRIPTOR_NAME, "DELL U2720Q"),
+            (EDID_DESCRIPTOR_SERIAL, "ABC123"),
+        ];
+        assert_eq!(
+            Edid::parse(&edid(42, &descriptors)),
+            Some(Edid {
+                manufacturer: "DEL".into(),
+                serial: Some("ABC123".into()),
+                model: Some("DELL U2720Q".into()),
+            })
+        );
+
+        // the numeric serial number is used without a serial descriptor
+        let e = Edid::parse(&edid(42, &[]));
+        assert_eq!(e.and_then(|e| e.serial), Some("42".into()));
+        let e = Edid::parse(&edid(0, &[]));
+        assert_eq!(e.map(|e| e.serial), Some(None));
+
+        assert_eq!(Edid::parse(&edid(42, &[])[..127]), None);
+        assert_eq!(Edid::parse(&[0; 128]), None);
+    }
+
+    #[test]
+    fn resolve() -> Result<()> {
+        let del = |serial: &str| Edid {
+            manufacturer: "DEL".into(),
+            serial: Some(serial.into()),
+            model: None,
+        };
+        let outputs = [
+            output("DP-1", Some(10), Some(del("A"))),
+            output("DP-2", Some(11), Some(del("B"))),
+            output("DP-3", Some(12), Some(del("ABC123"))),
+            output("HDMI-1", None, None),
+        ];
+        let crtcs = [10, 11, 12, 13];
+        let resolve = |selectors: &[&str], skip_missing| -> Result<_> {
+            let selectors = selectors
+                .iter()
+                .map(|s| s.parse())
+                .collect::<Result<Vec<CrtcSelector>, _>>()?;
+            Ok(resolve_crtcs(&selectors, &crtcs, &outputs, skip_missing))
+        };
+
+        assert_eq!(resolve(&[], false)??, [10, 11, 12, 13]);
+        assert_eq!(resolve(&["13", "DP-1"], false)??, [13, 10]);
+        assert_eq!(resolve(&["edid=del/B"], false)??, [11]);
+        assert_eq!(resolve(&["edid=DEL/abc123"], false)??, [12]);
+        // all monitors match and the disconnected output is skipped
+        assert_eq!(resolve(&["edid=DEL", "HDMI-1"], false)??, [10, 11, 12]);
+
+        assert!(matches!(
+            resolve(&["14"], false)?,
+            Err(SelectorError::InvalidCrtc(_))
+        ));
+        assert!(matches!(
+            resolve(&["10", "10"], false)?,
+            Err(SelectorError::NonUniqueCrtc)
+        ));
+        assert!(matches!(
+            resolve(&["DP-1", "edid=DEL/C"], false)?,
+            Err(SelectorError::InvalidOutput(..))
+        ));
+        // e.g. after the monitor was unplugged
+        assert_eq!(resolve(&["DP-1", "edid=DEL/C"], true)??, [10]);
+        Ok(())
+    }
+}
@@ -19,22 +19,33 @@
 */
 
 use crate::{
-    calc_colorramp::GammaRamps,
+    calc_colorramp::{GammaRamps, FULL_DEPTH},
     config::{RANDR_MAJOR_VERSION, RANDR_MINOR_VERSION_MIN},
     error::{
         gamma::{CrtcError, RandrCrtcError, RandrError},
         AdjusterError, AdjusterErrorInner,
     },
-    types::ColorSettings,
+    gamma_monitor::RampsState,
+    gamma_output::{has_outputs, resolve_crtcs, Edid, Output},
+    list::CrtcInfo,
+    snapshot::ramps_of,
+    types::{ColorSettings, CrtcSelector, RampsStatus},
+    types_display::WARN,
     utils::{CollectResult, InjectMapErr},
     Adjuster,
 };
+use std::cell::RefCell;
+use tracing::{info, warn};
 use x11rb::{
     connection::Connection as _,
-    cookie::{Cookie, VoidCookie},
-    errors::ConnectionError,
-    protocol::randr::{
-        ConnectionExt, GetCrtcGammaReply, GetCrtcGammaSizeReply,
+    cookie::Cookie,
+    errors::{ConnectionError, ReplyError},
+    protocol::{
+        randr::{
+            Connection, ConnectionExt, GetCrtcGammaReply,
+            GetCrtcGammaSizeReply, NotifyMask,
+        },
+        xproto::{AtomEnum, ConnectionExt as _, Window},
     },
     rust_connection::RustConnection as Conn,
 };
@@ -42,20 +53,23 @@ use x11rb::{
 #[derive(Debug)]
 pub struct Randr {
     conn: Conn,
-    crtcs: Vec<Crtc>,
+    root: Window,
+    selectors: Vec<CrtcSelector>,
+    // Changes when outputs are plugged or unplugged
+    crtcs: RefCell<Vec<Crtc>>,
 }
 
 #[derive(Debug)]
 struct Crtc {
     id: u32,
     ramp_size: u16,
-    saved_ramps: GammaRamps,
+    ramps: RampsState,
 }
 
 impl Randr {
     pub fn new(
         screen_num: Option<usize>,
-        crtc_ids: Vec<u32>,
+        selectors: Vec<CrtcSelector>,
     ) -> Result<Self, RandrError> {
         // uses the DISPLAY environment variable if screen_num is None
         let screen_num = screen_num.map(|n| ":".to_string() + &n.to_string());
@@ -78,39 +92,111 @@ impl Randr {
             })?
         }
 
-        let crtcs = Self::get_crtcs(&conn, screen_num, crtc_ids)?;
+        let root = conn.setup().roots[screen_num].root;
+        if has_outputs(&selectors) {
+            // Hotplug events are handled before each update
+            let mask = NotifyMask::OUTPUT_CHANGE | NotifyMask::CRTC_CHANGE;
+            conn.randr_select_input(root, mask)
+                .inject_map_err(RandrError::GetResourcesFailed)?
+                .check()
+                .inject_map_err(RandrError::GetResourcesFailed)?;
+        }
+
+        let crtc_ids = Self::resolve(&conn, root, &selectors, false)?;
+        let crtcs = RefCell::new(Self::get_crtcs(&conn, crtc_ids)?);
 
-        Ok(Self { conn, crtcs })
+        Ok(Self {
+            conn,
+            root,
+            selectors,
+            crtcs,
+        })
     }
 
-    fn get_crtcs(
+    fn resolve(
         conn: &Conn,
-        screen_num: usize,
-        mut crtc_ids: Vec<u32>,
-    ) -> Result<Vec<Crtc>, RandrError> {
-        let win = conn.setup().roots[screen_num].root;
-        let all_crtcs = conn
-            .randr_get_screen_resources_current(win)
+        root: Window,
+        selectors: &[CrtcSelector],
+        skip_missing: bool,
+    ) -> Result<Vec<u32>, RandrError> {
+        let res = conn
+            .randr_get_screen_resources_current(root)
             .inject_map_err(RandrError::GetResourcesFailed)?
             .reply()
-            .inject_map_err(RandrError::GetResourcesFailed)?
-            .crtcs;
+            .inject_map_err(RandrError::GetResourcesFailed)?;
 
-        let crtcs = if crtc_ids.is_empty() {
-            all_crtcs
+        let outputs = if has_outputs(selectors) {
+            Self::get_outputs(conn, &res.outputs, res.config_timestamp)
+                .inject_map_err(RandrError::GetOutputsFailed)?
         } else {
-            let len = crtc_ids.len();
-            crtc_ids.sort();
-            crtc_ids.dedup();
-            if len > crtc_ids.len() {
-                Err(RandrError::NonUniqueCrtc)?
-            }
-            let f = |&h| Self::validate_crtc(&all_crtcs, h);
-            crtc_ids.iter().try_for_each(f)?;
-            crtc_ids
+            Vec::new()
         };
 
+        let crtcs =
+            resolve_crtcs(selectors, &res.crtcs, &outputs, skip_missing)?;
+        Ok(crtcs)
+    }
+
+    fn get_outputs(
+        conn: &Conn,
+        outputs: &[u32],
+        timestamp: u32,
+    ) -> Result<Vec<Output>, ReplyError> {
+        let edid = conn.intern_atom(true, b"EDID")?.reply()?.atom;
+        outputs
+            .iter()
+            .map(|&o| {
+                let info = conn.randr_get_output_info(o, timestamp)?;
+                let prop = conn.randr_get_output_property(
+                    o,
+                    edid,
+                    AtomEnum::ANY,
+                    0,
+                    128,
+                    false,
+                    false,
+                )?;
+                Ok((info, prop))
+            })
+            // collect to send all of the requests
+            .collect::<Result<Vec<_>, ConnectionError>>()?
+            .into_iter()
+            .map(|(info, prop)| {
+                let info = info.reply()?;
+                Ok(Output {
+                    name: String::from_utf8_lossy(&info.name).into_owned(),
+                    connected: info.connection == Connection::CONNECTED,
+                    crtc: (info.crtc != 0).then_some(info.crtc),
+                    edid: Edid::parse(&prop.reply()?.data),
+                })
+            })
+            .collect()
+    }
+
+    pub fn crtcs_info(&self) -> Vec<CrtcInfo> {
+        let crtcs = self.crtcs.borrow();
         crtcs
+            .iter()
+            .map(|c| CrtcInfo::new(c.id, &c.ramps.saved()))
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Result<Vec<Output>, RandrError> {
+        let res = self
+            .conn
+            .randr_get_screen_resources_current(self.root)
+            .inject_map_err(RandrError::GetResourcesFailed)?
+            .reply()
+            .inject_map_err(RandrError::GetResourcesFailed)?;
+        Self::get_outputs(&self.conn, &res.outputs, res.config_timestamp)
+            .inject_map_err(RandrError::GetOutputsFailed)
+    }
+
+    fn get_crtcs(
+        conn: &Conn,
+        crtc_ids: Vec<u32>,
+    ) -> Result<Vec<Crtc>, RandrError> {
+        crtc_ids
             .into_iter()
             .map(|id| {
                 let c_ramp = conn.randr_get_crtc_gamma(id)?;
@@ -126,14 +212,6 @@ impl Randr {
             .map_err(RandrError::GetCrtcs)
     }
 
-    fn validate_crtc(all_crtcs: &[u32], id: u32) -> Result<(), RandrError> {
-        if all_crtcs.iter().any(|&i| id == i) {
-            Ok(())
-        } else {
-            Err(RandrError::InvalidCrtc(all_crtcs.to_vec()))
-        }
-    }
-
     fn get_crtc(
         (id, c_size, c_ramp): (
             u32,
@@ -143,7 +221,7 @@ impl Randr {
     ) -> Result<Crtc, CrtcError<u32, RandrCrtcError>> {
         let f = || -> Result<Crtc, RandrCrtcError> {
             let r = c_ramp.reply().map_err(RandrCrtcError::GetRampFailed)?;
-            let saved_ramps = GammaRamps([r.red, r.green, r.blue]);
+            let ramps = RampsState::new(GammaRamps([r.red, r.green, r.blue]));
             let ramp_size = c_size
                 .reply()
                 .map_err(RandrCrtcError::GetRampSizeFailed)?
@@ -155,20 +233,82 @@ impl Randr {
             Ok(Crtc {
                 id,
                 ramp_size,
-                saved_ramps,
+                ramps,
             })
         };
 
         f().map_err(|err| CrtcError { id, err })
     }
 
-    fn set_gamma_ramps<'s>(
-        &'s self,
-        f: impl Fn(&Crtc) -> Result<VoidCookie<'s, Conn>, ConnectionError>,
-    ) -> Result<(), AdjusterErrorInner> {
-        self.crtcs
+    /// Select the crtcs again if outputs were plugged or unplugged
+    fn refresh(&self) {
+        let mut changed = false;
+        while let Ok(Some(_)) = self.conn.poll_for_event() {
+            changed = true;
+        }
+        if !changed {
+            return;
+        }
+
+        let r = Self::resolve(&self.conn, self.root, &self.selectors, true);
+        let ids = match r {
+            Ok(ids) => ids,
+            Err(e) => {
+                let s = "unable to select crtcs after outputs changed";
+                warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+                return;
+            }
+        };
+        self.update_crtcs(&ids);
+    }
+
+    /// Crtcs that are no longer selected are restored
+    fn update_crtcs(&self, ids: &[u32]) {
+        let mut crtcs = self.crtcs.borrow_mut();
+        if crtcs.iter().map(|c| c.id).eq(ids.iter().copied()) {
+            return;
+        }
+        for crtc in crtcs.iter().filter(|c| !ids.contains(&c.id)) {
+            let saved = crtc.ramps.saved();
+            let _ = self.conn.randr_set_crtc_gamma(
+                crtc.id, &saved[0], &saved[1], &saved[2],
+            );
+        }
+
+        let new = ids
             .iter()
-            .map(f)
+            .filter(|&id| !crtcs.iter().any(|c| c.id == *id))
+            .copied()
+            .collect();
+        match Self::get_crtcs(&self.conn, new) {
+            Ok(new) => {
+                let mut old = std::mem::take(&mut *crtcs);
+                old.retain(|c| ids.contains(&c.id));
+                old.extend(new);
+                old.sort_by_key(|c| ids.iter().position(|&id| id == c.id));
+                *crtcs = old;
+                info!("Outputs changed, using crtcs {ids:?}");
+            }
+            Err(e) => {
+                let s = "unable to get crtcs after outputs changed";
+                warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+            }
+        }
+    }
+
+    /// Set the ramps of each crtc. Returns the ramps once all of them are set
+    fn set_gamma_ramps(
+        &self,
+        f: impl Fn(&Crtc) -> GammaRamps,
+    ) -> Result<Vec<GammaRamps>, AdjusterErrorInner> {
+        let crtcs = self.crtcs.borrow();
+        let ramps = crtcs.iter().map(f).collect::<Vec<_>>();
+        crtcs
+            .iter()
+            .zip(&ramps)
+            .map(|(crtc, r)| {
+                self.conn.randr_set_crtc_gamma(crtc.id, &r[0], &r[1], &r[2])
+            })
             // collect to send all of the requests
             .collect_result()
             .inject_map_err(AdjusterErrorInner::Randr)?
@@ -176,39 +316,92 @@ impl Randr {
             .map(|c| c.check())
             .collect_result()
             .inject_map_err(AdjusterErrorInner::Randr)?;
-        Ok(())
+        Ok(ramps)
+    }
+
+    fn get_gamma_ramps(&self) -> Result<Vec<GammaRamps>, AdjusterErrorInner> {
+        Ok(self
+            .crtcs
+            .borrow()
+            .iter()
+            .map(|crtc| self.conn.randr_get_crtc_gamma(crtc.id))
+            // collect to send all of the requests
+            .collect_result()
+            .inject_map_err(AdjusterErrorInner::Randr)?
+            .into_iter()
+            .map(|c| c.reply())
+            .collect_result()
+            .inject_map_err(AdjusterErrorInner::Randr)?
+            .into_iter()
+            .map(|r| GammaRamps([r.red, r.green, r.blue]))
+            .collect())
     }
 }
 
 impl Adjuster for Randr {
     fn restore(&self) -> Result<(), AdjusterError> {
-        self.set_gamma_ramps(|crtc| {
-            self.conn.randr_set_crtc_gamma(
-                crtc.id,
-                &crtc.saved_ramps[0],
-                &crtc.saved_ramps[1],
-                &crtc.saved_ramps[2],
-            )
-        })
-        .map_err(AdjusterError::Restore)
+        self.set_gamma_ramps(|crtc| crtc.ramps.saved().clone())
+            .map_err(AdjusterError::Restore)?;
+        Ok(())
     }
 
     fn set(
         &self,
         reset_ramps: bool,
         cs: &ColorSettings,
+    ) -> Result<(), AdjusterError> {
+        self.refresh();
+        let ramps = self
+            .set_gamma_ramps(|crtc| {
+                let mut ramps =
+                    crtc.ramps.base(reset_ramps, crtc.ramp_size as u32);
+                ramps.colorramp_fill(cs);
+                ramps.to_ramps(crtc.ramp_size as u32, FULL_DEPTH)
+            })
+            .map_err(AdjusterError::Set)?;
+        // only ramps that were applied are compared with the current ones
+        for (crtc, r) in self.crtcs.borrow().iter().zip(ramps) {
+            crtc.ramps.written(r);
+        }
+        Ok(())
+    }
+
+    fn verify(
+        &self,
+        adopt_foreign: bool,
+    ) -> Result<Vec<(u32, RampsStatus)>, AdjusterError> {
+        let current = self.get_gamma_ramps().map_err(AdjusterError::Get)?;
+        Ok(self
+            .crtcs
+            .borrow()
+            .iter()
+            .zip(current)
+            .map(|(crtc, r)| (crtc.id, crtc.ramps.check(r, adopt_foreign)))
+            .collect())
+    }
+
+    fn saved_ramps(&self) -> Vec<(u32, GammaRamps)> {
+        let crtcs = self.crtcs.borrow();
+        crtcs
+            .iter()
+            .map(|c| (c.id, c.ramps.saved().clone()))
+            .collect()
+    }
+
+    fn set_ramps(
+        &self,
+        ramps: &[(u32, GammaRamps)],
     ) -> Result<(), AdjusterError> {
         self.set_gamma_ramps(|crtc| {
-            let mut ramps = if reset_ramps {
-                GammaRamps::new(crtc.ramp_size as u32)
-            } else {
-                crtc.saved_ramps.clone()
-            };
-
-            ramps.colorramp_fill(cs);
-            self.conn
-                .randr_set_crtc_gamma(crtc.id, &ramps[0], &ramps[1], &ramps[2])
+            ramps_of(ramps, crtc.id, &crtc.ramps.saved())
         })
-        .map_err(AdjusterError::Restore)
+        .map_err(AdjusterError::Set)?;
+        Ok(())
+    }
+
+    fn set_base(&self, ramps: &[(u32, GammaRamps)]) {
+        for crtc in self.crtcs.borrow().iter() {
+            crtc.ramps.rebase(ramps, crtc.id);
+        }
     }
 }
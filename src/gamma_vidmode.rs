@@ -19,9 +19,12 @@
 */
 
 use crate::{
-    calc_colorramp::GammaRamps,
+    calc_colorramp::{GammaRamps, FULL_DEPTH},
     error::{gamma::VidmodeError, AdjusterError, AdjusterErrorInner},
-    types::ColorSettings,
+    gamma_monitor::RampsState,
+    list::CrtcInfo,
+    snapshot::ramps_of,
+    types::{ColorSettings, RampsStatus},
     utils::InjectMapErr,
     Adjuster,
 };
@@ -35,7 +38,7 @@ pub struct Vidmode {
     conn: X11Connection,
     screen_num: u16,
     ramp_size: u16,
-    saved_ramps: GammaRamps,
+    ramps: RampsState,
 }
 
 impl Vidmode {
@@ -74,10 +77,14 @@ impl Vidmode {
             conn,
             screen_num,
             ramp_size,
-            saved_ramps,
+            ramps: RampsState::new(saved_ramps),
         })
     }
 
+    pub fn crtc_info(&self) -> CrtcInfo {
+        CrtcInfo::new(self.screen_num as u32, &self.ramps.saved())
+    }
+
     fn set_gamma_ramps(
         &self,
         ramps: &GammaRamps,
@@ -95,11 +102,21 @@ impl Vidmode {
             .inject_map_err(AdjusterErrorInner::Vidmode)?;
         Ok(())
     }
+
+    fn get_gamma_ramps(&self) -> Result<GammaRamps, AdjusterErrorInner> {
+        let ramp = self
+            .conn
+            .xf86vidmode_get_gamma_ramp(self.screen_num, self.ramp_size)
+            .inject_map_err(AdjusterErrorInner::Vidmode)?
+            .reply()
+            .inject_map_err(AdjusterErrorInner::Vidmode)?;
+        Ok(GammaRamps([ramp.red, ramp.green, ramp.blue]))
+    }
 }
 
 impl Adjuster for Vidmode {
     fn restore(&self) -> Result<(), AdjusterError> {
-        self.set_gamma_ramps(&self.saved_ramps)
+        self.set_gamma_ramps(&self.ramps.saved())
             .map_err(AdjusterError::Restore)
     }
 
@@ -108,13 +125,37 @@ impl Adjuster for Vidmode {
         reset_ramps: bool,
         cs: &ColorSettings,
     ) -> Result<(), AdjusterError> {
-        let mut ramps = if reset_ramps {
-            GammaRamps::new(self.ramp_size as u32)
-        } else {
-            self.saved_ramps.clone()
-        };
-
+        let mut ramps = self.ramps.base(reset_ramps, self.ramp_size as u32);
         ramps.colorramp_fill(cs);
+        let ramps = ramps.to_ramps(self.ramp_size as u32, FULL_DEPTH);
+        self.set_gamma_ramps(&ramps).map_err(AdjusterError::Set)?;
+        self.ramps.written(ramps);
+        Ok(())
+    }
+
+    fn verify(
+        &self,
+        adopt_foreign: bool,
+    ) -> Result<Vec<(u32, RampsStatus)>, AdjusterError> {
+        let current = self.get_gamma_ramps().map_err(AdjusterError::Get)?;
+        let status = self.ramps.check(current, adopt_foreign);
+        Ok(vec![(self.screen_num as u32, status)])
+    }
+
+    fn saved_ramps(&self) -> Vec<(u32, GammaRamps)> {
+        vec![(self.screen_num as u32, self.ramps.saved().clone())]
+    }
+
+    fn set_ramps(
+        &self,
+        ramps: &[(u32, GammaRamps)],
+    ) -> Result<(), AdjusterError> {
+        let id = self.screen_num as u32;
+        let ramps = ramps_of(ramps, id, &self.ramps.saved());
         self.set_gamma_ramps(&ramps).map_err(AdjusterError::Set)
     }
+
+    fn set_base(&self, ramps: &[(u32, GammaRamps)]) {
+        self.ramps.rebase(ramps, self.screen_num as u32);
+    }
 }
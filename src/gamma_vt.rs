@@ -0,0 +1,175 @@
+/*  gamma_vt.rs -- Linux console palette adjustment
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#![allow(unsafe_code)]
+
+use crate::{
+    calc_colorramp::GammaRampsFloat,
+    error::{gamma::VtError, AdjusterError, AdjusterErrorInner},
+    types::ColorSettings,
+    Adjuster,
+};
+use nix::{ioctl_read_bad, ioctl_write_ptr_bad};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+// See ioctl_console(2)
+const GIO_CMAP: u32 = 0x4B70;
+const PIO_CMAP: u32 = 0x4B71;
+
+/// Red, green and blue values of the 16 colors of the console
+type Palette = [u8; 48];
+
+ioctl_read_bad!(gio_cmap, GIO_CMAP, Palette);
+ioctl_write_ptr_bad!(pio_cmap, PIO_CMAP, Palette);
+
+/// Palette of the kernel (default_red, default_grn and default_blu in
+/// drivers/tty/vt/vt.c)
+#[rustfmt::skip]
+const DEFAULT_PALETTE: Palette = [
+    0x00, 0x00, 0x00,  0xaa, 0x00, 0x00,  0x00, 0xaa, 0x00,  0xaa, 0x55, 0x00,
+    0x00, 0x00, 0xaa,  0xaa, 0x00, 0xaa,  0x00, 0xaa, 0xaa,  0xaa, 0xaa, 0xaa,
+    0x55, 0x55, 0x55,  0xff, 0x55, 0x55,  0x55, 0xff, 0x55,  0xff, 0xff, 0x55,
+    0x55, 0x55, 0xff,  0xff, 0x55, 0xff,  0x55, 0xff, 0xff,  0xff, 0xff, 0xff,
+];
+
+/// Tints the 16 colors of the Linux console. The kernel shares one palette
+/// between all of the virtual terminals, so the tty only selects the device
+/// that is used to change it
+#[derive(Debug)]
+pub struct Vt {
+    tty: File,
+    path: PathBuf,
+    // Palette before the Vt object was created
+    saved: Palette,
+}
+
+impl Vt {
+    /// Without a tty number, the controlling terminal is used if it is a
+    /// virtual terminal and the foreground one (/dev/tty0) otherwise
+    pub fn new(tty_num: Option<usize>) -> Result<Self, VtError> {
+        match tty_num {
+            Some(n) => Self::open(format!("/dev/tty{n}")),
+            None => {
+                Self::open("/dev/tty").or_else(|_| Self::open("/dev/tty0"))
+            }
+        }
+    }
+
+    fn open(path: impl AsRef<Path>) -> Result<Self, VtError> {
+        let path = path.as_ref().to_path_buf();
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| VtError::OpenDeviceFailed(e, path.clone()))?;
+        // Fails with ENOTTY if the device is not a virtual terminal
+        let saved = get_palette(&tty)
+            .map_err(|e| VtError::GetPaletteFailed(e, path.clone()))?;
+        Ok(Self { tty, path, saved })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn set_palette(&self, palette: &Palette) -> io::Result<()> {
+        // SAFETY: the kernel reads exactly the size of Palette from the
+        // pointer, which is valid for the duration of the call
+        unsafe { pio_cmap(self.tty.as_raw_fd(), palette) }?;
+        Ok(())
+    }
+}
+
+fn get_palette(tty: &File) -> io::Result<Palette> {
+    let mut palette = [0; 48];
+    // SAFETY: the kernel writes exactly the size of Palette to the pointer,
+    // which is valid for the duration of the call
+    unsafe { gio_cmap(tty.as_raw_fd(), &mut palette) }?;
+    Ok(palette)
+}
+
+/// Apply the color settings to each color of the palette the same way they
+/// are applied to the gamma ramps
+fn palette_fill(palette: &Palette, cs: &ColorSettings) -> Palette {
+    let mut ramps = GammaRampsFloat([0, 1, 2].map(|c| {
+        palette
+            .iter()
+            .skip(c)
+            .step_by(3)
+            .map(|&v| v as f64 / u8::MAX as f64)
+            .collect()
+    }));
+    ramps.colorramp_fill(cs);
+
+    let mut filled = [0; 48];
+    for (i, v) in filled.iter_mut().enumerate() {
+        let r = ramps[i % 3][i / 3] * u8::MAX as f64;
+        *v = r.round().clamp(0.0, u8::MAX as f64) as u8;
+    }
+    filled
+}
+
+impl Adjuster for Vt {
+    fn restore(&self) -> Result<(), AdjusterError> {
+        self.set_palette(&self.saved)
+            .map_err(|e| AdjusterError::Restore(AdjusterErrorInner::Vt(e)))
+    }
+
+    fn set(
+        &self,
+        reset_ramps: bool,
+        cs: &ColorSettings,
+    ) -> Result<(), AdjusterError> {
+        let palette = match reset_ramps {
+            true => &DEFAULT_PALETTE,
+            false => &self.saved,
+        };
+        self.set_palette(&palette_fill(palette, cs))
+            .map_err(|e| AdjusterError::Set(AdjusterErrorInner::Vt(e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::Temperature;
+    use anyhow::Result;
+
+    #[test]
+    fn vt_palette_fill() -> Result<()> {
+        let cs = ColorSettings::default();
+        assert_eq!(palette_fill(&DEFAULT_PALETTE, &cs), DEFAULT_PALETTE);
+
+        let cs = ColorSettings {
+            temp: Temperature::try_from(3000)?,
+            ..Default::default()
+        };
+        let p = palette_fill(&DEFAULT_PALETTE, &cs);
+        // white keeps its red and loses some of its blue
+        assert_eq!(p[45], 0xff);
+        assert!(p[47] < p[46] && p[46] < p[45]);
+        // black stays black
+        assert_eq!(p[..3], [0, 0, 0]);
+        Ok(())
+    }
+}
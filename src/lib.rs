@@ -19,8 +19,6 @@
 */
 
 // TODO: add tldr page: https://github.com/tldr-pages/tldr
-// TODO: add setting screen brightness, a percentage of the current brightness
-//       see: https://github.com/qualiaa/redshift-hooks
 // TODO: ? benchmark: https://github.com/nvzqz/divan
 // TODO: Fix large fade steps
 // TODO: ? Box large errors
@@ -34,22 +32,45 @@
 mod calc_colorramp;
 mod calc_solar;
 mod cli;
+mod compat;
 mod config;
+mod config_import;
 mod coproduct;
+mod curve;
 mod error;
+mod export;
 
 #[cfg(unix_without_macos)]
 mod gamma_drm;
 #[cfg(unix_without_macos)]
+mod gamma_file;
+#[cfg(unix_without_macos)]
+mod gamma_kwin;
+#[cfg(unix_without_macos)]
+mod gamma_monitor;
+#[cfg(unix_without_macos)]
+mod gamma_mutter;
+#[cfg(unix_without_macos)]
+mod gamma_output;
+#[cfg(unix_without_macos)]
 mod gamma_randr;
 #[cfg(unix_without_macos)]
 mod gamma_vidmode;
+#[cfg(unix_without_macos)]
+mod gamma_vt;
 
 #[cfg(windows)]
 mod gamma_win32gdi;
 
 mod gamma_dummy;
+mod list;
 mod location_manual;
+mod location_timezone;
+#[cfg(unix_without_macos)]
+mod snapshot;
+mod state;
+#[cfg(unix_without_macos)]
+mod status;
 mod types;
 mod types_display;
 mod types_parse;
@@ -58,29 +79,46 @@ mod utils;
 #[cfg(windows)]
 use crate::gamma_win32gdi::Win32Gdi;
 #[cfg(unix_without_macos)]
-use crate::{gamma_drm::Drm, gamma_randr::Randr, gamma_vidmode::Vidmode};
+use crate::{
+    calc_colorramp::GammaRamps, gamma_drm::Drm, gamma_file::Recorder,
+    gamma_kwin::Kwin, gamma_mutter::Mutter, gamma_randr::Randr,
+    gamma_vidmode::Vidmode, gamma_vt::Vt, snapshot::run_snapshot_mode,
+    status::run_status_mode,
+};
 pub use cli::cli_args_command;
+use config_import::run_config_import_mode;
 use error::ReddishError;
+use export::run_export_mode;
 use gamma_dummy::Dummy;
 use itertools::Itertools;
+use list::run_list_mode;
 use location_manual::Manual;
+use location_timezone::Timezone;
 use types::Location;
 
 use crate::{
     cli::ClapColorChoiceExt,
     config::{Config, ConfigBuilder, FADE_STEPS},
     error::{AdjusterError, ProviderError},
-    types::{ColorSettings, Elevation, Mode, Period, PeriodInfo},
-    types_display::{BODY, HEADER},
+    types::{
+        ColorSettings, Elevation, Mode, Period, PeriodInfo, RampsStatus,
+        SnapshotMode,
+    },
+    types_display::{BODY, HEADER, WARN},
 };
 use anstream::AutoStream;
 use chrono::{DateTime, SubsecRound, TimeDelta};
+#[cfg(unix)]
+use std::sync::mpsc::Sender;
 use std::{
-    fmt::Debug,
-    io,
+    cell::Cell,
+    fmt::{Debug, Display},
+    io, panic,
+    path::PathBuf,
     sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
 };
-use tracing::{error, info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::fmt::writer::MakeWriterExt;
 
 pub fn main() {
@@ -98,48 +136,305 @@ pub fn main() {
                 .with_level(false)
                 .with_target(false)
                 .init();
-        })?
-        .build()?;
-
-        let (tx, rx) = mpsc::channel();
-        ctrlc::set_handler(move || {
-            #[allow(clippy::expect_used)]
-            tx.send(()).expect("Could not send signal on channel")
-        })
-        .or_else(|e| match c.mode {
-            Mode::Oneshot | Mode::Set | Mode::Reset | Mode::Print => Ok(()),
-            Mode::Daemon => Err(e),
         })?;
+        // These modes only need the configuration, the display is untouched
+        let c = match c.mode() {
+            Mode::ConfigShow => {
+                print_document(&c);
+                return Ok(());
+            }
+            Mode::ConfigCheck => {
+                run_config_check_mode(c.files());
+                return Ok(());
+            }
+            Mode::ConfigImport(path) => {
+                run_config_import_mode(path.as_deref())?;
+                return Ok(());
+            }
+            _ => c.build()?,
+        };
+        // The config lives until the program exits, the panic hook uses it
+        let c: &'static Config = Box::leak(Box::new(c));
 
-        run(&c, &rx)
+        let rx = set_signal_handler(c)?;
+        run(c, &rx)
     })()
-    .unwrap_or_else(|e| error!("{e}"))
+    .unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(e.exit_code())
+    })
 }
 
-fn run(c: &Config, sig: &Receiver<()>) -> Result<(), ReddishError> {
-    match c.mode {
-        Mode::Daemon => {
-            info!("{c}\n{HEADER}Current{HEADER:#}:");
-            DaemonMode::new(c, sig).run_loop()?;
-            c.method.restore()?;
-        }
-        Mode::Oneshot => {
-            // Use period and transition progress to set color temperature
-            let (p, i) = Period::from(&c.scheme, &c.location, c.time)?;
-            let interp = c.night.interpolate_with(&c.day, p.into());
-            info!("{c}\n{HEADER}Current{HEADER:#}:\n{p}\n{i}\n{interp}");
-            c.method.set(c.reset_ramps, &interp)?;
+/// Receive SIGINT, SIGTERM, SIGHUP and SIGQUIT on a channel. Only the daemon
+/// requires it, other modes run without handling signals if it fails
+fn set_signal_handler(c: &Config) -> Result<Receiver<()>, ReddishError> {
+    let (tx, rx) = mpsc::channel();
+    // must be called before any other thread is spawned
+    #[cfg(unix)]
+    if let Mode::Daemon
+    | Mode::Oneshot
+    | Mode::Set
+    | Mode::Reset { .. }
+    | Mode::Snapshot(SnapshotMode::Restore(_))
+    | Mode::Replay { .. } = c.mode
+    {
+        handle_sigquit(tx.clone()).or_else(|e| match c.mode {
+            Mode::Daemon => Err(e),
+            _ => Ok(()),
+        })?;
+    }
+    ctrlc::set_handler(move || {
+        #[allow(clippy::expect_used)]
+        tx.send(()).expect("Could not send signal on channel")
+    })
+    .or_else(|e| match c.mode {
+        Mode::Oneshot
+        | Mode::Set
+        | Mode::Reset { .. }
+        | Mode::Print
+        | Mode::List { .. }
+        | Mode::ConfigShow
+        | Mode::ConfigCheck
+        | Mode::ConfigImport(_)
+        | Mode::Snapshot(_)
+        | Mode::Status
+        | Mode::Export { .. }
+        | Mode::Replay { .. } => Ok(()),
+        Mode::Daemon => Err(e),
+    })?;
+    Ok(rx)
+}
+
+/// SIGQUIT is not handled by the ctrlc crate. It is blocked and received on
+/// a separate thread instead, so it is handled like SIGINT and SIGTERM
+#[cfg(unix)]
+fn handle_sigquit(tx: Sender<()>) -> Result<(), ReddishError> {
+    use nix::sys::signal::{SigSet, Signal};
+    let mut set = SigSet::empty();
+    set.add(Signal::SIGQUIT);
+    set.thread_block()?;
+    std::thread::spawn(
+        move || {
+            while set.wait().is_ok() && tx.send(()).is_ok() {}
+        },
+    );
+    Ok(())
+}
+
+fn run(c: &'static Config, sig: &Receiver<()>) -> Result<(), ReddishError> {
+    if let Mode::Daemon
+    | Mode::Oneshot
+    | Mode::Set
+    | Mode::Reset { .. }
+    | Mode::Snapshot(SnapshotMode::Restore(_))
+    | Mode::Replay { .. } = c.mode
+    {
+        restore_on_panic(c);
+    }
+
+    // Keep the original ramps in case this process gets killed or the
+    // ramps are left adjusted on purpose. see: reset --original
+    #[cfg(unix_without_macos)]
+    if let Mode::Daemon
+    | Mode::Oneshot
+    | Mode::Set
+    | Mode::Reset { original: false }
+    | Mode::Snapshot(SnapshotMode::Restore(_))
+    | Mode::Replay { .. } = c.mode
+    {
+        snapshot::save_original(&c.original_key, c.method.saved_ramps());
+    }
+
+    run_mode(c, sig)
+}
+
+thread_local! {
+    // Config of a mode that adjusts the ramps, see: restore_on_panic
+    static ADJUSTING: Cell<Option<&'static Config>> = const { Cell::new(None) };
+}
+
+/// Restore the ramps if the program panics after this is called. The ramps
+/// are only adjusted on the main thread, which is where the hook runs if the
+/// adjustment panics
+fn restore_on_panic(c: &'static Config) {
+    ADJUSTING.set(Some(c));
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(c) = ADJUSTING.take() {
+            if let Err(e) = c.method.restore() {
+                error!("{e}");
+            }
         }
+        hook(info);
+    }));
+}
+
+fn run_mode(c: &Config, sig: &Receiver<()>) -> Result<(), ReddishError> {
+    match c.mode {
+        Mode::Daemon => run_daemon_mode(c, sig)?,
+        Mode::Oneshot => run_oneshot_mode(c, sig)?,
         Mode::Set => {
             // for this command, color settings are stored in the day field
-            c.method.set(c.reset_ramps, &c.day)?;
+            fade_to(c, sig, c.reset_ramps, &c.day)?;
+            state::save(&c.state_key, Some(&c.day));
         }
-        Mode::Reset => {
-            c.method.set(true, &ColorSettings::default())?;
+        Mode::Reset { original: false } => {
+            fade_to(c, sig, true, &ColorSettings::default())?;
+            state::save(&c.state_key, None);
         }
+        Mode::Reset { original: true } => run_reset_original_mode(c)?,
         Mode::Print => run_print_mode(c)?,
+        Mode::List { json } => run_list_mode(json)?,
+        #[cfg(unix_without_macos)]
+        Mode::Snapshot(ref m) => run_snapshot_mode(c, m)?,
+        #[cfg(not(unix_without_macos))]
+        Mode::Snapshot(_) => warn_unsupported("snapshots are"),
+        #[cfg(unix_without_macos)]
+        Mode::Status => run_status_mode(c)?,
+        #[cfg(not(unix_without_macos))]
+        Mode::Status => warn_unsupported("reading gamma ramps is"),
+        Mode::Export {
+            format,
+            size,
+            ref output,
+        } => run_export_mode(c, format, size, output.as_deref())?,
+        #[cfg(unix_without_macos)]
+        Mode::Replay { ref path, delay } => {
+            gamma_file::run_replay_mode(c, sig, path, delay)?;
+        }
+        #[cfg(not(unix_without_macos))]
+        Mode::Replay { .. } => warn_unsupported("replaying recordings is"),
+        // handled before the adjustment method is initialized
+        Mode::ConfigShow | Mode::ConfigCheck | Mode::ConfigImport(_) => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix_without_macos))]
+fn warn_unsupported(s: &str) {
+    warn!("{WARN}warning:{WARN:#} {s} not supported on this platform");
+}
+
+fn run_daemon_mode(
+    c: &Config,
+    sig: &Receiver<()>,
+) -> Result<(), ReddishError> {
+    info!("{c}\n{HEADER}Current{HEADER:#}:");
+    // the daemon fades from the color settings that were applied last
+    #[cfg(unix_without_macos)]
+    if state::load(&c.state_key).is_some() {
+        rebase(&c.method, &original_ramps(c));
     }
+    // The ramps are restored on errors too
+    let r = DaemonMode::new(c, sig).run_loop();
+    let restored = c.method.restore();
+    r?;
+    restored?;
+    state::save(&c.state_key, None);
+    Ok(())
+}
 
+fn run_oneshot_mode(
+    c: &Config,
+    sig: &Receiver<()>,
+) -> Result<(), ReddishError> {
+    // Use period and transition progress to set color temperature
+    let (p, i) = Period::from(&c.scheme, &c.location, c.time)?;
+    let interp = c.night.interpolate_with(&c.day, p.into());
+    info!("{c}\n{HEADER}Current{HEADER:#}:\n{p}\n{i}\n{interp}");
+    fade_to(c, sig, c.reset_ramps, &interp)?;
+    state::save(&c.state_key, Some(&interp));
+    Ok(())
+}
+
+/// Set the color settings, fading from the current color settings if a fade
+/// duration is set. A signal ends the fade with the target color settings
+fn fade_to(
+    c: &Config,
+    sig: &Receiver<()>,
+    reset_ramps: bool,
+    target: &ColorSettings,
+) -> Result<(), ReddishError> {
+    // The ramps on screen are the result of the current color settings, so
+    // every step is applied on top of the ramps from before them instead
+    let start = current_color_settings(c);
+    #[cfg(unix_without_macos)]
+    if start.is_some() {
+        rebase(&c.method, &original_ramps(c));
+    }
+    if let Some(duration) = c.fade {
+        let start = start.unwrap_or_default();
+        let sleep_duration = duration / FADE_STEPS as u32;
+        for step in 0..FADE_STEPS {
+            let cs = DaemonMode::interpolate(&start, target, step);
+            c.method.set(reset_ramps, &cs)?;
+            match sig.recv_timeout(sleep_duration) {
+                Ok(()) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+                // setting the signal handler is allowed to fail in this mode
+                Err(RecvTimeoutError::Disconnected) => {
+                    thread::sleep(sleep_duration);
+                }
+            }
+        }
+    }
+    Ok(c.method.set(reset_ramps, target)?)
+}
+
+/// Color settings that were applied last, or the estimated color settings
+/// of the gamma ramps if they were not saved. None if the gamma ramps don't
+/// fit any color settings, e.g. the calibration of a display
+fn current_color_settings(c: &Config) -> Option<ColorSettings> {
+    if let Some(cs) = state::load(&c.state_key) {
+        return Some(cs);
+    }
+    #[cfg(unix_without_macos)]
+    if let Some((_, ramps)) = c.method.saved_ramps().first() {
+        let (cs, error) = ramps.estimate();
+        if error <= status::FIT_TOLERANCE {
+            return Some(cs);
+        }
+    }
+    None
+}
+
+/// Color settings are applied on top of the saved ramps. If the saved ramps
+/// are the result of an earlier adjustment, the adjustments would add up, so
+/// the original ramps are used as the base instead, or linear ramps for the
+/// crtcs without original ramps
+#[cfg(unix_without_macos)]
+fn rebase(method: &impl Adjuster, original: &[(u32, GammaRamps)]) {
+    let ramps = method.saved_ramps().into_iter().map(|(id, r)| {
+        let linear = GammaRamps::new(r[0].len() as u32);
+        (id, snapshot::ramps_of(original, id, &linear))
+    });
+    method.set_base(&ramps.collect_vec());
+}
+
+/// Ramps from before the first adjustment, if they were saved
+#[cfg(unix_without_macos)]
+fn original_ramps(c: &Config) -> Vec<(u32, GammaRamps)> {
+    let s = snapshot::load_original(&c.original_key);
+    s.map(|s| s.ramps()).unwrap_or_default()
+}
+
+#[cfg(unix_without_macos)]
+fn run_reset_original_mode(c: &Config) -> Result<(), ReddishError> {
+    let s = snapshot::load_original(&c.original_key)?;
+    c.method.set_ramps(&s.ramps())?;
+    let ids = c.method.saved_ramps().into_iter().map(|(id, _)| id);
+    snapshot::remove_original(&c.original_key, &ids.collect_vec());
+    state::save(&c.state_key, None);
+    Ok(())
+}
+
+#[cfg(not(unix_without_macos))]
+fn run_reset_original_mode(c: &Config) -> Result<(), ReddishError> {
+    let s = "original gamma ramps are not saved on this platform";
+    warn!("{WARN}warning:{WARN:#} {s}");
+    c.method.set(true, &ColorSettings::default())?;
+    state::save(&c.state_key, None);
     Ok(())
 }
 
@@ -156,6 +451,22 @@ fn run_print_mode(c: &Config) -> Result<(), ReddishError> {
     Ok(info!("{}", buf.join("\n")))
 }
 
+/// Documents such as config files or JSON are the result of the commands
+/// that print them, not logs. They are written to stdout directly so that
+/// --quiet does not suppress them and they can be piped to other programs
+fn print_document(s: impl Display) {
+    println!("{s}");
+}
+
+fn run_config_check_mode(files: &[PathBuf]) {
+    if files.is_empty() {
+        info!("no configuration file found");
+    }
+    for path in files {
+        info!("{BODY}{}{BODY:#}: ok", path.display());
+    }
+}
+
 #[derive(Debug)]
 struct DaemonMode<'a, 'b> {
     cfg: &'a Config,
@@ -184,7 +495,8 @@ impl<'a, 'b> DaemonMode<'a, 'b> {
             fade: Default::default(),
             period: Default::default(),
             info: Default::default(),
-            interp: Default::default(),
+            // fade from the color settings that were applied last
+            interp: state::load(&cfg.state_key).unwrap_or_default(),
             prev_period: Default::default(),
             prev_info: Default::default(),
             prev_interp: Default::default(),
@@ -211,6 +523,12 @@ impl<'a, 'b> DaemonMode<'a, 'b> {
 
             self.log();
 
+            // Check if another program has changed the ramps since the last
+            // update. Ramps are reapplied below anyway
+            if c.monitor_ramps && self.fade == FadeStatus::Completed {
+                self.verify_ramps();
+            }
+
             // // Activate hooks if period changed
             // if period != prev_period {
             //     hooks_signal_period_change(prev_period, period);
@@ -242,6 +560,34 @@ impl<'a, 'b> DaemonMode<'a, 'b> {
         }
     }
 
+    /// Failing to read the ramps back is not fatal, they are checked again
+    /// on the next iteration
+    fn verify_ramps(&self) {
+        let c = self.cfg;
+        let statuses = match c.method.verify(c.adopt_foreign_ramps) {
+            Ok(statuses) => statuses,
+            Err(e) => {
+                let w = format!("unable to verify the gamma ramps: {e}");
+                warn!("{WARN}warning:{WARN:#} {w}");
+                return;
+            }
+        };
+        for (id, status) in statuses {
+            let s = match status {
+                RampsStatus::Intact => continue,
+                RampsStatus::Overwritten => {
+                    let s = "were overwritten by another program, reapplying";
+                    info!("Gamma ramps of crtc {id} {s}");
+                    continue;
+                }
+                RampsStatus::Foreign => "",
+                RampsStatus::Adopted => ", adopted as the new base",
+            };
+            let w = format!("foreign gamma ramps persist on crtc {id}{s}");
+            warn!("{WARN}warning:{WARN:#} {w}");
+        }
+    }
+
     fn next_interpolate(
         &self,
         target: ColorSettings,
@@ -310,6 +656,32 @@ trait Adjuster {
         reset_ramps: bool,
         cs: &ColorSettings,
     ) -> Result<(), AdjusterError>;
+    /// Read back the current gamma ramps and compare them with the ramps that
+    /// were last set. Returns the status of each crtc
+    fn verify(
+        &self,
+        _adopt_foreign: bool,
+    ) -> Result<Vec<(u32, RampsStatus)>, AdjusterError> {
+        Ok(Vec::new())
+    }
+    /// Gamma ramps of each crtc before the Adjuster object was created
+    #[cfg(unix_without_macos)]
+    fn saved_ramps(&self) -> Vec<(u32, GammaRamps)> {
+        Vec::new()
+    }
+    /// Set the gamma ramps of the crtcs with the same id. Other crtcs are
+    /// left unchanged
+    #[cfg(unix_without_macos)]
+    fn set_ramps(
+        &self,
+        _ramps: &[(u32, GammaRamps)],
+    ) -> Result<(), AdjusterError> {
+        Ok(())
+    }
+    /// Replace the saved ramps of the crtcs with the same id, which color
+    /// settings are applied on top of. Other crtcs are left unchanged
+    #[cfg(unix_without_macos)]
+    fn set_base(&self, _ramps: &[(u32, GammaRamps)]) {}
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -337,17 +709,26 @@ impl Default for FadeStatus {
 pub enum LocationProvider {
     Manual(Manual),
     Geoclue2(Geoclue2),
+    Timezone(Timezone),
 }
 
 #[derive(Debug)]
 pub enum AdjustmentMethod {
     Dummy(Dummy),
     #[cfg(unix_without_macos)]
-    Randr(Randr),
+    Randr(Box<Randr>),
     #[cfg(unix_without_macos)]
     Drm(Drm),
     #[cfg(unix_without_macos)]
-    Vidmode(Vidmode),
+    Vidmode(Box<Vidmode>),
+    #[cfg(unix_without_macos)]
+    Mutter(Mutter),
+    #[cfg(unix_without_macos)]
+    Kwin(Kwin),
+    #[cfg(unix_without_macos)]
+    Vt(Vt),
+    #[cfg(unix_without_macos)]
+    File(Recorder),
     #[cfg(windows)]
     Win32Gdi(Win32Gdi),
 }
@@ -370,6 +751,7 @@ impl Provider for LocationProvider {
         match self {
             Self::Manual(t) => t.get(),
             Self::Geoclue2(t) => t.get(),
+            Self::Timezone(t) => t.get(),
         }
     }
 }
@@ -384,11 +766,89 @@ impl Adjuster for AdjustmentMethod {
             Self::Drm(t) => t.restore(),
             #[cfg(unix_without_macos)]
             Self::Vidmode(t) => t.restore(),
+            #[cfg(unix_without_macos)]
+            Self::Mutter(t) => t.restore(),
+            #[cfg(unix_without_macos)]
+            Self::Kwin(t) => t.restore(),
+            #[cfg(unix_without_macos)]
+            Self::Vt(t) => t.restore(),
+            #[cfg(unix_without_macos)]
+            Self::File(t) => t.restore(),
             #[cfg(windows)]
             Self::Win32Gdi(t) => t.restore(),
         }
     }
 
+    fn verify(
+        &self,
+        adopt_foreign: bool,
+    ) -> Result<Vec<(u32, RampsStatus)>, AdjusterError> {
+        match self {
+            Self::Dummy(t) => t.verify(adopt_foreign),
+            #[cfg(unix_without_macos)]
+            Self::Randr(t) => t.verify(adopt_foreign),
+            #[cfg(unix_without_macos)]
+            Self::Drm(t) => t.verify(adopt_foreign),
+            #[cfg(unix_without_macos)]
+            Self::Vidmode(t) => t.verify(adopt_foreign),
+            #[cfg(unix_without_macos)]
+            Self::Mutter(t) => t.verify(adopt_foreign),
+            #[cfg(unix_without_macos)]
+            Self::Kwin(t) => t.verify(adopt_foreign),
+            #[cfg(unix_without_macos)]
+            Self::Vt(t) => t.verify(adopt_foreign),
+            #[cfg(unix_without_macos)]
+            Self::File(t) => t.verify(adopt_foreign),
+            #[cfg(windows)]
+            Self::Win32Gdi(t) => t.verify(adopt_foreign),
+        }
+    }
+
+    #[cfg(unix_without_macos)]
+    fn saved_ramps(&self) -> Vec<(u32, GammaRamps)> {
+        match self {
+            Self::Dummy(t) => t.saved_ramps(),
+            Self::Randr(t) => t.saved_ramps(),
+            Self::Drm(t) => t.saved_ramps(),
+            Self::Vidmode(t) => t.saved_ramps(),
+            Self::Mutter(t) => t.saved_ramps(),
+            Self::Kwin(t) => t.saved_ramps(),
+            Self::Vt(t) => t.saved_ramps(),
+            Self::File(t) => t.saved_ramps(),
+        }
+    }
+
+    #[cfg(unix_without_macos)]
+    fn set_ramps(
+        &self,
+        ramps: &[(u32, GammaRamps)],
+    ) -> Result<(), AdjusterError> {
+        match self {
+            Self::Dummy(t) => t.set_ramps(ramps),
+            Self::Randr(t) => t.set_ramps(ramps),
+            Self::Drm(t) => t.set_ramps(ramps),
+            Self::Vidmode(t) => t.set_ramps(ramps),
+            Self::Mutter(t) => t.set_ramps(ramps),
+            Self::Kwin(t) => t.set_ramps(ramps),
+            Self::Vt(t) => t.set_ramps(ramps),
+            Self::File(t) => t.set_ramps(ramps),
+        }
+    }
+
+    #[cfg(unix_without_macos)]
+    fn set_base(&self, ramps: &[(u32, GammaRamps)]) {
+        match self {
+            Self::Dummy(t) => t.set_base(ramps),
+            Self::Randr(t) => t.set_base(ramps),
+            Self::Drm(t) => t.set_base(ramps),
+            Self::Vidmode(t) => t.set_base(ramps),
+            Self::Mutter(t) => t.set_base(ramps),
+            Self::Kwin(t) => t.set_base(ramps),
+            Self::Vt(t) => t.set_base(ramps),
+            Self::File(t) => t.set_base(ramps),
+        }
+    }
+
     fn set(
         &self,
         reset_ramps: bool,
@@ -402,6 +862,14 @@ impl Adjuster for AdjustmentMethod {
             Self::Drm(t) => t.set(reset_ramps, cs),
             #[cfg(unix_without_macos)]
             Self::Vidmode(t) => t.set(reset_ramps, cs),
+            #[cfg(unix_without_macos)]
+            Self::Mutter(t) => t.set(reset_ramps, cs),
+            #[cfg(unix_without_macos)]
+            Self::Kwin(t) => t.set(reset_ramps, cs),
+            #[cfg(unix_without_macos)]
+            Self::Vt(t) => t.set(reset_ramps, cs),
+            #[cfg(unix_without_macos)]
+            Self::File(t) => t.set(reset_ramps, cs),
             #[cfg(windows)]
             Self::Win32Gdi(t) => t.set(reset_ramps, cs),
             // #[cfg(macos)]
@@ -0,0 +1,398 @@
+/*  list.rs -- List adjustment methods, screens, crtcs and outputs
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(windows)]
+use crate::gamma_win32gdi::Win32Gdi;
+#[cfg(unix_without_macos)]
+use crate::{
+    calc_colorramp::GammaRamps,
+    gamma_drm::Drm,
+    gamma_kwin::Kwin,
+    gamma_mutter::Mutter,
+    gamma_output::{Edid, Output},
+    gamma_randr::Randr,
+    gamma_vidmode::Vidmode,
+    gamma_vt::Vt,
+};
+
+use crate::{
+    error::ReddishError,
+    print_document,
+    types_display::{BODY, ERR, HEADER},
+};
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+use tracing::info;
+
+#[derive(Debug, Serialize)]
+pub struct MethodInfo {
+    pub name: &'static str,
+    /// Reason the method is not usable. None if it is usable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub screens: Vec<ScreenInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScreenInfo {
+    /// X display or path of the DRM device
+    pub name: String,
+    pub crtcs: Vec<CrtcInfo>,
+    #[cfg(unix_without_macos)]
+    pub outputs: Vec<Output>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrtcInfo {
+    pub id: u32,
+    pub ramp_size: u32,
+    /// Last value of each ramp. 1.0 means the channel is not dimmed
+    pub white_point: [f64; 3],
+    /// True if the ramps are not the identity ramps
+    pub adjusted: bool,
+}
+
+#[cfg(unix_without_macos)]
+impl CrtcInfo {
+    pub fn new(id: u32, ramps: &GammaRamps) -> Self {
+        let ramp_size = ramps[0].len() as u32;
+        let white_point = [0, 1, 2].map(|c| {
+            let last = ramps[c].last().copied().unwrap_or_default();
+            last as f64 / u16::MAX as f64
+        });
+        Self {
+            id,
+            ramp_size,
+            white_point,
+            adjusted: !ramps.is_close_to(&GammaRamps::new(ramp_size)),
+        }
+    }
+}
+
+impl MethodInfo {
+    fn new<E: Display>(
+        name: &'static str,
+        screens: Result<Vec<ScreenInfo>, E>,
+    ) -> Self {
+        match screens {
+            Ok(screens) => Self {
+                name,
+                error: None,
+                screens,
+            },
+            Err(e) => Self {
+                name,
+                error: Some(e.to_string()),
+                screens: Vec::new(),
+            },
+        }
+    }
+}
+
+pub fn run_list_mode(json: bool) -> Result<(), ReddishError> {
+    let methods = list_methods();
+    if json {
+        print_document(serde_json::to_string_pretty(&methods)?);
+    } else {
+        info!("{}", methods.iter().join("\n"));
+    }
+    Ok(())
+}
+
+fn list_methods() -> Vec<MethodInfo> {
+    let mut methods = Vec::new();
+
+    #[cfg(unix_without_macos)]
+    methods.extend([
+        list_randr(),
+        list_vidmode(),
+        MethodInfo::new("drm", list_drm_cards()),
+        list_mutter(),
+        list_kwin(),
+        list_vt(),
+    ]);
+
+    #[cfg(windows)]
+    methods.push(MethodInfo::new(
+        "win32gdi",
+        Win32Gdi::new().map(|_| {
+            vec![ScreenInfo {
+                name: "current display".into(),
+                crtcs: Vec::new(),
+            }]
+        }),
+    ));
+
+    methods.push(MethodInfo::new("dummy", Ok::<_, String>(Vec::new())));
+    methods
+}
+
+#[cfg(unix_without_macos)]
+fn list_randr() -> MethodInfo {
+    let display = std::env::var("DISPLAY").unwrap_or_default();
+    let screens = Randr::new(None, Vec::new()).and_then(|m| {
+        Ok(vec![ScreenInfo {
+            name: display,
+            crtcs: m.crtcs_info(),
+            outputs: m.outputs()?,
+        }])
+    });
+    MethodInfo::new("randr", screens)
+}
+
+#[cfg(unix_without_macos)]
+fn list_vidmode() -> MethodInfo {
+    let display = std::env::var("DISPLAY").unwrap_or_default();
+    let screens = Vidmode::new(None).map(|m| {
+        vec![ScreenInfo {
+            name: display,
+            crtcs: vec![m.crtc_info()],
+            outputs: Vec::new(),
+        }]
+    });
+    MethodInfo::new("vidmode", screens)
+}
+
+#[cfg(unix_without_macos)]
+fn list_mutter() -> MethodInfo {
+    let screens = Mutter::new(Vec::new()).and_then(|m| {
+        Ok(vec![ScreenInfo {
+            name: "session bus".into(),
+            crtcs: m.crtcs_info(),
+            outputs: m.outputs()?,
+        }])
+    });
+    MethodInfo::new("mutter", screens)
+}
+
+/// KWin adjusts all of the outputs together
+#[cfg(unix_without_macos)]
+fn list_kwin() -> MethodInfo {
+    let screens = Kwin::new().map(|_| {
+        vec![ScreenInfo {
+            name: "session bus".into(),
+            crtcs: Vec::new(),
+            outputs: Vec::new(),
+        }]
+    });
+    MethodInfo::new("kwin", screens)
+}
+
+/// The console palette has no crtcs, it applies to all of the consoles
+#[cfg(unix_without_macos)]
+fn list_vt() -> MethodInfo {
+    let screens = Vt::new(None).map(|m| {
+        vec![ScreenInfo {
+            name: m.path().display().to_string(),
+            crtcs: Vec::new(),
+            outputs: Vec::new(),
+        }]
+    });
+    MethodInfo::new("vt", screens)
+}
+
+/// Returns the error of the first card if none of them are usable
+#[cfg(unix_without_macos)]
+fn list_drm_cards() -> Result<Vec<ScreenInfo>, String> {
+    let mut cards = std::fs::read_dir("/dev/dri")
+        .into_iter()
+        .flatten()
+        .filter_map(|e| {
+            let name = e.ok()?.file_name().into_string().ok()?;
+            name.strip_prefix("card")?.parse::<usize>().ok()
+        })
+        .collect_vec();
+    cards.sort();
+    if cards.is_empty() {
+        // Report why the default card can not be used
+        cards.push(0);
+    }
+
+    let mut error = None;
+    let mut screens = Vec::new();
+    for n in cards {
+        let r = Drm::new(Some(n), Vec::new(), None).and_then(|m| {
+            Ok(ScreenInfo {
+                name: format!("/dev/dri/card{n}"),
+                crtcs: m.crtcs_info(),
+                outputs: m.outputs()?,
+            })
+        });
+        match r {
+            Ok(s) => screens.push(s),
+            Err(e) => {
+                error.get_or_insert(e.to_string());
+            }
+        }
+    }
+
+    match (screens.is_empty(), error) {
+        (true, Some(e)) => Err(e),
+        _ => Ok(screens),
+    }
+}
+
+//
+
+impl Display for MethodInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self {
+            name,
+            error,
+            screens,
+        } = self;
+
+        match error {
+            None => write!(f, "{HEADER}{name}{HEADER:#}: usable")?,
+            Some(e) => write!(
+                f,
+                "{HEADER}{name}{HEADER:#}: {ERR}not usable{ERR:#}\n  {}",
+                e.lines().join("\n  ")
+            )?,
+        }
+        screens.iter().try_for_each(|s| write!(f, "\n{s}"))
+    }
+}
+
+impl Display for ScreenInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "  {BODY}Screen{BODY:#}: {}", self.name)?;
+        self.crtcs.iter().try_for_each(|c| write!(f, "\n{c}"))?;
+        #[cfg(unix_without_macos)]
+        self.outputs.iter().try_for_each(|o| write!(f, "\n{o}"))?;
+        Ok(())
+    }
+}
+
+impl Display for CrtcInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self {
+            id,
+            ramp_size,
+            white_point: [r, g, b],
+            adjusted,
+        } = self;
+        let state = match adjusted {
+            true => "adjusted",
+            false => "not adjusted",
+        };
+        write!(
+            f,
+            "    {BODY}Crtc{BODY:#} {id}: ramp size {ramp_size}, \
+            white point {r:.2}:{g:.2}:{b:.2} ({state})"
+        )
+    }
+}
+
+#[cfg(unix_without_macos)]
+impl Display for Output {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self {
+            name,
+            connected,
+            crtc,
+            edid,
+        } = self;
+        write!(f, "    {BODY}Output{BODY:#} {name}: ")?;
+        match (connected, crtc) {
+            (false, _) => write!(f, "disconnected")?,
+            (true, Some(crtc)) => write!(f, "crtc {crtc}")?,
+            (true, None) => write!(f, "disabled")?,
+        }
+        match edid {
+            Some(Edid {
+                manufacturer,
+                serial,
+                model,
+            }) => {
+                write!(f, ", edid={manufacturer}")?;
+                if let Some(serial) = serial {
+                    write!(f, "/{serial}")?;
+                }
+                if let Some(model) = model {
+                    write!(f, " ({model})")?;
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(unix_without_macos)]
+mod test {
+    use super::*;
+    use anyhow::Result;
+    use serde_json::json;
+
+    #[test]
+    fn list_json() -> Result<()> {
+        let mut ramps = GammaRamps::new(4);
+        ramps.0[2][3] = 0;
+        let screen = ScreenInfo {
+            name: "/dev/dri/card0".into(),
+            crtcs: vec![CrtcInfo::new(41, &ramps)],
+            outputs: vec![Output {
+                name: "DP-1".into(),
+                connected: true,
+                crtc: Some(41),
+                edid: Some(Edid {
+                    manufacturer: "DEL".into(),
+                    serial: Some("ABC123".into()),
+                    model: None,
+                }),
+            }],
+        };
+        let usable = MethodInfo::new("drm", Ok::<_, String>(vec![screen]));
+        let expected = json!({
+            "name": "drm",
+            "screens": [{
+                "name": "/dev/dri/card0",
+                "crtcs": [{
+                    "id": 41,
+                    "ramp_size": 4,
+                    "white_point": [1.0, 1.0, 0.0],
+                    "adjusted": true,
+                }],
+                "outputs": [{
+                    "name": "DP-1",
+                    "connected": true,
+                    "crtc": 41,
+                    "edid": {
+                        "manufacturer": "DEL",
+                        "serial": "ABC123",
+                        "model": null,
+                    },
+                }],
+            }],
+        });
+        assert_eq!(serde_json::to_value(&usable)?, expected);
+
+        let error = "cannot open display".to_string();
+        let unusable = MethodInfo::new("randr", Err::<Vec<_>, _>(error));
+        let expected = json!({
+            "name": "randr",
+            "error": "cannot open display",
+            "screens": [],
+        });
+        assert_eq!(serde_json::to_value(&unusable)?, expected);
+        Ok(())
+    }
+}
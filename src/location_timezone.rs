@@ -0,0 +1,181 @@
+/*  location_timezone.rs -- Location of the system timezone
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    error::{ProviderError, TimezoneError},
+    types::Location,
+    Provider,
+};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// Tables of the tz database with the coordinates of the principal city of
+// each timezone. zone.tab has a row for each country of a timezone
+const ZONE_TABLES: [&str; 2] = ["zone1970.tab", "zone.tab"];
+const ZONEINFO_DIRS: [&str; 3] = [
+    "/usr/share/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+    "/usr/lib/zoneinfo",
+];
+// Used if the tz database is not installed, e.g. on Windows
+const BUNDLED_TABLE: &str = include_str!("../assets/zone1970.tab");
+
+/// Coordinates of the principal city of the timezone of the system. They
+/// are usually close enough to get the times of dawn and dusk right
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timezone {
+    zone: String,
+    location: Location,
+}
+
+impl Timezone {
+    pub fn new() -> Result<Self, TimezoneError> {
+        let zone = zone().ok_or(TimezoneError::ZoneNotFound)?;
+        // links such as Asia/Calcutta are not in the tables
+        let location = lookup(&zone)
+            .or_else(|| {
+                let mut dirs = zoneinfo_dirs().into_iter();
+                dirs.find_map(|d| lookup(&zone_of_path(&d.join(&zone))?))
+            })
+            .ok_or_else(|| TimezoneError::NoLocation(zone.clone()))?;
+        Ok(Self { zone, location })
+    }
+
+    pub fn zone(&self) -> &str {
+        &self.zone
+    }
+}
+
+impl Provider for Timezone {
+    fn get(&self) -> Result<Location, ProviderError> {
+        Ok(self.location)
+    }
+}
+
+/// Name of the timezone of the system, e.g. Europe/Berlin. TZ is either a
+/// name or a path of a file in the tz database, optionally prefixed with a
+/// colon
+fn zone() -> Option<String> {
+    match env::var("TZ") {
+        Ok(tz) if !tz.trim_start_matches(':').is_empty() => {
+            let tz = tz.trim_start_matches(':');
+            match tz.starts_with('/') {
+                true => zone_of_path(Path::new(tz)),
+                false => Some(tz.to_string()),
+            }
+        }
+        _ => zone_of_path(Path::new("/etc/localtime")).or_else(|| {
+            let s = fs::read_to_string("/etc/timezone").ok()?;
+            Some(s.trim().to_string()).filter(|s| !s.is_empty())
+        }),
+    }
+}
+
+/// Name of a timezone from the path of its file. Symlinks are followed, which
+/// also resolves the links of the tz database to the canonical names
+fn zone_of_path(path: &Path) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    let path = path.to_str()?;
+    let (_, zone) = path.rsplit_once("zoneinfo/")?;
+    let zone = zone.strip_prefix("posix/").unwrap_or(zone);
+    let zone = zone.strip_prefix("right/").unwrap_or(zone);
+    Some(zone.to_string())
+}
+
+fn zoneinfo_dirs() -> Vec<PathBuf> {
+    let tzdir = env::var_os("TZDIR").map(PathBuf::from);
+    tzdir
+        .into_iter()
+        .chain(ZONEINFO_DIRS.map(PathBuf::from))
+        .collect()
+}
+
+/// Search the tables of the system first, they are likely to be newer
+fn lookup(zone: &str) -> Option<Location> {
+    let tables = zoneinfo_dirs()
+        .into_iter()
+        .flat_map(|d| ZONE_TABLES.map(|t| fs::read_to_string(d.join(t)).ok()));
+    tables
+        .flatten()
+        .find_map(|t| find(&t, zone))
+        .or_else(|| find(BUNDLED_TABLE, zone))
+}
+
+/// Rows are tab separated with the country codes, the coordinates, the
+/// timezone and an optional comment
+fn find(table: &str, zone: &str) -> Option<Location> {
+    table
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| l.split('\t').collect::<Vec<_>>())
+        .find(|row| row.get(2) == Some(&zone))
+        .and_then(|row| parse_coordinates(row.get(1)?))
+}
+
+/// Coordinates in ISO 6709 format, either ±DDMM±DDDMM or ±DDMMSS±DDDMMSS
+fn parse_coordinates(s: &str) -> Option<Location> {
+    let i = s.get(1..)?.find(['+', '-'])? + 1;
+    let (lat, lon) = s.split_at(i);
+    let angle = |s: &str, n: usize| {
+        let (sign, digits) = s.split_at_checked(1)?;
+        let sign = match sign {
+            "+" => 1.0,
+            "-" => -1.0,
+            _ => return None,
+        };
+        if !matches!(digits.len().checked_sub(n), Some(2 | 4))
+            || !digits.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let (deg, rest) = digits.split_at(n);
+        let (min, sec) = rest.split_at(2);
+        let sec = sec.parse::<f64>().unwrap_or_default();
+        let v = deg.parse::<f64>().ok()? + min.parse::<f64>().ok()? / 60.0;
+        Some(sign * (v + sec / 3600.0))
+    };
+
+    Some(Location {
+        lat: angle(lat, 2)?.try_into().ok()?,
+        lon: angle(lon, 3)?.try_into().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timezone_lookup() {
+        let loc = |zone| {
+            let l = find(BUNDLED_TABLE, zone)?;
+            Some(((*l.lat * 1e4).round(), (*l.lon * 1e4).round()))
+        };
+        // +5230+01322
+        assert_eq!(loc("Europe/Berlin"), Some((525000.0, 133667.0)));
+        // -2332-04637
+        assert_eq!(loc("America/Sao_Paulo"), Some((-235333.0, -466167.0)));
+        // +404251-0740023
+        assert_eq!(loc("America/New_York"), Some((407142.0, -740064.0)));
+        assert_eq!(loc("Etc/UTC"), None);
+        assert!(parse_coordinates("+52-013").is_none());
+        assert!(parse_coordinates("5230+01322").is_none());
+    }
+}
@@ -0,0 +1,424 @@
+/*  snapshot.rs -- Gamma ramps snapshots
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    calc_colorramp::GammaRamps,
+    config::{Config, PKG_NAME},
+    error::{ReddishError, SnapshotError},
+    types::SnapshotMode,
+    types_display::{BODY, ERR, WARN},
+    Adjuster,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+// Directory of the named snapshots in the data directory
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Gamma ramps of the crtcs of an adjustment method. It is stored in TOML
+/// format with a table for each crtc, e.g.:
+/// ```toml
+/// method = "randr"
+///
+/// [[crtc]]
+/// id = 63
+/// red = [0, 64, 128, ...]
+/// green = [0, 64, 128, ...]
+/// blue = [0, 64, 128, ...]
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub method: String,
+    #[serde(rename = "crtc", default)]
+    pub crtcs: Vec<CrtcRamps>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrtcRamps {
+    pub id: u32,
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+}
+
+impl Snapshot {
+    pub fn new(method: String, ramps: Vec<(u32, GammaRamps)>) -> Self {
+        let crtcs = ramps
+            .into_iter()
+            .map(|(id, GammaRamps([red, green, blue]))| CrtcRamps {
+                id,
+                red,
+                green,
+                blue,
+            })
+            .collect();
+        Self { method, crtcs }
+    }
+
+    /// Crtcs with ramps of different or zero sizes are ignored
+    pub fn ramps(&self) -> Vec<(u32, GammaRamps)> {
+        self.crtcs
+            .iter()
+            .filter_map(|c| {
+                let n = c.red.len();
+                if n == 0 || c.green.len() != n || c.blue.len() != n {
+                    let s = "invalid gamma ramps of crtc";
+                    warn!("{WARN}warning:{WARN:#} {s} {}", c.id);
+                    return None;
+                }
+                let ramps = [c.red.clone(), c.green.clone(), c.blue.clone()];
+                Some((c.id, GammaRamps(ramps)))
+            })
+            .collect()
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SnapshotError> {
+        let s = fs::read_to_string(path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => SnapshotError::NotFound(path.into()),
+            _ => SnapshotError::ReadFailed(e, path.into()),
+        })?;
+        toml::from_str(&s)
+            .map_err(|e| SnapshotError::DeserializeFailed(e, path.into()))
+    }
+
+    /// The snapshot is written to a temporary file first so that a crash
+    /// cannot leave a partially written file behind
+    pub fn write(&self, path: &Path) -> Result<(), SnapshotError> {
+        let s = toml::to_string(self)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| SnapshotError::WriteFailed(e, dir.into()))?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        fs::write(&tmp, s)
+            .map_err(|e| SnapshotError::WriteFailed(e, tmp.clone()))?;
+        fs::rename(&tmp, path)
+            .map_err(|e| SnapshotError::WriteFailed(e, path.into()))
+    }
+}
+
+/// Ramps of a crtc in a list of ramps, resampled to the ramp size of the
+/// crtc. The saved ramps are used if the crtc is not in the list
+pub fn ramps_of(
+    ramps: &[(u32, GammaRamps)],
+    id: u32,
+    saved: &GammaRamps,
+) -> GammaRamps {
+    match ramps.iter().find(|(i, _)| *i == id) {
+        Some((_, r)) => r.resample(saved[0].len() as u32),
+        None => saved.clone(),
+    }
+}
+
+// Name of the adjustment method without its parameters, e.g. randr for
+// randr:0:HDMI-1
+fn method_name(key: &str) -> &str {
+    key.split(':').next().unwrap_or_default()
+}
+
+fn snapshots_dir() -> Result<PathBuf, SnapshotError> {
+    dirs::data_dir()
+        .map(|d| d.join(PKG_NAME).join(SNAPSHOTS_DIR))
+        .ok_or(SnapshotError::DataDirNotFound)
+}
+
+fn snapshot_path(name: &str) -> Result<PathBuf, SnapshotError> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        Err(SnapshotError::InvalidName(name.into()))?
+    }
+    Ok(snapshots_dir()?.join(name).with_extension("toml"))
+}
+
+pub fn run_snapshot_mode(
+    c: &Config,
+    mode: &SnapshotMode,
+) -> Result<(), ReddishError> {
+    match mode {
+        SnapshotMode::Save(name) => {
+            let path = snapshot_path(name)?;
+            let ramps = c.method.saved_ramps();
+            Snapshot::new(c.state_key.clone(), ramps).write(&path)?;
+            info!("Gamma ramps saved to {}", path.display());
+        }
+        SnapshotMode::Restore(name) => run_restore(c, name)?,
+        SnapshotMode::List => run_list()?,
+        SnapshotMode::Diff(name, other) => {
+            let a = Snapshot::read(&snapshot_path(name)?)?;
+            let (b, other) = match other {
+                Some(o) => (Snapshot::read(&snapshot_path(o)?)?, o.as_str()),
+                None => {
+                    let ramps = c.method.saved_ramps();
+                    (Snapshot::new(c.state_key.clone(), ramps), "current")
+                }
+            };
+            info!("{}", diff(&a.ramps(), name, &b.ramps(), other).join("\n"));
+        }
+    }
+    Ok(())
+}
+
+fn run_restore(c: &Config, name: &str) -> Result<(), ReddishError> {
+    let snapshot = Snapshot::read(&snapshot_path(name)?)?;
+    let method = method_name(&c.state_key);
+    // the file method emulates any display, e.g. to start a recording
+    if method_name(&snapshot.method) != method && method != "file" {
+        let s =
+            format!("snapshot was saved using the {} method", snapshot.method);
+        warn!("{WARN}warning:{WARN:#} {s}");
+    }
+
+    let ramps = snapshot.ramps();
+    let saved = c.method.saved_ramps();
+    for (id, _) in &ramps {
+        if !saved.iter().any(|(i, _)| i == id) {
+            let s = format!("crtc {id} of the snapshot is not selected");
+            warn!("{WARN}warning:{WARN:#} {s}");
+        }
+    }
+    Ok(c.method.set_ramps(&ramps)?)
+}
+
+fn run_list() -> Result<(), SnapshotError> {
+    let dir = snapshots_dir()?;
+    let paths = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| SnapshotError::ReadDirFailed(e, dir.clone()))?,
+        Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(e) => Err(SnapshotError::ReadDirFailed(e, dir.clone()))?,
+    };
+
+    let mut paths = paths
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect_vec();
+    paths.sort();
+    if paths.is_empty() {
+        info!("no snapshot found in {}", dir.display());
+    }
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        match Snapshot::read(&path) {
+            Ok(s) => {
+                let crtcs = s.crtcs.iter();
+                let crtcs =
+                    crtcs.map(|c| format!("{} ({})", c.id, c.red.len()));
+                let crtcs = match crtcs.collect_vec().join(", ") {
+                    crtcs if crtcs.is_empty() => "no crtcs".into(),
+                    crtcs => format!("crtcs {crtcs}"),
+                };
+                info!("{BODY}{name}{BODY:#}: {}, {crtcs}", s.method);
+            }
+            Err(e) => info!("{BODY}{name}{BODY:#}: {ERR}{e}{ERR:#}"),
+        }
+    }
+    Ok(())
+}
+
+/// Largest difference of each channel of the crtcs, in percent of the
+/// maximum value. Ramps of different sizes are resampled
+fn diff(
+    a: &[(u32, GammaRamps)],
+    a_name: &str,
+    b: &[(u32, GammaRamps)],
+    b_name: &str,
+) -> Vec<String> {
+    let ids = a.iter().chain(b).map(|(id, _)| *id).sorted().dedup();
+    ids.map(|id| {
+        let find = |r: &[(u32, GammaRamps)]| {
+            r.iter().find(|(i, _)| *i == id).map(|(_, r)| r.clone())
+        };
+        let s = match (find(a), find(b)) {
+            (Some(_), None) => format!("only in {a_name}"),
+            (None, Some(_)) => format!("only in {b_name}"),
+            (None, None) => String::new(),
+            (Some(a), Some(b)) => {
+                let b = b.resample(a[0].len() as u32);
+                let d = [0, 1, 2].map(|c| {
+                    let max =
+                        a[c].iter().zip(&b[c]).map(|(x, y)| x.abs_diff(*y));
+                    max.max().unwrap_or_default() as f64 / u16::MAX as f64
+                });
+                if d == [0.0; 3] {
+                    "identical".into()
+                } else {
+                    let [r, g, b] = d.map(|d| d * 100.0);
+                    format!("red {r:.2}%, green {g:.2}%, blue {b:.2}%")
+                }
+            }
+        };
+        format!("{BODY}crtc {id}{BODY:#}: {s}")
+    })
+    .collect()
+}
+
+// The original ramps of each adjustment method are kept until the runtime
+// directory is cleared, usually on logout
+fn original_path(key: &str) -> Result<PathBuf, SnapshotError> {
+    let name = format!("original-{}.toml", key.replace('/', "_"));
+    dirs::runtime_dir()
+        .map(|d| d.join(PKG_NAME).join(name))
+        .ok_or(SnapshotError::RuntimeDirNotFound)
+}
+
+/// Save the ramps of the crtcs that are not in the snapshot of the original
+/// ramps yet. Ramps that were saved before, e.g. by a process that did not
+/// exit cleanly or that selected other crtcs, are kept
+pub fn save_original(key: &str, ramps: Vec<(u32, GammaRamps)>) {
+    // e.g. the dummy method, which does not need the runtime directory
+    if ramps.is_empty() {
+        return;
+    }
+    let f = || merge_original(&original_path(key)?, key, ramps);
+    f().unwrap_or_else(|e| {
+        let s = "unable to save the original gamma ramps";
+        warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+    })
+}
+
+fn merge_original(
+    path: &Path,
+    key: &str,
+    ramps: Vec<(u32, GammaRamps)>,
+) -> Result<(), SnapshotError> {
+    let mut snapshot = match Snapshot::read(path) {
+        Ok(s) => s,
+        Err(SnapshotError::NotFound(_)) => Snapshot::default(),
+        Err(e) => return Err(e),
+    };
+    let len = snapshot.crtcs.len();
+    let new = Snapshot::new(key.into(), ramps);
+    for crtc in new.crtcs {
+        if !snapshot.crtcs.iter().any(|c| c.id == crtc.id) {
+            snapshot.crtcs.push(crtc);
+        }
+    }
+    if len == snapshot.crtcs.len() {
+        return Ok(());
+    }
+    snapshot.method = key.into();
+    snapshot.write(path)
+}
+
+pub fn load_original(key: &str) -> Result<Snapshot, SnapshotError> {
+    Snapshot::read(&original_path(key)?)
+}
+
+/// Remove the ramps of crtcs from the snapshot of the original ramps. The
+/// file is removed when no crtc is left
+pub fn remove_original(key: &str, ids: &[u32]) {
+    let f = || retain_original(&original_path(key)?, ids);
+    f().unwrap_or_else(|e| {
+        let s = "unable to remove the original gamma ramps";
+        warn!("{WARN}warning:{WARN:#} {s}:\n{e}");
+    })
+}
+
+fn retain_original(path: &Path, ids: &[u32]) -> Result<(), SnapshotError> {
+    let mut snapshot = Snapshot::read(path)?;
+    snapshot.crtcs.retain(|c| !ids.contains(&c.id));
+    if !snapshot.crtcs.is_empty() {
+        return snapshot.write(path);
+    }
+    fs::remove_file(path)
+        .map_err(|e| SnapshotError::WriteFailed(e, path.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{gamma_file::Recorder, types::ColorSettings};
+    use anyhow::Result;
+
+    #[test]
+    fn snapshot_toml() -> Result<()> {
+        let ramps = vec![(63, GammaRamps::new(4)), (64, GammaRamps::new(2))];
+        let s = toml::to_string(&Snapshot::new("randr".into(), ramps))?;
+        assert!(s.starts_with("method = \"randr\"\n\n[[crtc]]\nid = 63\n"));
+
+        let snapshot: Snapshot = toml::from_str(&s)?;
+        let ramps = snapshot.ramps();
+        assert_eq!(ramps.len(), 2);
+        assert_eq!(ramps[0].1[0], [0, 0x5555, 0xAAAA, 0xFFFF]);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_resample() {
+        let ramps = GammaRamps::new(256);
+        let up = ramps.resample(1024);
+        assert_eq!(up[0].len(), 1024);
+        assert_eq!(up[0].first(), ramps[0].first());
+        assert_eq!(up[0].last(), ramps[0].last());
+        let down = up.resample(256);
+        assert!(down.is_close_to(&ramps));
+
+        let restored = ramps_of(&[(1, ramps.clone())], 1, &up);
+        assert_eq!(restored[0].len(), 1024);
+        let unchanged = ramps_of(&[(2, ramps)], 1, &up);
+        assert_eq!(unchanged[0], up[0]);
+    }
+
+    #[test]
+    fn original_ramps() -> Result<()> {
+        let name =
+            format!("reddish-shift-test-{}-original", std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        let (path, recording) =
+            (dir.join("original.toml"), dir.join("rec.jsonl"));
+        fs::create_dir_all(&dir)?;
+        let ids = |path: &Path| -> Result<Vec<u32>> {
+            let crtcs = Snapshot::read(path)?.crtcs.into_iter();
+            Ok(crtcs.map(|c| c.id).collect())
+        };
+
+        let m = Recorder::new(recording.clone(), Some(4), vec![0, 1])?;
+        merge_original(&path, "file", m.saved_ramps())?;
+        let cs = ColorSettings {
+            temp: 3000.try_into()?,
+            ..Default::default()
+        };
+        m.set(true, &cs)?;
+
+        // a later process keeps the saved ramps and adds its other crtcs
+        let m = Recorder::new(recording.clone(), None, Vec::new())?;
+        let mut ramps = m.saved_ramps();
+        assert!(!ramps[0].1.is_close_to(&GammaRamps::new(4)));
+        ramps.push((2, GammaRamps::new(4)));
+        merge_original(&path, "file", ramps)?;
+        assert_eq!(ids(&path)?, [0, 1, 2]);
+        let saved = Snapshot::read(&path)?.ramps();
+        assert!(saved[0].1.is_close_to(&GammaRamps::new(4)));
+
+        retain_original(&path, &[0, 2])?;
+        assert_eq!(ids(&path)?, [1]);
+        retain_original(&path, &[1])?;
+        let removed = !path.exists();
+        fs::remove_dir_all(&dir)?;
+        assert!(removed);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+/*  state.rs -- Persisted color settings
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    config::PKG_NAME, error::StateError, types::ColorSettings,
+    types_display::WARN,
+};
+use std::{fs, io::ErrorKind, path::PathBuf};
+use toml::{Table, Value};
+use tracing::warn;
+
+// The last applied color settings of each adjustment method are stored in a
+// table named after the method and the ids of its crtcs, e.g.:
+// ["drm:0:51,70"]
+// temperature = 4500
+// gamma = "1:1:1"
+// brightness = 0.9
+const STATE_FILE: &str = "state.toml";
+
+fn path() -> Result<PathBuf, StateError> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|d| d.join(PKG_NAME).join(STATE_FILE))
+        .ok_or(StateError::StateDirNotFound)
+}
+
+fn read() -> Result<(Table, PathBuf), StateError> {
+    let path = path()?;
+    let s = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(StateError::ReadFailed(e, path)),
+    };
+    match toml::from_str(&s) {
+        Ok(t) => Ok((t, path)),
+        Err(e) => Err(StateError::DeserializeFailed(e, path)),
+    }
+}
+
+/// Last color settings that were applied using an adjustment method
+pub fn load(key: &str) -> Option<ColorSettings> {
+    let f = || -> Result<_, StateError> {
+        let (mut table, path) = read()?;
+        let Some(v) = table.remove(key) else {
+            return Ok(None);
+        };
+        v.try_into()
+            .map(Some)
+            .map_err(|e| StateError::DeserializeFailed(e, path))
+    };
+    f().unwrap_or_else(|e| {
+        warn!("{WARN}warning:{WARN:#} {e}");
+        None
+    })
+}
+
+/// Remember the color settings that were applied using an adjustment method.
+/// The entry is removed if the settings are None, e.g. after a reset
+pub fn save(key: &str, cs: Option<&ColorSettings>) {
+    let f = || -> Result<_, StateError> {
+        let (mut table, path) = read().or_else(|e| match e {
+            // an invalid state file is replaced
+            StateError::DeserializeFailed(_, path) => Ok((Table::new(), path)),
+            e => Err(e),
+        })?;
+
+        match cs {
+            Some(cs) => {
+                table.insert(key.into(), Value::try_from(cs)?);
+            }
+            None if table.remove(key).is_none() => return Ok(()),
+            None => {}
+        }
+
+        // write to a temporary file first so that a crash cannot leave a
+        // partially written state file behind
+        let s = toml::to_string(&table)?;
+        let tmp = path.with_extension("toml.tmp");
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| StateError::WriteFailed(e, dir.into()))?;
+        }
+        fs::write(&tmp, s)
+            .map_err(|e| StateError::WriteFailed(e, tmp.clone()))?;
+        fs::rename(&tmp, &path).map_err(|e| StateError::WriteFailed(e, path))
+    };
+    f().unwrap_or_else(|e| warn!("{WARN}warning:{WARN:#} {e}"))
+}
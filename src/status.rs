@@ -0,0 +1,75 @@
+/*  status.rs -- Estimation of the applied color settings
+    This file is part of <https://github.com/mahor1221/reddish-shift>.
+    Copyright (C) 2024 Mahor Foruzesh <mahor1221@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    config::Config,
+    error::ReddishError,
+    state,
+    types::ColorSettings,
+    types_display::{BODY, HEADER},
+    Adjuster,
+};
+use tracing::info;
+
+// Fit errors larger than this mean that the ramps were most likely not set
+// by reddish-shift on top of linear ramps
+pub const FIT_TOLERANCE: f64 = 0.01;
+
+pub fn run_status_mode(c: &Config) -> Result<(), ReddishError> {
+    let ramps = c.method.saved_ramps();
+    if ramps.is_empty() {
+        info!("no crtc to read the gamma ramps from");
+    }
+
+    let last = state::load(&c.state_key);
+    let is_estimable = last.as_ref().is_some_and(|l| estimable(l) == *l);
+    for (id, ramps) in ramps {
+        let (cs, error) = ramps.estimate();
+        let note = match &last {
+            Some(_) if !is_estimable => {
+                "the tint, effects and curve of the last applied color \
+                 settings can not be estimated"
+            }
+            _ if error > FIT_TOLERANCE => {
+                "not set by reddish-shift, or set on top of other ramps"
+            }
+            Some(last) if !cs.is_very_diff_from(last) => {
+                "matches the last applied color settings"
+            }
+            Some(_) => "differs from the last applied color settings",
+            None => "no color settings were applied by reddish-shift",
+        };
+        let error = error * 100.0;
+        info!(
+            "{HEADER}Crtc {id}{HEADER:#}:\n{cs}
+    {BODY}Fit error{BODY:#}: {error:.2}% ({note})"
+        );
+    }
+    Ok(())
+}
+
+/// Fields of the color settings that are estimated from the ramps. The tint,
+/// effects and curve are not
+fn estimable(cs: &ColorSettings) -> ColorSettings {
+    ColorSettings {
+        temp: cs.temp,
+        gamma: cs.gamma,
+        brght: cs.brght,
+        ..Default::default()
+    }
+}
@@ -18,13 +18,16 @@
 
 use crate::{
     calc_solar::{solar_elevation, SOLAR_CIVIL_TWILIGHT_ELEV},
+    curve::Curve,
     error::{types::*, ProviderError},
+    types_display::WARN,
     utils::{InjectErr, IntoGeneric},
     LocationProvider, Provider,
 };
 use chrono::{DateTime, Local, NaiveTime, Timelike};
 use frunk::{validated::IntoValidated, Generic};
-use std::ops::Deref;
+use std::{fmt::Display, ops::Deref, path::PathBuf};
+use tracing::warn;
 
 /// Angular elevation of the sun at which the color temperature transition
 /// period starts and ends (in degrees).
@@ -39,6 +42,13 @@ pub const DEFAULT_GAMMA: f64 = 1.0;
 pub const DEFAULT_TEMPERATURE: u16 = 6500;
 pub const DEFAULT_TEMPERATURE_DAY: u16 = 6500;
 pub const DEFAULT_TEMPERATURE_NIGHT: u16 = 4500;
+/// Named color temperatures, also usable as presets
+pub const TEMPERATURE_NAMES: [(&str, u16); 4] = [
+    ("candle", 1900),
+    ("incandescent", 2700),
+    ("halogen", 3400),
+    ("daylight", 6500),
+];
 
 pub const MIN_TEMPERATURE: u16 = 1000;
 pub const MAX_TEMPERATURE: u16 = 25000;
@@ -46,6 +56,12 @@ pub const MIN_BRIGHTNESS: f64 = 0.1;
 pub const MAX_BRIGHTNESS: f64 = 1.0;
 pub const MIN_GAMMA: f64 = 0.1;
 pub const MAX_GAMMA: f64 = 10.0;
+pub const DEFAULT_CONTRAST: f64 = 1.0;
+pub const MIN_CONTRAST: f64 = 0.1;
+pub const MAX_CONTRAST: f64 = 5.0;
+pub const MAX_BLACK_LEVEL: f64 = 0.5;
+pub const MIN_TINT: f64 = -0.05;
+pub const MAX_TINT: f64 = 0.05;
 pub const MIN_LATITUDE: f64 = -90.0;
 pub const MAX_LATITUDE: f64 = 90.0;
 pub const MIN_LONGITUDE: f64 = -180.0;
@@ -62,11 +78,39 @@ pub struct Brightness(f64);
 #[derive(Debug, Clone, Copy)]
 pub struct Gamma([f64; 3]);
 
+/// Distance of the white point from the Planckian locus (Duv). Positive
+/// values move it towards green and negative values towards magenta
+#[derive(Debug, Clone, Copy)]
+pub struct Tint(f64);
+
+/// Gain of the red, green and blue channels between 0 and 1. A muted channel
+/// has a gain of 0
+#[derive(Debug, Clone, Copy)]
+pub struct Channels([f64; 3]);
+
+/// Amount of inversion of the ramps between 0 and 1
+#[derive(Debug, Clone, Copy)]
+pub struct Invert(f64);
+
+/// Lowest value of the ramps, the rest of the ramps is scaled to fit above it
+#[derive(Debug, Clone, Copy)]
+pub struct BlackLevel(f64);
+
+/// Slope of the ramps around their middle value
+#[derive(Debug, Clone, Copy)]
+pub struct Contrast(f64);
+
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct ColorSettings {
     pub temp: Temperature,
+    pub tint: Tint,
     pub gamma: Gamma,
     pub brght: Brightness,
+    pub channels: Channels,
+    pub invert: Invert,
+    pub black: BlackLevel,
+    pub contrast: Contrast,
+    pub curve: Curve,
 }
 
 #[derive(Debug, Clone, Copy, Generic)]
@@ -111,14 +155,59 @@ pub struct Location {
     pub lon: Longitude,
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum Mode {
     #[default]
     Daemon,
     Oneshot,
     Set,
-    Reset,
+    Reset {
+        original: bool,
+    },
     Print,
+    List {
+        json: bool,
+    },
+    ConfigShow,
+    ConfigCheck,
+    ConfigImport(Option<PathBuf>),
+    Snapshot(SnapshotMode),
+    Status,
+    Export {
+        format: Option<ExportFormat>,
+        size: u16,
+        output: Option<PathBuf>,
+    },
+    Replay {
+        path: PathBuf,
+        delay: Option<u64>,
+    },
+}
+
+/// File formats of the export command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Cube,
+    Icc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotMode {
+    Save(String),
+    Restore(String),
+    List,
+    /// Compare a snapshot with another one or with the current gamma ramps
+    Diff(String, Option<String>),
+}
+
+/// Value of the set command, either absolute or relative to the last applied
+/// color settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetValue<T> {
+    Absolute(T),
+    Relative(f64),
+    Percent(f64),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -127,10 +216,12 @@ pub enum TransitionScheme {
     Elev(ElevationRange),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum LocationProviderType {
     Manual(Location),
     Geoclue2,
+    #[default]
+    Timezone,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -140,13 +231,13 @@ pub enum AdjustmentMethodType {
     #[cfg(unix_without_macos)]
     Drm {
         card_num: Option<usize>,
-        crtcs: Vec<u32>,
+        crtcs: Vec<CrtcSelector>,
     },
 
     #[cfg(unix_without_macos)]
     Randr {
         screen_num: Option<usize>,
-        crtcs: Vec<u32>,
+        crtcs: Vec<CrtcSelector>,
     },
 
     #[cfg(unix_without_macos)]
@@ -154,13 +245,66 @@ pub enum AdjustmentMethodType {
         screen_num: Option<usize>,
     },
 
+    #[cfg(unix_without_macos)]
+    Mutter {
+        crtcs: Vec<CrtcSelector>,
+    },
+
+    #[cfg(unix_without_macos)]
+    Kwin,
+
+    #[cfg(unix_without_macos)]
+    Vt {
+        tty_num: Option<usize>,
+    },
+
+    /// Emulated crtcs whose adjustments are appended to a file
+    #[cfg(unix_without_macos)]
+    File {
+        path: PathBuf,
+        ramp_size: Option<u32>,
+        crtcs: Vec<u32>,
+    },
+
     #[cfg(windows)]
     Win32Gdi,
 }
 
+/// Selects a crtc by its id or through the output that is connected to it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CrtcSelector {
+    Id(u32),
+    /// Name of a RandR output or a DRM connector, e.g. HDMI-1
+    Output(String),
+    /// Manufacturer id and optionally the serial number found in the EDID of
+    /// the connected monitor
+    Edid {
+        manufacturer: String,
+        serial: Option<String>,
+    },
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Alpha(f64);
 
+/// 3x3 color transformation matrix in row-major order. Each output channel
+/// is the dot product of its row and the input (red, green, blue) values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrix([f64; 9]);
+
+/// Result of comparing the ramps read back from a crtc with the ramps that
+/// were last written to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RampsStatus {
+    Intact,
+    /// Another program has changed the ramps
+    Overwritten,
+    /// Another program keeps changing the ramps to the same values
+    Foreign,
+    /// Foreign ramps are adopted as the new base
+    Adopted,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Period {
     Daytime,
@@ -179,6 +323,12 @@ pub struct DayNight<T> {
 pub type TemperatureRange = DayNight<Temperature>;
 pub type BrightnessRange = DayNight<Brightness>;
 pub type GammaRange = DayNight<Gamma>;
+pub type TintRange = DayNight<Tint>;
+pub type ChannelsRange = DayNight<Channels>;
+pub type InvertRange = DayNight<Invert>;
+pub type BlackLevelRange = DayNight<BlackLevel>;
+pub type ContrastRange = DayNight<Contrast>;
+pub type CurveRange = DayNight<Curve>;
 
 //
 
@@ -200,6 +350,55 @@ impl Default for Gamma {
     }
 }
 
+impl Default for Tint {
+    fn default() -> Self {
+        Self(0.0)
+    }
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self([1.0; 3])
+    }
+}
+
+impl Default for Invert {
+    fn default() -> Self {
+        Self(0.0)
+    }
+}
+
+impl Default for BlackLevel {
+    fn default() -> Self {
+        Self(0.0)
+    }
+}
+
+impl Default for Contrast {
+    fn default() -> Self {
+        Self(DEFAULT_CONTRAST)
+    }
+}
+
+impl ColorMatrix {
+    pub fn new(m: [f64; 9]) -> Self {
+        Self(m)
+    }
+
+    /// Mix each channel with the luminance (Rec. 709 weights) of the pixel.
+    /// 0 results in grayscale and values above 1 increase the saturation
+    pub fn saturation(s: f64) -> Self {
+        const LUMA: [f64; 3] = [0.2126, 0.7152, 0.0722];
+        let mut m = [0.0; 9];
+        for (i, v) in m.iter_mut().enumerate() {
+            let (row, col) = (i / 3, i % 3);
+            let identity = if row == col { 1.0 } else { 0.0 };
+            *v = (1.0 - s) * LUMA[col] + s * identity;
+        }
+        Self(m)
+    }
+}
+
 impl Default for Elevation {
     fn default() -> Self {
         Self(0.0)
@@ -249,12 +448,6 @@ impl Default for TransitionScheme {
     }
 }
 
-impl Default for LocationProviderType {
-    fn default() -> Self {
-        Self::Manual(Default::default())
-    }
-}
-
 impl Default for Period {
     fn default() -> Self {
         Self::Daytime
@@ -283,6 +476,15 @@ impl TryFrom<u16> for Temperature {
     }
 }
 
+impl Temperature {
+    pub fn from_name(name: &str) -> Option<Self> {
+        TEMPERATURE_NAMES
+            .into_iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, t)| Self(t))
+    }
+}
+
 impl TryFrom<f64> for Brightness {
     type Error = BrightnessError;
 
@@ -314,6 +516,159 @@ impl TryFrom<(f64, f64, f64)> for Gamma {
     }
 }
 
+pub fn channel(n: f64) -> Result<f64, ChannelError> {
+    if (0.0..=1.0).contains(&n) {
+        Ok(n)
+    } else {
+        Err(ChannelError(n))
+    }
+}
+
+impl TryFrom<(f64, f64, f64)> for Channels {
+    type Error = ChannelsRgbError;
+
+    fn try_from((r, g, b): (f64, f64, f64)) -> Result<Self, Self::Error> {
+        let (r, g, b) =
+            (channel(r).into_validated() + channel(g) + channel(b))
+                .into_result()?
+                .into_generic();
+        Ok(Self([r, g, b]))
+    }
+}
+
+impl Channels {
+    /// Channels named by a combination of the letters r, g and b are kept
+    /// and the rest are muted, e.g. r for red only night vision
+    pub fn from_letters(s: &str) -> Option<Self> {
+        if s.is_empty() || !s.chars().all(|c| "rgb".contains(c)) {
+            return None;
+        }
+        Some(Self(['r', 'g', 'b'].map(|c| match s.contains(c) {
+            true => 1.0,
+            false => 0.0,
+        })))
+    }
+}
+
+impl TryFrom<f64> for Tint {
+    type Error = TintError;
+
+    fn try_from(n: f64) -> Result<Self, Self::Error> {
+        if (MIN_TINT..=MAX_TINT).contains(&n) {
+            Ok(Self(n))
+        } else {
+            Err(TintError(n))
+        }
+    }
+}
+
+impl TryFrom<f64> for Invert {
+    type Error = InvertError;
+
+    fn try_from(n: f64) -> Result<Self, Self::Error> {
+        if (0.0..=1.0).contains(&n) {
+            Ok(Self(n))
+        } else {
+            Err(InvertError(n))
+        }
+    }
+}
+
+impl From<bool> for Invert {
+    fn from(b: bool) -> Self {
+        Self(if b { 1.0 } else { 0.0 })
+    }
+}
+
+impl TryFrom<f64> for BlackLevel {
+    type Error = BlackLevelError;
+
+    fn try_from(n: f64) -> Result<Self, Self::Error> {
+        if (0.0..=MAX_BLACK_LEVEL).contains(&n) {
+            Ok(Self(n))
+        } else {
+            Err(BlackLevelError(n))
+        }
+    }
+}
+
+impl TryFrom<f64> for Contrast {
+    type Error = ContrastError;
+
+    fn try_from(n: f64) -> Result<Self, Self::Error> {
+        if (MIN_CONTRAST..=MAX_CONTRAST).contains(&n) {
+            Ok(Self(n))
+        } else {
+            Err(ContrastError(n))
+        }
+    }
+}
+
+/// Types that can be changed by relative values of the set command
+pub trait Adjustable: Sized + Copy + Display {
+    const NAME: &'static str;
+    /// Map the value and clamp it to its range. Returns true if clamped
+    fn map(self, f: impl Fn(f64) -> f64) -> (Self, bool);
+}
+
+fn clamp(n: f64, min: f64, max: f64) -> (f64, bool) {
+    let c = n.clamp(min, max);
+    (c, c != n)
+}
+
+impl Adjustable for Temperature {
+    const NAME: &'static str = "temperature";
+    fn map(self, f: impl Fn(f64) -> f64) -> (Self, bool) {
+        let (min, max) = (MIN_TEMPERATURE as f64, MAX_TEMPERATURE as f64);
+        let (n, clamped) = clamp(f(self.0 as f64).round(), min, max);
+        (Self(n as u16), clamped)
+    }
+}
+
+impl Adjustable for Brightness {
+    const NAME: &'static str = "brightness";
+    fn map(self, f: impl Fn(f64) -> f64) -> (Self, bool) {
+        let (n, clamped) = clamp(f(self.0), MIN_BRIGHTNESS, MAX_BRIGHTNESS);
+        (Self(n), clamped)
+    }
+}
+
+impl Adjustable for Gamma {
+    const NAME: &'static str = "gamma";
+    fn map(self, f: impl Fn(f64) -> f64) -> (Self, bool) {
+        let c = self.0.map(|n| clamp(f(n), MIN_GAMMA, MAX_GAMMA));
+        (
+            Self(c.map(|(n, _)| n)),
+            c.iter().any(|(_, clamped)| *clamped),
+        )
+    }
+}
+
+impl<T: Adjustable> SetValue<T> {
+    pub fn is_relative(&self) -> bool {
+        !matches!(self, Self::Absolute(_))
+    }
+
+    /// Values out of range are clamped with a warning
+    pub fn apply(self, current: T) -> T {
+        let (t, clamped) = self.apply_clamped(current);
+        if clamped {
+            let s = format!("{} is clamped to {t}", T::NAME);
+            warn!("{WARN}warning:{WARN:#} {s}");
+        }
+        t
+    }
+
+    /// Returns true if the value is clamped to its range
+    pub fn apply_clamped(self, current: T) -> (T, bool) {
+        match self {
+            Self::Absolute(t) => (t, false),
+            Self::Relative(d) => current.map(|n| n + d),
+            Self::Percent(p) => current.map(|n| n * (1.0 + p / 100.0)),
+        }
+    }
+}
+
 impl TryFrom<f64> for Latitude {
     type Error = LatitudeError;
 
@@ -370,7 +725,7 @@ pub fn hour(h: u8) -> Result<u8, HourError> {
 }
 
 pub fn minute(m: u8) -> Result<u8, MinuteError> {
-    if m < 24 {
+    if m < 60 {
         Ok(m)
     } else {
         Err(MinuteError(m))
@@ -476,6 +831,40 @@ impl TryFrom<f64> for GammaRange {
     }
 }
 
+impl From<bool> for InvertRange {
+    fn from(b: bool) -> Self {
+        let t = Invert::from(b);
+        Self { day: t, night: t }
+    }
+}
+
+impl TryFrom<f64> for TintRange {
+    type Error = TintError;
+
+    fn try_from(n: f64) -> Result<Self, Self::Error> {
+        let t = Tint::try_from(n)?;
+        Ok(Self { day: t, night: t })
+    }
+}
+
+impl TryFrom<f64> for BlackLevelRange {
+    type Error = BlackLevelError;
+
+    fn try_from(n: f64) -> Result<Self, Self::Error> {
+        let t = BlackLevel::try_from(n)?;
+        Ok(Self { day: t, night: t })
+    }
+}
+
+impl TryFrom<f64> for ContrastRange {
+    type Error = ContrastError;
+
+    fn try_from(n: f64) -> Result<Self, Self::Error> {
+        let t = Contrast::try_from(n)?;
+        Ok(Self { day: t, night: t })
+    }
+}
+
 impl TryFrom<f64> for Alpha {
     type Error = AlphaError;
 
@@ -515,6 +904,48 @@ impl Deref for Gamma {
     }
 }
 
+impl Deref for Channels {
+    type Target = [f64; 3];
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for Invert {
+    type Target = f64;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for Tint {
+    type Target = f64;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for BlackLevel {
+    type Target = f64;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for Contrast {
+    type Target = f64;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for ColorMatrix {
+    type Target = [f64; 9];
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 impl Deref for TimeOffset {
     type Target = u32;
     fn deref(&self) -> &Self::Target {
@@ -581,6 +1012,31 @@ impl PartialEq for Gamma {
         eq(self[0], other[0]) && eq(self[1], other[1]) && eq(self[2], other[2])
     }
 }
+impl PartialEq for Channels {
+    fn eq(&self, other: &Self) -> bool {
+        eq(self[0], other[0]) && eq(self[1], other[1]) && eq(self[2], other[2])
+    }
+}
+impl PartialEq for Tint {
+    fn eq(&self, other: &Self) -> bool {
+        eq(**self, **other)
+    }
+}
+impl PartialEq for Invert {
+    fn eq(&self, other: &Self) -> bool {
+        eq(**self, **other)
+    }
+}
+impl PartialEq for BlackLevel {
+    fn eq(&self, other: &Self) -> bool {
+        eq(**self, **other)
+    }
+}
+impl PartialEq for Contrast {
+    fn eq(&self, other: &Self) -> bool {
+        eq(**self, **other)
+    }
+}
 
 //
 
@@ -699,21 +1155,48 @@ impl ColorSettings {
         let temp = Temperature(
             ((1.0 - a) * *self.temp as f64 + a * *other.temp as f64) as u16,
         );
+        let tint = Tint((1.0 - a) * *self.tint + a * *other.tint);
         let gamma = Gamma(
             [0, 1, 2].map(|i| (1.0 - a) * self.gamma[i] + a * other.gamma[i]),
         );
         let brght = Brightness((1.0 - a) * *self.brght + a * *other.brght);
-
-        ColorSettings { temp, gamma, brght }
+        let channels =
+            Channels([0, 1, 2].map(|i| {
+                (1.0 - a) * self.channels[i] + a * other.channels[i]
+            }));
+        let invert = Invert((1.0 - a) * *self.invert + a * *other.invert);
+        let black = BlackLevel((1.0 - a) * *self.black + a * *other.black);
+        let contrast =
+            Contrast((1.0 - a) * *self.contrast + a * *other.contrast);
+        let curve = self.curve.interpolate_with(&other.curve, a);
+
+        ColorSettings {
+            temp,
+            tint,
+            gamma,
+            brght,
+            channels,
+            invert,
+            black,
+            contrast,
+            curve,
+        }
     }
 
     /// Return true if color settings have major differences
     /// Used to determine if a fade should be applied in continual mode
     pub fn is_very_diff_from(&self, other: &Self) -> bool {
         (*self.temp as i16 - *other.temp as i16).abs() > 25
+            || (*self.tint - *other.tint).abs() > 0.002
             || (*self.brght - *other.brght).abs() > 0.1
             || (self.gamma[0] - other.gamma[0]).abs() > 0.1
             || (self.gamma[1] - other.gamma[1]).abs() > 0.1
             || (self.gamma[2] - other.gamma[2]).abs() > 0.1
+            || (0..3)
+                .any(|i| (self.channels[i] - other.channels[i]).abs() > 0.1)
+            || (*self.invert - *other.invert).abs() > 0.1
+            || (*self.black - *other.black).abs() > 0.02
+            || (*self.contrast - *other.contrast).abs() > 0.1
+            || self.curve.max_diff(&other.curve) > 0.02
     }
 }
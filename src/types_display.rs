@@ -18,14 +18,17 @@
 
 use crate::{
     config::Config,
+    curve::Curve,
     types::{
-        Brightness, ColorSettings, Elevation, ElevationRange, Gamma, Location,
-        Period, PeriodInfo, Temperature, Time, TimeOffset, TimeRange,
-        TimeRanges, TransitionScheme,
+        AdjustmentMethodType, BlackLevel, Brightness, Channels, ColorSettings,
+        Contrast, CrtcSelector, Elevation, ElevationRange, Gamma, Invert,
+        Location, Period, PeriodInfo, Temperature, Time, TimeOffset,
+        TimeRange, TimeRanges, Tint, TransitionScheme,
     },
     AdjustmentMethod, DaemonMode, FadeStatus, LocationProvider,
 };
 use anstyle::{AnsiColor, Color, Style};
+use itertools::Itertools;
 use std::fmt::{self, Display, Formatter};
 use tracing::info;
 
@@ -60,7 +63,7 @@ impl Display for Brightness {
 struct BrightnessDisplay<'a>(&'a Brightness);
 impl Display for BrightnessDisplay<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}%", **self.0 as u8 * 100)
+        write!(f, "{}%", (**self.0 * 100.0).round() as u8)
     }
 }
 
@@ -70,6 +73,64 @@ impl Display for Gamma {
     }
 }
 
+/// Letters of the channels that are kept if the others are muted
+impl Display for Channels {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let letters = ['r', 'g', 'b']
+            .into_iter()
+            .zip(self.iter())
+            .filter(|(_, n)| **n == 1.0)
+            .map(|(c, _)| c)
+            .collect::<String>();
+        match self.iter().all(|n| *n == 0.0 || *n == 1.0)
+            && !letters.is_empty()
+        {
+            true => f.write_str(&letters),
+            false => write!(f, "{:.2}:{:.2}:{:.2}", self[0], self[1], self[2]),
+        }
+    }
+}
+
+impl Display for Invert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if **self == 0.0 {
+            f.write_str("false")
+        } else if **self == 1.0 {
+            f.write_str("true")
+        } else {
+            write!(f, "{}", **self)
+        }
+    }
+}
+
+impl Display for BlackLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", **self)
+    }
+}
+
+impl Display for Contrast {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", **self)
+    }
+}
+
+impl Display for Tint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", **self)
+    }
+}
+
+impl Display for Curve {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.path() {
+            Some(path) => write!(f, "{}", path.display()),
+            None if self.is_linear() => f.write_str("linear"),
+            None => f.write_str("blended"),
+        }
+    }
+}
+
 struct GammaDisplay<'a>(&'a Gamma);
 impl Display for GammaDisplay<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -77,6 +138,81 @@ impl Display for GammaDisplay<'_> {
     }
 }
 
+impl Display for CrtcSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{id}"),
+            Self::Output(name) => write!(f, "{name}"),
+            Self::Edid {
+                manufacturer,
+                serial: None,
+            } => write!(f, "edid={manufacturer}"),
+            Self::Edid {
+                manufacturer,
+                serial: Some(serial),
+            } => write!(f, "edid={manufacturer}/{serial}"),
+        }
+    }
+}
+
+impl Display for AdjustmentMethodType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Dummy => "dummy".into(),
+            #[cfg(unix_without_macos)]
+            Self::Drm { card_num, crtcs } => {
+                method_param("drm", card_num, crtcs)
+            }
+            #[cfg(unix_without_macos)]
+            Self::Randr { screen_num, crtcs } => {
+                method_param("randr", screen_num, crtcs)
+            }
+            #[cfg(unix_without_macos)]
+            Self::Vidmode { screen_num } => {
+                method_param("vidmode", screen_num, &[])
+            }
+            #[cfg(unix_without_macos)]
+            Self::Mutter { crtcs } => method_param("mutter", &None, crtcs),
+            #[cfg(unix_without_macos)]
+            Self::Kwin => "kwin".into(),
+            #[cfg(unix_without_macos)]
+            Self::Vt { tty_num } => method_param("vt", tty_num, &[]),
+            #[cfg(unix_without_macos)]
+            Self::File {
+                path,
+                ramp_size,
+                crtcs,
+            } => {
+                let name = format!("file:{}", path.display());
+                let crtcs = crtcs.iter().map(|&id| CrtcSelector::Id(id));
+                method_param(
+                    &name,
+                    &ramp_size.map(|n| n as usize),
+                    &crtcs.collect_vec(),
+                )
+            }
+            #[cfg(windows)]
+            Self::Win32Gdi => "win32gdi".into(),
+        };
+        f.write_str(&s)
+    }
+}
+
+/// Name of a method followed by its optional parameters, separated by colons
+#[cfg(unix_without_macos)]
+fn method_param(
+    name: &str,
+    n: &Option<usize>,
+    crtcs: &[CrtcSelector],
+) -> String {
+    let n = n.map(|n| n.to_string()).unwrap_or_default();
+    match crtcs.is_empty() {
+        true if n.is_empty() => name.to_string(),
+        true => format!("{name}:{n}"),
+        false => format!("{name}:{n}:{}", crtcs.iter().join(",")),
+    }
+}
+
 impl Display for Time {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let Time { hour: h, minute: m } = self;
@@ -132,7 +268,9 @@ struct ColorSettingsDisplay<'a> {
 
 impl<'a> From<&'a ColorSettings> for ColorSettingsDisplay<'a> {
     fn from(cs: &'a ColorSettings) -> Self {
-        let ColorSettings { temp, gamma, brght } = cs;
+        let ColorSettings {
+            temp, gamma, brght, ..
+        } = cs;
         let temp = TemperatureDisplay(temp);
         let gamma = GammaDisplay(gamma);
         let brght = BrightnessDisplay(brght);
@@ -145,10 +283,44 @@ impl Display for ColorSettings {
         let ColorSettingsDisplay { temp, gamma, brght } = self.into();
         write!(
             f,
-            "    {BODY}Temperature{BODY:#}: {temp}K
-    {BODY}Brightness{BODY:#}: {brght}%
-    {BODY}Gamma{BODY:#}: {gamma}"
-        )
+            "    {BODY}Temperature{BODY:#}: {temp}
+    {BODY}Tint{BODY:#}: {}
+    {BODY}Brightness{BODY:#}: {brght}
+    {BODY}Gamma{BODY:#}: {gamma}",
+            self.tint
+        )?;
+        for (name, effect) in self.effects(&Self::default()) {
+            write!(f, "\n    {BODY}{name}{BODY:#}: {effect}")?;
+        }
+        Ok(())
+    }
+}
+
+impl ColorSettings {
+    /// Names and values of the effects that differ from another color
+    /// settings
+    fn effects(&self, other: &Self) -> Vec<(&'static str, String)> {
+        [
+            (
+                "Channels",
+                self.channels != other.channels,
+                &self.channels as _,
+            ),
+            ("Invert", self.invert != other.invert, &self.invert as _),
+            ("Black level", self.black != other.black, &self.black as _),
+            (
+                "Contrast",
+                self.contrast != other.contrast,
+                &self.contrast as _,
+            ),
+            ("Curve", self.curve != other.curve, &self.curve as _),
+        ]
+        .into_iter()
+        .filter(|(_, diff, _)| *diff)
+        .map(|(name, _, effect): (_, _, &dyn Display)| {
+            (name, effect.to_string())
+        })
+        .collect()
     }
 }
 
@@ -192,6 +364,14 @@ impl Display for AdjustmentMethod {
             AdjustmentMethod::Drm(_) => "drm",
             #[cfg(unix_without_macos)]
             AdjustmentMethod::Vidmode(_) => "vidmode",
+            #[cfg(unix_without_macos)]
+            AdjustmentMethod::Mutter(_) => "mutter",
+            #[cfg(unix_without_macos)]
+            AdjustmentMethod::Kwin(_) => "kwin",
+            #[cfg(unix_without_macos)]
+            AdjustmentMethod::Vt(_) => "vt",
+            #[cfg(unix_without_macos)]
+            AdjustmentMethod::File(_) => "file",
             #[cfg(windows)]
             AdjustmentMethod::Win32Gdi(_) => "win32gdi",
         };
@@ -201,11 +381,13 @@ impl Display for AdjustmentMethod {
 
 impl Display for LocationProvider {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let s = match self {
-            LocationProvider::Manual(_) => "manual",
-            LocationProvider::Geoclue2(_) => "geoclue2",
-        };
-        write!(f, "{s}")
+        match self {
+            LocationProvider::Manual(_) => write!(f, "manual"),
+            LocationProvider::Geoclue2(_) => write!(f, "geoclue2"),
+            LocationProvider::Timezone(t) => {
+                write!(f, "timezone ({})", t.zone())
+            }
+        }
     }
 }
 
@@ -222,8 +404,13 @@ impl Display for Config {
             disable_fade,
             sleep_duration_short,
             sleep_duration,
+            monitor_ramps,
+            adopt_foreign_ramps,
+            fade: _,
             mode: _,
             time: _,
+            state_key: _,
+            original_key: _,
         } = self;
 
         writeln!(f, "{BODY}Adjustment method{BODY:#}: {method}")?;
@@ -234,6 +421,9 @@ impl Display for Config {
         writeln!(f, "{BODY}Sleep duration{BODY:#}: {s}ms")?;
         let s = sleep_duration_short.as_millis();
         writeln!(f, "{BODY}Sleep duration short{BODY:#}: {s}ms")?;
+        writeln!(f, "{BODY}Monitor ramps{BODY:#}: {monitor_ramps}")?;
+        let s = adopt_foreign_ramps;
+        writeln!(f, "{BODY}Adopt foreign ramps{BODY:#}: {s}")?;
 
         writeln!(f, "{HEADER}Daytime{HEADER:#}:")?;
         match scheme {
@@ -304,16 +494,24 @@ impl DaemonMode<'_, '_> {
             (&self.interp).into();
         if self.fade == FadeStatus::Completed || self.prev_interp.is_none() {
             if Some(temp.0) != self.prev_interp.as_ref().map(|c| &c.temp) {
-                info!("    {BODY}Temperature{BODY:#}: {temp}K");
+                info!("    {BODY}Temperature{BODY:#}: {temp}");
+            }
+            let tint = &self.interp.tint;
+            if Some(tint) != self.prev_interp.as_ref().map(|c| &c.tint) {
+                info!("    {BODY}Tint{BODY:#}: {tint}");
             }
             if Some(gamma.0) != self.prev_interp.as_ref().map(|c| &c.gamma) {
                 info!("    {BODY}Gamma{BODY:#}: {gamma}");
             }
             if Some(brght.0) != self.prev_interp.as_ref().map(|c| &c.brght) {
-                info!("    {BODY}Brightness{BODY:#}: {brght}%");
+                info!("    {BODY}Brightness{BODY:#}: {brght}");
+            }
+            let prev = self.prev_interp.clone().unwrap_or_default();
+            for (name, effect) in self.interp.effects(&prev) {
+                info!("    {BODY}{name}{BODY:#}: {effect}");
             }
         } else if Some(temp.0) != self.prev_interp.as_ref().map(|c| &c.temp) {
-            info!("    {BODY}Temperature{BODY:#}: {temp}K");
+            info!("    {BODY}Temperature{BODY:#}: {temp}");
         }
     }
 }
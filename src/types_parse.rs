@@ -19,20 +19,24 @@
 use crate::{
     error::{gamma::CrtcError, parse::*},
     types::{
-        gamma, hour, minute, AdjustmentMethodType, Brightness, DayNight,
-        Elevation, ElevationRange, Gamma, Latitude, Location,
-        LocationProviderType, Longitude, Temperature, Time, TimeOffset,
-        TimeRange, TimeRanges, TransitionScheme,
+        channel, gamma, hour, minute, AdjustmentMethodType, BlackLevel,
+        Brightness, Channels, ColorMatrix, Contrast, CrtcSelector, DayNight,
+        Elevation, ElevationRange, ExportFormat, Gamma, Invert, Latitude,
+        Location, LocationProviderType, Longitude, SetValue, Temperature,
+        Time, TimeOffset, TimeRange, TimeRanges, Tint, TransitionScheme,
     },
     utils::{CollectResult, InjectErr, IntoGeneric},
 };
 use frunk::validated::IntoValidated;
-use std::str::FromStr;
+use std::{error::Error, str::FromStr};
 
 impl FromStr for Temperature {
     type Err = TemperatureError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(t) = Self::from_name(s.trim()) {
+            return Ok(t);
+        }
         Ok(s.trim()
             .parse::<u16>()
             .map_err(|e| TemperatureError::Parse(e, s.into()))?
@@ -71,6 +75,102 @@ impl FromStr for Gamma {
     }
 }
 
+impl FromStr for Channels {
+    type Err = ChannelsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(t) = Self::from_letters(s.trim()) {
+            return Ok(t);
+        }
+        let f = |s: &str| -> Result<f64, ChannelsErrorT> {
+            channel(s.parse::<f64>().inject_err()?).inject_err()
+        };
+
+        match *s.split(':').map(str::trim).collect::<Vec<_>>().as_slice() {
+            [r, g, b] => Ok((f(r).into_validated() + f(g) + f(b))
+                .into_result()?
+                .into_generic::<(f64, f64, f64)>()
+                .try_into()
+                .unwrap_or_else(|_| unreachable!())),
+            [rgb] => {
+                let n = f(rgb)?;
+                Ok((n, n, n).try_into().unwrap_or_else(|_| unreachable!()))
+            }
+            _ => Err(ChannelsError::Fmt),
+        }
+    }
+}
+
+impl FromStr for Tint {
+    type Err = TintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.trim()
+            .parse::<f64>()
+            .map_err(|e| TintError::Parse(e, s.into()))?
+            .try_into()?)
+    }
+}
+
+impl FromStr for Invert {
+    type Err = InvertError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(b) = s.trim().parse::<bool>() {
+            return Ok(b.into());
+        }
+        Ok(s.trim()
+            .parse::<f64>()
+            .map_err(|e| InvertError::Parse(e, s.into()))?
+            .try_into()?)
+    }
+}
+
+impl FromStr for BlackLevel {
+    type Err = BlackLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.trim()
+            .parse::<f64>()
+            .map_err(|e| BlackLevelError::Parse(e, s.into()))?
+            .try_into()?)
+    }
+}
+
+impl FromStr for Contrast {
+    type Err = ContrastError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.trim()
+            .parse::<f64>()
+            .map_err(|e| ContrastError::Parse(e, s.into()))?
+            .try_into()?)
+    }
+}
+
+impl FromStr for ColorMatrix {
+    type Err = ColorMatrixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let f = |s: &str| {
+            s.parse::<f64>()
+                .map_err(|e| ColorMatrixError::Parse(e, s.into()))
+        };
+
+        let v = s
+            .split(',')
+            .map(str::trim)
+            .map(f)
+            .collect::<Result<Vec<_>, _>>()?;
+        match *v.as_slice() {
+            [s] => Ok(Self::saturation(s)),
+            _ => {
+                Ok(Self::new(v.try_into().map_err(|_| ColorMatrixError::Fmt)?))
+            }
+        }
+    }
+}
+
 impl FromStr for Latitude {
     type Err = LatitudeError;
 
@@ -213,12 +313,26 @@ impl FromStr for TransitionScheme {
     }
 }
 
+impl FromStr for ExportFormat {
+    type Err = ExportFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "cube" => Ok(Self::Cube),
+            "icc" | "icm" => Ok(Self::Icc),
+            _ => Err(ExportFormatError(s.into())),
+        }
+    }
+}
+
 impl FromStr for LocationProviderType {
     type Err = LocationProviderError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "geoclue2" => Ok(Self::Geoclue2),
+            "timezone" => Ok(Self::Timezone),
             _ => s
                 .parse()
                 .map(Self::Manual)
@@ -227,6 +341,62 @@ impl FromStr for LocationProviderType {
     }
 }
 
+impl<T: FromStr> FromStr for SetValue<T>
+where
+    T::Err: Error,
+{
+    type Err = SetValueError<T::Err>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let f = |n: &str| {
+            n.parse::<f64>()
+                .map_err(|e| SetValueError::Relative(e, s.into()))
+        };
+        match s.strip_suffix('%') {
+            _ if !s.starts_with(['+', '-']) => {
+                Ok(Self::Absolute(s.parse().map_err(SetValueError::Absolute)?))
+            }
+            Some(p) => Ok(Self::Percent(f(p)?)),
+            None => Ok(Self::Relative(f(s)?)),
+        }
+    }
+}
+
+impl FromStr for CrtcSelector {
+    type Err = CrtcSelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse() {
+            return Ok(Self::Id(id));
+        }
+
+        match s.split_once('=') {
+            Some(("edid", edid)) => {
+                let (manufacturer, serial) = match edid.split_once('/') {
+                    Some((m, s)) => (m, Some(s.to_string())),
+                    None => (edid, None),
+                };
+                if manufacturer.len() != 3
+                    || !manufacturer.chars().all(|c| c.is_ascii_alphabetic())
+                {
+                    Err(CrtcSelectorError::Manufacturer)?
+                }
+                if serial.as_ref().is_some_and(String::is_empty) {
+                    Err(CrtcSelectorError::Serial)?
+                }
+                Ok(Self::Edid {
+                    manufacturer: manufacturer.to_ascii_uppercase(),
+                    serial,
+                })
+            }
+            Some(_) => Err(CrtcSelectorError::Fmt),
+            None if s.is_empty() => Err(CrtcSelectorError::Fmt),
+            None => Ok(Self::Output(s.to_string())),
+        }
+    }
+}
+
 impl FromStr for AdjustmentMethodType {
     type Err = AdjustmentMethodTypeError;
 
@@ -246,19 +416,25 @@ impl FromStr for AdjustmentMethodType {
                 screen_num: None,
                 crtcs: vec![],
             }),
+            #[cfg(unix_without_macos)]
+            "mutter" => Ok(Self::Mutter { crtcs: vec![] }),
+            #[cfg(unix_without_macos)]
+            "kwin" => Ok(Self::Kwin),
+            #[cfg(unix_without_macos)]
+            "vt" => Ok(Self::Vt { tty_num: None }),
             #[cfg(windows)]
             "win32gdi" => Ok(Self::Win32Gdi),
             _ => Err(AdjustmentMethodTypeParamError::InvalidName(s.into())),
         };
 
         let num = |o: Option<&str>| match o {
-            None => Ok(None),
+            None | Some("") => Ok(None),
             Some(s) => Ok(Some(s.parse::<usize>().map_err(|e| {
                 AdjustmentMethodTypeParamError::Display(e, s.into())
             })?)),
         };
         let crtcs = |o: Option<&str>| match o {
-            None => Ok(Vec::<u32>::new()),
+            None => Ok(Vec::<CrtcSelector>::new()),
             Some(s) => Ok(s
                 .split(',')
                 .map(|id| {
@@ -270,6 +446,43 @@ impl FromStr for AdjustmentMethodType {
                 .collect_result()?),
         };
 
+        #[cfg(unix_without_macos)]
+        let ramp_size = |o: Option<&str>| match o {
+            None | Some("") => Ok(None),
+            Some(s) => match s.parse::<u32>() {
+                Ok(n) if n >= 2 => Ok(Some(n)),
+                _ => Err(AdjustmentMethodTypeParamError::RampSize(s.into())),
+            },
+        };
+        // file:PATH[:RAMP_SIZE[:CRTC1,CRTC2,...]], the path cannot contain
+        // colons. Emulated crtcs are selected by their ids only
+        #[cfg(unix_without_macos)]
+        let file = |p: &[&str]| {
+            if p.len() > 3 {
+                Err(AdjustmentMethodTypeError::Fmt)?
+            }
+            let [path, n, c] = [0, 1, 2].map(|i| p.get(i).copied());
+            let path = path
+                .filter(|p| !p.is_empty())
+                .ok_or(AdjustmentMethodTypeError::MissingPath)?;
+            let (ramp_size, crtcs) = (ramp_size(n).into_validated()
+                + crtcs(c))
+            .into_result()?
+            .into_generic::<(_, _)>();
+            let crtcs = crtcs
+                .into_iter()
+                .map(|c| match c {
+                    CrtcSelector::Id(id) => Ok(id),
+                    _ => Err(AdjustmentMethodTypeError::EmulatedCrtcs),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Self::File {
+                path: path.into(),
+                ramp_size,
+                crtcs,
+            })
+        };
+
         let f = |k: &str, n: Option<&str>, c: Option<&str>| {
             let (mut k, n, c) = (kind(k).into_validated() + num(n) + crtcs(c))
                 .into_result()?
@@ -294,6 +507,32 @@ impl FromStr for AdjustmentMethodType {
                         Err(AdjustmentMethodTypeError::SelectingCrtcNotSupported)?
                     }
                 }
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::Mutter { crtcs } => {
+                    if n.is_some() {
+                        Err(AdjustmentMethodTypeError::SelectingDisplayNotSupported)?
+                    }
+                    *crtcs = c;
+                }
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::Kwin => {
+                    if n.is_some() {
+                        Err(AdjustmentMethodTypeError::SelectingDisplayNotSupported)?
+                    }
+                    if !c.is_empty() {
+                        Err(AdjustmentMethodTypeError::SelectingCrtcNotSupported)?
+                    }
+                }
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::Vt { tty_num } => {
+                    *tty_num = n;
+                    if !c.is_empty() {
+                        Err(AdjustmentMethodTypeError::SelectingCrtcNotSupported)?
+                    }
+                }
+                // parsed separately
+                #[cfg(unix_without_macos)]
+                AdjustmentMethodType::File { .. } => {}
 
                 #[cfg(windows)]
                 AdjustmentMethodType::Win32Gdi => {
@@ -310,6 +549,8 @@ impl FromStr for AdjustmentMethodType {
         };
 
         match s.split(':').map(str::trim).collect::<Vec<_>>().as_slice() {
+            #[cfg(unix_without_macos)]
+            ["file", p @ ..] => file(p),
             [k] => f(k, None, None),
             [k, n] => f(k, Some(n), None),
             [k, n, c] => f(k, Some(n), Some(c)),
@@ -318,6 +559,26 @@ impl FromStr for AdjustmentMethodType {
     }
 }
 
+/// Split on the dashes between the day and night values. A dash at the start
+/// of a value is its sign, e.g. 0.01--0.01 for a negative tint at night
+fn split_day_night(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut sign) = (0, true);
+    for (i, c) in s.char_indices() {
+        match c {
+            '-' if !sign => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+                sign = true;
+            }
+            c if c.is_whitespace() => {}
+            _ => sign = false,
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
 impl<E, T> FromStr for DayNight<T>
 where
     E: DayNightErrorType,
@@ -326,7 +587,7 @@ where
     type Err = DayNightError<E>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match *s.split('-').collect::<Vec<_>>().as_slice() {
+        match *split_day_night(s).as_slice() {
             [day_night] => {
                 let day_night = day_night.parse::<T>()?;
                 Ok(Self {
@@ -341,3 +602,84 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn crtc_selector() -> Result<()> {
+        assert_eq!("3".parse::<CrtcSelector>()?, CrtcSelector::Id(3));
+        assert_eq!(
+            "HDMI-1".parse::<CrtcSelector>()?,
+            CrtcSelector::Output("HDMI-1".into())
+        );
+        assert_eq!(
+            "edid=del".parse::<CrtcSelector>()?,
+            CrtcSelector::Edid {
+                manufacturer: "DEL".into(),
+                serial: None,
+            }
+        );
+        assert_eq!(
+            "edid=DEL/ABC/1".parse::<CrtcSelector>()?,
+            CrtcSelector::Edid {
+                manufacturer: "DEL".into(),
+                serial: Some("ABC/1".into()),
+            }
+        );
+
+        let err = |s: &str| s.parse::<CrtcSelector>().err();
+        assert!(matches!(
+            err("edid=DE"),
+            Some(CrtcSelectorError::Manufacturer)
+        ));
+        assert!(matches!(
+            err("edid=D3L"),
+            Some(CrtcSelectorError::Manufacturer)
+        ));
+        assert!(matches!(err("edid=DEL/"), Some(CrtcSelectorError::Serial)));
+        assert!(matches!(err("output=DP-1"), Some(CrtcSelectorError::Fmt)));
+        assert!(matches!(err(""), Some(CrtcSelectorError::Fmt)));
+        Ok(())
+    }
+
+    #[test]
+    fn set_value() -> Result<()> {
+        let temp = |s: &str| s.parse::<SetValue<Temperature>>();
+        let brght = |s: &str| s.parse::<SetValue<Brightness>>();
+        let gamma = |s: &str| s.parse::<SetValue<Gamma>>();
+        assert_eq!(temp("+300")?, SetValue::Relative(300.0));
+        assert_eq!(temp(" 4000 ")?, SetValue::Absolute(4000.try_into()?));
+        assert_eq!(temp("candle")?, SetValue::Absolute(1900.try_into()?));
+        assert_eq!(brght("-10%")?, SetValue::Percent(-10.0));
+        assert_eq!(gamma("-0.1")?, SetValue::Relative(-0.1));
+        assert!(matches!(temp("+x"), Err(SetValueError::Relative(..))));
+        assert!(matches!(temp("-x%"), Err(SetValueError::Relative(..))));
+        assert!(matches!(temp("100"), Err(SetValueError::Absolute(_))));
+
+        let t = Temperature::try_from(6500)?;
+        assert_eq!(temp("+300")?.apply(t), 6800.try_into()?);
+        assert_eq!(temp("4000")?.apply(t), 4000.try_into()?);
+        let b = Brightness::try_from(0.5)?;
+        assert_eq!(brght("-10%")?.apply(b), 0.45.try_into()?);
+        let g = Gamma::try_from(1.0)?;
+        assert_eq!(gamma("-0.1")?.apply(g), 0.9.try_into()?);
+        Ok(())
+    }
+
+    #[test]
+    fn set_value_clamped() -> Result<()> {
+        let t = Temperature::try_from(1200)?;
+        let (t, clamped) = SetValue::Relative(-500.0).apply_clamped(t);
+        assert_eq!((t, clamped), (1000.try_into()?, true));
+        let b = Brightness::try_from(0.9)?;
+        let (b, clamped) = SetValue::Percent(50.0).apply_clamped(b);
+        assert_eq!((b, clamped), (1.0.try_into()?, true));
+        let g = Gamma::try_from(1.0)?;
+        let (g, clamped) = SetValue::Relative(0.5).apply_clamped(g);
+        assert_eq!((g, clamped), (1.5.try_into()?, false));
+        Ok(())
+    }
+}